@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use lib0::any::Any;
+use lib0::decoding::{Cursor, Read};
+use lib0::encoding::Write;
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use crate::json_builder::JsonBuilder;
+use crate::shared_types::CompatiblePyType;
+use crate::type_conversions::ToPython;
+use crate::y_doc::YDoc;
+use crate::y_transaction::{try_decode, Binary, EncodingException};
+
+/// Keeps track of presence information (like cursor positions or user names) of all peers
+/// collaborating on a document, following the Yjs awareness protocol. Unlike document contents,
+/// awareness states are not persisted: every client owns its own state, which replaces any of its
+/// previous states.
+///
+/// Local state changes are propagated by passing `encode_awareness_update` payloads to other peers,
+/// which then apply them with `apply_awareness_update`.
+#[pyclass(unsendable)]
+pub struct Awareness {
+    client_id: u64,
+    /// Known states of all clients. Clients which went offline have no entry here, but their
+    /// clocks are still kept in `clocks`.
+    states: HashMap<u64, Any>,
+    clocks: HashMap<u64, u32>,
+    observers: HashMap<u32, PyObject>,
+    next_observer_id: u32,
+}
+
+/// Describes clients affected by a change of awareness states.
+#[pyclass(unsendable)]
+pub struct AwarenessEvent {
+    /// Clients which had no state before.
+    #[pyo3(get)]
+    added: Vec<u64>,
+    /// Clients which had their states replaced, even if with an identical state.
+    #[pyo3(get)]
+    updated: Vec<u64>,
+    /// Clients which had their states removed.
+    #[pyo3(get)]
+    removed: Vec<u64>,
+}
+
+#[pymethods]
+impl AwarenessEvent {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "AwarenessEvent(added={:?}, updated={:?}, removed={:?})",
+            self.added, self.updated, self.removed
+        )
+    }
+}
+
+impl AwarenessEvent {
+    fn new() -> Self {
+        AwarenessEvent {
+            added: Vec::new(),
+            updated: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+
+    fn clients(&self) -> Vec<u64> {
+        let clients = self.added.iter().chain(&self.updated).chain(&self.removed);
+        clients.copied().collect()
+    }
+}
+
+#[pymethods]
+impl Awareness {
+    /// Creates an awareness instance for a given document. The local client starts with an empty
+    /// state.
+    #[new]
+    pub fn new(doc: &YDoc) -> Self {
+        let client_id = doc.client_id();
+        Awareness {
+            client_id,
+            states: HashMap::from([(client_id, Any::Map(Box::default()))]),
+            clocks: HashMap::from([(client_id, 0)]),
+            observers: HashMap::new(),
+            next_observer_id: 0,
+        }
+    }
+
+    /// Client ID of the document this awareness belongs to.
+    #[getter]
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// Returns the state of the local client, or `None` if it has been removed.
+    pub fn get_local_state(&self) -> PyObject {
+        Python::with_gil(|py| match self.states.get(&self.client_id) {
+            Some(state) => state.clone().into_py(py),
+            None => py.None(),
+        })
+    }
+
+    /// Replaces the state of the local client. Setting it to `None` marks the client as offline.
+    /// States are exchanged as JSON, which doesn't tell integers and floats apart, so numbers
+    /// without a fractional part are read back as `int`s, both locally and by other peers.
+    pub fn set_local_state(slf: &PyCell<Self>, state: Option<&PyAny>) -> PyResult<()> {
+        let state = match state {
+            Some(state) if !state.is_none() => {
+                Some(json_numbers(CompatiblePyType::try_from(state)?.try_into()?))
+            }
+            _ => None,
+        };
+        if let Some(state) = &state {
+            // states are exchanged as JSON, which can't represent every value
+            JsonBuilder::new().append_json(state)?;
+        }
+        let (event, observers) = {
+            let mut awareness = slf.borrow_mut();
+            let client_id = awareness.client_id;
+            let clock = awareness
+                .clocks
+                .get(&client_id)
+                .map_or(0, |clock| clock + 1);
+            let mut event = AwarenessEvent::new();
+            awareness.set(client_id, clock, state, &mut event);
+            (event, awareness.observers.values().cloned().collect())
+        };
+        Self::notify(event, observers)
+    }
+
+    /// Returns a dictionary of states of all clients known to be online, keyed by client ID.
+    pub fn get_states(&self) -> PyObject {
+        Python::with_gil(|py| {
+            let states = PyDict::new(py);
+            for (client_id, state) in self.states.iter() {
+                states
+                    .set_item(client_id, state.clone().into_py(py))
+                    .unwrap();
+            }
+            states.into()
+        })
+    }
+
+    /// Subscribes a callback, which receives an `AwarenessEvent` every time the awareness states
+    /// get updated, either locally or by applying an update. Returns an ID, which can be used to
+    /// cancel the callback with `unobserve`.
+    pub fn observe(&mut self, callback: PyObject) -> u32 {
+        let id = self.next_observer_id;
+        self.next_observer_id += 1;
+        self.observers.insert(id, callback);
+        id
+    }
+
+    /// Cancels the callback associated with a given subscription ID.
+    pub fn unobserve(&mut self, subscription_id: u32) {
+        self.observers.remove(&subscription_id);
+    }
+}
+
+impl Awareness {
+    /// Checks if an entry with a given `clock` replaces the currently known state of a client.
+    fn is_newer(&self, client_id: u64, clock: u32, is_removal: bool) -> bool {
+        match self.clocks.get(&client_id) {
+            None => true,
+            // removals win over updates with the same clock
+            Some(&current) => {
+                current < clock
+                    || (current == clock && is_removal && self.states.contains_key(&client_id))
+            }
+        }
+    }
+
+    /// Sets the state of a client and records it in the `event`. Setting `None` removes it.
+    fn set(&mut self, client_id: u64, clock: u32, state: Option<Any>, event: &mut AwarenessEvent) {
+        self.clocks.insert(client_id, clock);
+        let had_state = self.states.contains_key(&client_id);
+        match state {
+            Some(state) => {
+                self.states.insert(client_id, state);
+                if had_state {
+                    event.updated.push(client_id);
+                } else {
+                    event.added.push(client_id);
+                }
+            }
+            None if had_state => {
+                self.states.remove(&client_id);
+                event.removed.push(client_id);
+            }
+            None => {}
+        }
+    }
+
+    fn notify(event: AwarenessEvent, observers: Vec<PyObject>) -> PyResult<()> {
+        if event.is_empty() || observers.is_empty() {
+            return Ok(());
+        }
+        Python::with_gil(|py| {
+            let event = Py::new(py, event)?;
+            for callback in observers {
+                callback.call1(py, (event.clone_ref(py),))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Encodes states of given `clients` (or all known clients, if not provided) into an awareness
+/// update, which can be applied by other peers with `apply_awareness_update`.
+#[pyfunction]
+pub fn encode_awareness_update(
+    awareness: &Awareness,
+    clients: Option<Vec<u64>>,
+) -> PyResult<PyObject> {
+    let clients = clients.unwrap_or_else(|| awareness.clocks.keys().copied().collect());
+    let mut buf = Vec::new();
+    buf.write_var(clients.len());
+    for client_id in clients {
+        let clock = awareness.clocks.get(&client_id).ok_or_else(|| {
+            PyKeyError::new_err(format!("Awareness has no state of a client {client_id}"))
+        })?;
+        let mut json = String::new();
+        match awareness.states.get(&client_id) {
+            Some(state) => state.to_json(&mut json),
+            None => json.push_str("null"),
+        }
+        buf.write_var(client_id);
+        buf.write_var(*clock);
+        buf.write_string(&json);
+    }
+    Ok(Python::with_gil(|py| PyBytes::new(py, &buf).into()))
+}
+
+/// Applies an awareness update created with `encode_awareness_update` by another peer. Returns
+/// IDs of clients whose states were added, updated or removed.
+#[pyfunction]
+pub fn apply_awareness_update(awareness: &PyCell<Awareness>, update: Binary) -> PyResult<Vec<u64>> {
+    let entries = try_decode(|| decode_entries(&update))
+        .map_err(|e| EncodingException::new_err(format!("Awareness update is malformed: {e}")))?;
+    let (event, observers) = {
+        let mut awareness = awareness.borrow_mut();
+        let mut event = AwarenessEvent::new();
+        for (client_id, clock, state) in entries {
+            let state = match state {
+                Any::Null => None,
+                state => Some(json_numbers(state)),
+            };
+            if !awareness.is_newer(client_id, clock, state.is_none()) {
+                continue;
+            }
+            let is_local = client_id == awareness.client_id;
+            if state.is_none() && is_local && awareness.states.contains_key(&client_id) {
+                // Other peers can't remove the local client while it's still online. Its clock is
+                // increased instead, so that its state overrides the removal once broadcasted.
+                let state = awareness.states.get(&client_id).cloned();
+                awareness.set(client_id, clock + 1, state, &mut event);
+            } else {
+                awareness.set(client_id, clock, state, &mut event);
+            }
+        }
+        (event, awareness.observers.values().cloned().collect())
+    };
+    let clients = event.clients();
+    Awareness::notify(event, observers)?;
+    Ok(clients)
+}
+
+fn decode_entries(update: &[u8]) -> Result<Vec<(u64, u32, Any)>, lib0::error::Error> {
+    let mut cursor = Cursor::new(update);
+    let len: u32 = cursor.read_var()?;
+    // every entry takes at least 3 bytes, which bounds the length of malformed updates
+    let remaining = (cursor.buf.len() - cursor.next) / 3;
+    let mut entries = Vec::with_capacity((len as usize).min(remaining));
+    for _ in 0..len {
+        let client_id = cursor.read_var()?;
+        let clock = cursor.read_var()?;
+        let state = Any::from_json(cursor.read_string()?)?;
+        entries.push((client_id, clock, state));
+    }
+    Ok(entries)
+}
+
+/// Converts numbers without a fractional part into integers, the way they are read from JSON
+/// encoded states of remote clients.
+fn json_numbers(value: Any) -> Any {
+    const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+    match value {
+        Any::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER => Any::BigInt(n as i64),
+        Any::Array(items) => Any::Array(items.into_vec().into_iter().map(json_numbers).collect()),
+        Any::Map(entries) => Any::Map(Box::new(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key, json_numbers(value)))
+                .collect(),
+        )),
+        value => value,
+    }
+}
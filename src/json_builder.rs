@@ -1,9 +1,11 @@
 use std::{collections::HashMap, convert::TryFrom};
 
 use lib0::any::Any;
-use pyo3::{exceptions::PyTypeError, PyErr, PyObject, PyResult, Python};
+use pyo3::types::{PyDict, PyList};
+use pyo3::{exceptions::PyTypeError, IntoPy, PyErr, PyObject, PyResult, Python};
 
 use crate::shared_types::{CompatiblePyType, YPyType};
+use crate::type_conversions::{number_into_py, DATETIME_TAG};
 
 #[derive(Clone, Debug)]
 pub(crate) struct JsonBuilder(String);
@@ -41,13 +43,7 @@ impl<'a> JsonBuildable for CompatiblePyType<'a> {
             }
             CompatiblePyType::Int(i) => buffer.push_str(&i.to_string()),
             CompatiblePyType::Float(f) => buffer.push_str(&f.to_string()),
-            CompatiblePyType::String(s) => {
-                let string: String = s.extract().unwrap();
-                buffer.reserve(string.len() + 2);
-                buffer.push_str("\"");
-                buffer.push_str(&string);
-                buffer.push_str("\"");
-            }
+            CompatiblePyType::String(s) => push_json_string(buffer, s.to_str()?),
             CompatiblePyType::List(list) => {
                 buffer.push_str("[");
                 let length = list.len();
@@ -73,6 +69,11 @@ impl<'a> JsonBuildable for CompatiblePyType<'a> {
                 }
                 buffer.push_str("}");
             }
+            CompatiblePyType::Bytes(_) | CompatiblePyType::ByteArray(_) => {
+                return Err(buffer_error())
+            }
+            // serialized the same way as once integrated
+            CompatiblePyType::DateTime(_) => Any::try_from(self.clone())?.build_json(buffer)?,
             CompatiblePyType::YType(y_type) => y_type.build_json(buffer)?,
             CompatiblePyType::None => buffer.push_str("null"),
         }
@@ -101,11 +102,64 @@ impl<'a> JsonBuildable for YPyType<'a> {
 impl JsonBuildable for Any {
     type JsonError = PyErr;
     fn build_json(&self, buffer: &mut String) -> Result<(), Self::JsonError> {
+        fn contains_buffer(any: &Any) -> bool {
+            match any {
+                Any::Buffer(_) => true,
+                Any::Array(items) => items.iter().any(contains_buffer),
+                Any::Map(entries) => entries.values().any(contains_buffer),
+                _ => false,
+            }
+        }
+        // lib0 panics when serializing binary values
+        if contains_buffer(self) {
+            return Err(buffer_error());
+        }
         self.to_json(buffer);
         Ok(())
     }
 }
 
+/// Appends a quoted JSON string, escaping characters which can't be a part of it as they are.
+pub(crate) fn push_json_string(buffer: &mut String, s: &str) {
+    Any::String(s.into()).to_json(buffer)
+}
+
+fn buffer_error() -> PyErr {
+    PyTypeError::new_err("Binary values cannot be converted to a JSON format.")
+}
+
+/// Converts a `value` into plain Python objects, which can be serialized with `json.dumps`.
+/// Buffers are represented as base64 encoded strings and datetimes as ISO 8601 strings.
+pub(crate) fn json_value_into_py(py: Python, value: &Any) -> PyResult<PyObject> {
+    Ok(match value {
+        Any::Null | Any::Undefined => py.None(),
+        Any::Bool(v) => v.into_py(py),
+        Any::Number(v) => number_into_py(py, *v),
+        Any::BigInt(v) => v.into_py(py),
+        Any::String(v) => v.into_py(py),
+        Any::Buffer(v) => py
+            .import("base64")?
+            .call_method1("b64encode", (v.as_ref(),))?
+            .call_method0("decode")?
+            .into(),
+        Any::Array(items) => {
+            let items: PyResult<Vec<PyObject>> =
+                items.iter().map(|v| json_value_into_py(py, v)).collect();
+            PyList::new(py, items?).into()
+        }
+        Any::Map(entries) if entries.len() == 1 && entries.contains_key(DATETIME_TAG) => {
+            json_value_into_py(py, &entries[DATETIME_TAG])?
+        }
+        Any::Map(entries) => {
+            let dict = PyDict::new(py);
+            for (k, v) in entries.iter() {
+                dict.set_item(k, json_value_into_py(py, v)?)?;
+            }
+            dict.into()
+        }
+    })
+}
+
 impl JsonBuildable for HashMap<String, PyObject> {
     type JsonError = PyErr;
 
@@ -117,7 +171,7 @@ impl JsonBuildable for HashMap<String, PyObject> {
                 if i != 0 {
                     buffer.push_str(",");
                 }
-                buffer.push_str(k);
+                push_json_string(buffer, k);
                 buffer.push_str(":");
                 value.build_json(buffer)?;
             }
@@ -1,7 +1,11 @@
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
+mod awareness;
 mod json_builder;
+mod msgpack;
 mod shared_types;
+mod sticky_index;
+mod sync;
 mod type_conversions;
 mod y_array;
 mod y_doc;
@@ -13,7 +17,7 @@ use crate::y_doc::*;
 
 /// Python bindings for Y.rs
 #[pymodule]
-pub fn y_py(_py: Python, m: &PyModule) -> PyResult<()> {
+pub fn y_py(py: Python, m: &PyModule) -> PyResult<()> {
     // Data Types
     m.add_class::<y_doc::YDoc>()?;
     m.add_class::<y_transaction::YTransaction>()?;
@@ -22,6 +26,8 @@ pub fn y_py(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<y_map::YMap>()?;
     m.add_class::<y_xml::YXmlText>()?;
     m.add_class::<y_xml::YXmlElement>()?;
+    m.add_class::<sticky_index::StickyIndex>()?;
+    m.add_class::<awareness::Awareness>()?;
     // Events
     m.add_class::<y_text::YTextEvent>()?;
     m.add_class::<y_array::YArrayEvent>()?;
@@ -29,9 +35,35 @@ pub fn y_py(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<y_xml::YXmlTextEvent>()?;
     m.add_class::<y_xml::YXmlEvent>()?;
     m.add_class::<y_doc::AfterTransactionEvent>()?;
+    m.add_class::<awareness::AwarenessEvent>()?;
+    // Exceptions
+    m.add(
+        "ReadOnlyTransactionException",
+        py.get_type::<y_transaction::ReadOnlyTransactionException>(),
+    )?;
+    m.add(
+        "EncodingException",
+        py.get_type::<y_transaction::EncodingException>(),
+    )?;
     // Functions
     m.add_wrapped(wrap_pyfunction!(encode_state_vector))?;
     m.add_wrapped(wrap_pyfunction!(encode_state_as_update))?;
     m.add_wrapped(wrap_pyfunction!(apply_update))?;
+    m.add_wrapped(wrap_pyfunction!(apply_updates))?;
+    m.add_wrapped(wrap_pyfunction!(encode_state_vector_v2))?;
+    m.add_wrapped(wrap_pyfunction!(encode_state_as_update_v2))?;
+    m.add_wrapped(wrap_pyfunction!(apply_update_v2))?;
+    m.add_wrapped(wrap_pyfunction!(encode_state_from_snapshot))?;
+    m.add_wrapped(wrap_pyfunction!(merge_updates))?;
+    m.add_wrapped(wrap_pyfunction!(diff_updates))?;
+    m.add_wrapped(wrap_pyfunction!(sticky_index::encode_relative_position))?;
+    m.add_wrapped(wrap_pyfunction!(sticky_index::decode_relative_position))?;
+    m.add_wrapped(wrap_pyfunction!(awareness::encode_awareness_update))?;
+    m.add_wrapped(wrap_pyfunction!(awareness::apply_awareness_update))?;
+    m.add_wrapped(wrap_pyfunction!(sync::sync_step_1))?;
+    m.add_wrapped(wrap_pyfunction!(sync::read_sync_step_1))?;
+    m.add_wrapped(wrap_pyfunction!(sync::read_sync_step_2))?;
+    m.add_wrapped(wrap_pyfunction!(sync::write_update))?;
+    m.add_wrapped(wrap_pyfunction!(sync::read_sync_message))?;
     Ok(())
 }
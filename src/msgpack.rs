@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use lib0::any::Any;
+use pyo3::types::{PyDict, PyList};
+use pyo3::{PyAny, PyResult};
+
+use crate::shared_types::{CompatiblePyType, YPyType};
+use crate::y_transaction::EncodingException;
+
+/// Maximum nesting depth of decoded arrays and maps, which keeps malformed payloads from
+/// exhausting the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Serializes a given value using MessagePack format. Buffers are written using the `bin` family,
+/// while numbers with no fractional part are written as integers.
+pub(crate) fn encode(value: &Any) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_any(&mut buf, value);
+    buf
+}
+
+/// Deserializes a single MessagePack value. Integers are read as [Any::BigInt], so that they are
+/// not turned into floats once converted into Python objects.
+pub(crate) fn decode(data: &[u8]) -> PyResult<Any> {
+    let mut reader = Reader {
+        data,
+        pos: 0,
+        depth: 0,
+    };
+    let value = reader.read_any()?;
+    if reader.pos != data.len() {
+        return Err(EncodingException::new_err(
+            "Unexpected trailing bytes after a MessagePack value.",
+        ));
+    }
+    Ok(value)
+}
+
+/// Converts contents of a preliminary shared type into [Any]. Unlike regular conversion used when
+/// integrating values, it also accepts preliminary shared types nested inside lists and dicts.
+pub(crate) fn py_to_any(value: &PyAny) -> PyResult<Any> {
+    if let Ok(list) = value.downcast::<PyList>() {
+        let items: PyResult<Vec<Any>> = list.iter().map(py_to_any).collect();
+        Ok(Any::Array(items?.into_boxed_slice()))
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        let entries: PyResult<HashMap<String, Any>> = dict
+            .iter()
+            .map(|(k, v)| Ok((k.extract()?, py_to_any(v)?)))
+            .collect();
+        Ok(Any::Map(Box::new(entries?)))
+    } else {
+        match CompatiblePyType::try_from(value)? {
+            CompatiblePyType::YType(YPyType::Text(text)) => {
+                Ok(Any::String(text.borrow().__str__().into_boxed_str()))
+            }
+            CompatiblePyType::YType(YPyType::Array(array)) => array.borrow().to_any(),
+            CompatiblePyType::YType(YPyType::Map(map)) => map.borrow().to_any(),
+            other => other.try_into(),
+        }
+    }
+}
+
+fn write_any(buf: &mut Vec<u8>, value: &Any) {
+    match value {
+        Any::Null | Any::Undefined => buf.push(0xc0),
+        Any::Bool(false) => buf.push(0xc2),
+        Any::Bool(true) => buf.push(0xc3),
+        Any::Number(n) if n.fract() == 0.0 && n.abs() < 2_f64.powi(63) => write_int(buf, *n as i64),
+        Any::Number(n) => {
+            buf.push(0xcb);
+            buf.extend_from_slice(&n.to_be_bytes());
+        }
+        Any::BigInt(n) => write_int(buf, *n),
+        Any::String(s) => {
+            let len = s.len();
+            if len < 32 {
+                buf.push(0xa0 | len as u8);
+            } else {
+                write_len(buf, len, Some(0xd9), 0xda, 0xdb);
+            }
+            buf.extend_from_slice(s.as_bytes());
+        }
+        Any::Buffer(b) => {
+            write_len(buf, b.len(), Some(0xc4), 0xc5, 0xc6);
+            buf.extend_from_slice(b);
+        }
+        Any::Array(items) => {
+            if items.len() < 16 {
+                buf.push(0x90 | items.len() as u8);
+            } else {
+                write_len(buf, items.len(), None, 0xdc, 0xdd);
+            }
+            items.iter().for_each(|item| write_any(buf, item));
+        }
+        Any::Map(entries) => {
+            if entries.len() < 16 {
+                buf.push(0x80 | entries.len() as u8);
+            } else {
+                write_len(buf, entries.len(), None, 0xde, 0xdf);
+            }
+            for (key, value) in entries.iter() {
+                write_any(buf, &Any::String(key.as_str().into()));
+                write_any(buf, value);
+            }
+        }
+    }
+}
+
+/// Writes a type marker followed by a length prefix of the smallest width able to fit `len`.
+fn write_len(buf: &mut Vec<u8>, len: usize, marker8: Option<u8>, marker16: u8, marker32: u8) {
+    match marker8 {
+        Some(marker) if len <= u8::MAX as usize => buf.extend_from_slice(&[marker, len as u8]),
+        _ if len <= u16::MAX as usize => {
+            buf.push(marker16);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            buf.push(marker32);
+            buf.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+fn write_int(buf: &mut Vec<u8>, n: i64) {
+    match n {
+        0..=0x7f => buf.push(n as u8),
+        -32..=-1 => buf.push(n as u8),
+        0x80..=0xff => buf.extend_from_slice(&[0xcc, n as u8]),
+        0x100..=0xffff => {
+            buf.push(0xcd);
+            buf.extend_from_slice(&(n as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            buf.push(0xce);
+            buf.extend_from_slice(&(n as u32).to_be_bytes());
+        }
+        -0x80..=-33 => buf.extend_from_slice(&[0xd0, n as u8]),
+        -0x8000..=-0x81 => {
+            buf.push(0xd1);
+            buf.extend_from_slice(&(n as i16).to_be_bytes());
+        }
+        -0x8000_0000..=-0x8001 => {
+            buf.push(0xd2);
+            buf.extend_from_slice(&(n as i32).to_be_bytes());
+        }
+        _ => {
+            buf.push(0xd3);
+            buf.extend_from_slice(&n.to_be_bytes());
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> PyResult<&'a [u8]> {
+        let end = self.pos + len;
+        if end > self.data.len() {
+            return Err(EncodingException::new_err(
+                "Unexpected end of MessagePack data.",
+            ));
+        }
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> PyResult<[u8; N]> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> PyResult<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_len(&mut self, width: u8) -> PyResult<usize> {
+        Ok(match width {
+            1 => self.read_u8()? as usize,
+            2 => u16::from_be_bytes(self.read_array()?) as usize,
+            _ => u32::from_be_bytes(self.read_array()?) as usize,
+        })
+    }
+
+    fn read_str(&mut self, len: usize) -> PyResult<Box<str>> {
+        let bytes = self.read_bytes(len)?;
+        std::str::from_utf8(bytes)
+            .map(Box::from)
+            .map_err(|e| EncodingException::new_err(e.to_string()))
+    }
+
+    /// Number of bytes left to read. Lengths of collections are read from the payload, so they
+    /// have to be checked against it before reserving any memory.
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn enter(&mut self) -> PyResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(EncodingException::new_err(format!(
+                "MessagePack data is nested deeper than {MAX_DEPTH} levels."
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_items(&mut self, len: usize) -> PyResult<Any> {
+        self.enter()?;
+        // every item takes at least a single byte
+        let mut items = Vec::with_capacity(len.min(self.remaining()));
+        for _ in 0..len {
+            items.push(self.read_any()?);
+        }
+        self.depth -= 1;
+        Ok(Any::Array(items.into_boxed_slice()))
+    }
+
+    fn read_entries(&mut self, len: usize) -> PyResult<Any> {
+        self.enter()?;
+        // every entry takes at least two bytes: a key and a value
+        let mut entries = HashMap::with_capacity(len.min(self.remaining() / 2));
+        for _ in 0..len {
+            let key = match self.read_any()? {
+                Any::String(key) => key.into(),
+                other => {
+                    return Err(EncodingException::new_err(format!(
+                        "MessagePack map keys must be strings, found: {other}"
+                    )))
+                }
+            };
+            entries.insert(key, self.read_any()?);
+        }
+        self.depth -= 1;
+        Ok(Any::Map(Box::new(entries)))
+    }
+
+    fn read_any(&mut self) -> PyResult<Any> {
+        let marker = self.read_u8()?;
+        Ok(match marker {
+            0x00..=0x7f => Any::BigInt(marker as i64),
+            0x80..=0x8f => self.read_entries((marker & 0x0f) as usize)?,
+            0x90..=0x9f => self.read_items((marker & 0x0f) as usize)?,
+            0xa0..=0xbf => Any::String(self.read_str((marker & 0x1f) as usize)?),
+            0xc0 => Any::Null,
+            0xc2 => Any::Bool(false),
+            0xc3 => Any::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.read_len(1 << (marker - 0xc4))?;
+                Any::Buffer(self.read_bytes(len)?.into())
+            }
+            0xca => Any::Number(f32::from_be_bytes(self.read_array()?) as f64),
+            0xcb => Any::Number(f64::from_be_bytes(self.read_array()?)),
+            0xcc => Any::BigInt(self.read_u8()? as i64),
+            0xcd => Any::BigInt(u16::from_be_bytes(self.read_array()?) as i64),
+            0xce => Any::BigInt(u32::from_be_bytes(self.read_array()?) as i64),
+            0xcf => {
+                let n = u64::from_be_bytes(self.read_array()?);
+                match i64::try_from(n) {
+                    Ok(n) => Any::BigInt(n),
+                    Err(_) => Any::Number(n as f64),
+                }
+            }
+            0xd0 => Any::BigInt(self.read_u8()? as i8 as i64),
+            0xd1 => Any::BigInt(i16::from_be_bytes(self.read_array()?) as i64),
+            0xd2 => Any::BigInt(i32::from_be_bytes(self.read_array()?) as i64),
+            0xd3 => Any::BigInt(i64::from_be_bytes(self.read_array()?)),
+            0xd9..=0xdb => {
+                let len = self.read_len(1 << (marker - 0xd9))?;
+                Any::String(self.read_str(len)?)
+            }
+            0xdc | 0xdd => {
+                let len = self.read_len(2 << (marker - 0xdc))?;
+                self.read_items(len)?
+            }
+            0xde | 0xdf => {
+                let len = self.read_len(2 << (marker - 0xde))?;
+                self.read_entries(len)?
+            }
+            0xe0..=0xff => Any::BigInt(marker as i8 as i64),
+            other => {
+                return Err(EncodingException::new_err(format!(
+                    "Unsupported MessagePack type marker: {other:#04x}"
+                )))
+            }
+        })
+    }
+}
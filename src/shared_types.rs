@@ -6,11 +6,19 @@ use crate::{
 };
 use pyo3::create_exception;
 use pyo3::types as pytypes;
-use pyo3::{exceptions::PyException, prelude::*};
+use pyo3::{
+    exceptions::{PyException, PyIndexError},
+    prelude::*,
+};
+use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::fmt::Display;
+use std::rc::Weak;
+use yrs::types::DeepObservable;
 use yrs::types::TYPE_REFS_XML_TEXT;
 use yrs::types::{TypeRefs, TYPE_REFS_ARRAY, TYPE_REFS_MAP, TYPE_REFS_TEXT};
 use yrs::{types::TYPE_REFS_XML_ELEMENT, SubscriptionId};
+use yrs::{Array, Map, Text, XmlElement, XmlText};
 
 // Common errors
 create_exception!(y_py, PreliminaryObservationException, PyException, "Occurs when an observer is attached to a Y type that is not integrated into a YDoc. Y types can only be observed once they have been added to a YDoc.");
@@ -38,17 +46,219 @@ impl DefaultPyErr for IntegratedOperationException {
     }
 }
 
-#[pyclass]
-#[derive(Clone, Copy)]
-pub struct ShallowSubscription(pub SubscriptionId);
-#[pyclass]
-#[derive(Clone, Copy)]
-pub struct DeepSubscription(pub SubscriptionId);
+thread_local! {
+    /// The first exception raised by an observer callback during the current commit.
+    static OBSERVER_ERROR: RefCell<Option<PyErr>> = RefCell::new(None);
+}
+
+/// Reports an exception raised by an observer callback. Callbacks are called while a transaction
+/// is being committed, which can't be interrupted, so the exception is kept aside until the
+/// commit is done and then raised by `raise_observer_error`. When more callbacks fail, only the
+/// first exception is kept, while the other ones are printed together with their tracebacks.
+pub(crate) fn report_observer_error(py: Python, err: PyErr) {
+    OBSERVER_ERROR.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        if pending.is_some() {
+            err.print(py);
+        } else {
+            *pending = Some(err);
+        }
+    })
+}
+
+/// Raises an exception reported by `report_observer_error` since the last call, if any.
+pub(crate) fn raise_observer_error(_py: Python) -> PyResult<()> {
+    match OBSERVER_ERROR.with(|pending| pending.borrow_mut().take()) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Resolves an `index` of a sequence with a given `len`, where negative indexes count from its
+/// end, like in Python. `name` describes the sequence in an error raised when a negative index
+/// still falls before its beginning. Indexes past the end are left to be checked by the caller.
+pub(crate) fn normalize_index(index: i64, len: usize, name: &str) -> PyResult<u32> {
+    let resolved = if index < 0 { index + len as i64 } else { index };
+    u32::try_from(resolved).map_err(|_| {
+        PyIndexError::new_err(format!(
+            "Index {index} is out of range of {name} with length {len}."
+        ))
+    })
+}
+
+/// Shared type an observer callback has been attached to. Subscriptions keep it around, so that
+/// they can cancel their callbacks on their own.
+#[derive(Clone)]
+pub enum Observed {
+    Text(Text),
+    Array(Array),
+    Map(Map),
+    XmlElement(XmlElement),
+    XmlText(XmlText),
+}
+
+impl Observed {
+    fn unobserve(&mut self, id: SubscriptionId) {
+        match self {
+            Observed::Text(v) => v.unobserve(id),
+            Observed::Array(v) => v.unobserve(id),
+            Observed::Map(v) => v.unobserve(id),
+            Observed::XmlElement(v) => v.unobserve(id),
+            Observed::XmlText(v) => v.unobserve(id),
+        }
+    }
+
+    fn unobserve_deep(&mut self, id: SubscriptionId) {
+        match self {
+            Observed::Text(v) => v.unobserve_deep(id),
+            Observed::Array(v) => v.unobserve_deep(id),
+            Observed::Map(v) => v.unobserve_deep(id),
+            Observed::XmlElement(v) => v.unobserve_deep(id),
+            Observed::XmlText(v) => v.unobserve_deep(id),
+        }
+    }
+}
+
+/// Handle of a callback registered with `observe`. Besides being passed to `unobserve`, it can
+/// cancel the callback by itself, either explicitly with `drop`, by being used as a context
+/// manager, or implicitly once the handle is garbage collected.
+#[pyclass(unsendable)]
+pub struct ShallowSubscription {
+    /// Integer identifier of the subscription.
+    #[pyo3(get)]
+    pub id: SubscriptionId,
+    target: Observed,
+    /// Deep observer keeping the `before` snapshot of the callback up to date, if it has one.
+    snapshot_id: Option<SubscriptionId>,
+    /// Callback owned by the observer. It's released once the observer is cancelled or the
+    /// observed type is freed along with its document, after which there is nothing to cancel.
+    callback: Weak<PyObject>,
+}
+
+impl ShallowSubscription {
+    pub fn new(id: SubscriptionId, target: Observed, callback: Weak<PyObject>) -> Self {
+        ShallowSubscription {
+            id,
+            target,
+            snapshot_id: None,
+            callback,
+        }
+    }
+
+    /// Ties the lifetime of a deep observer refreshing the snapshot to this subscription.
+    pub fn with_snapshot(mut self, snapshot_id: SubscriptionId) -> Self {
+        self.snapshot_id = Some(snapshot_id);
+        self
+    }
+}
+
+#[pymethods]
+impl ShallowSubscription {
+    /// Cancels the observer callback. Calling it more than once has no effect.
+    pub fn drop(&mut self) {
+        if self.callback.strong_count() > 0 {
+            self.target.unobserve(self.id);
+            if let Some(snapshot_id) = self.snapshot_id {
+                self.target.unobserve_deep(snapshot_id)
+            }
+        }
+    }
+
+    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    pub fn __exit__(
+        &mut self,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        self.drop();
+        false
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("ShallowSubscription(id={})", self.id)
+    }
+}
+
+impl Drop for ShallowSubscription {
+    fn drop(&mut self) {
+        ShallowSubscription::drop(self)
+    }
+}
+
+/// Handle of a callback registered with `observe_deep`. Works the same way as
+/// `ShallowSubscription`.
+#[pyclass(unsendable)]
+pub struct DeepSubscription {
+    /// Integer identifier of the subscription.
+    #[pyo3(get)]
+    pub id: SubscriptionId,
+    target: Observed,
+    /// Callback owned by the observer, see `ShallowSubscription`.
+    callback: Weak<PyObject>,
+}
+
+impl DeepSubscription {
+    pub fn new(id: SubscriptionId, target: Observed, callback: Weak<PyObject>) -> Self {
+        DeepSubscription {
+            id,
+            target,
+            callback,
+        }
+    }
+}
+
+#[pymethods]
+impl DeepSubscription {
+    /// Cancels the observer callback. Calling it more than once has no effect.
+    pub fn drop(&mut self) {
+        if self.callback.strong_count() > 0 {
+            self.target.unobserve_deep(self.id)
+        }
+    }
+
+    pub fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    pub fn __exit__(
+        &mut self,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        self.drop();
+        false
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("DeepSubscription(id={})", self.id)
+    }
+}
+
+impl Drop for DeepSubscription {
+    fn drop(&mut self) {
+        DeepSubscription::drop(self)
+    }
+}
 
 #[derive(FromPyObject)]
-pub enum SubId {
-    Shallow(ShallowSubscription),
-    Deep(DeepSubscription),
+pub enum SubId<'a> {
+    Shallow(PyRefMut<'a, ShallowSubscription>),
+    Deep(PyRefMut<'a, DeepSubscription>),
+}
+
+impl SubId<'_> {
+    /// Cancels the observer callback of a subscription handle passed to `unobserve`.
+    pub fn unobserve(self) {
+        match self {
+            SubId::Shallow(mut sub) => ShallowSubscription::drop(&mut sub),
+            SubId::Deep(mut sub) => DeepSubscription::drop(&mut sub),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -57,6 +267,9 @@ pub enum CompatiblePyType<'a> {
     Int(&'a pytypes::PyInt),
     Float(&'a pytypes::PyFloat),
     String(&'a pytypes::PyString),
+    Bytes(&'a pytypes::PyBytes),
+    ByteArray(&'a pytypes::PyByteArray),
+    DateTime(&'a pytypes::PyDateTime),
     List(&'a pytypes::PyList),
     Dict(&'a pytypes::PyDict),
     YType(YPyType<'a>),
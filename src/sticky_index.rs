@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+
+use lib0::any::Any;
+use lib0::decoding::{Cursor, Read};
+use lib0::encoding::Write;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use yrs::block::{
+    ItemContent, BLOCK_GC_REF_NUMBER, BLOCK_SKIP_REF_NUMBER, HAS_ORIGIN, HAS_PARENT_SUB,
+    HAS_RIGHT_ORIGIN, ID,
+};
+use yrs::types::text::YChange;
+use yrs::types::Value;
+use yrs::updates::decoder::{Decoder, DecoderV1};
+use yrs::{DeleteSet, OffsetKind, Snapshot, StateVector, Text, Transaction};
+
+use crate::y_text::char_len;
+use crate::y_transaction::{try_decode, EncodingException, YTransaction};
+
+/// A position within a `YText`, which - unlike a plain index - stays in place when the text around
+/// it gets edited, including concurrent edits made by other peers. It's created with
+/// `YText.sticky_index` and can be turned back into an index with `YText.resolve_index`.
+///
+/// Positions are anchored to characters, so `assoc` decides which one of the two characters
+/// adjacent to the index is used: the one after it (when `assoc` is `0` or more) or the one before
+/// it (when `assoc` is negative). This only matters when new text gets inserted right at the
+/// position. Embeds can't be used as anchors, so next to them the closest character in direction of
+/// `assoc` is used instead.
+#[pyclass(unsendable)]
+#[derive(Clone, PartialEq)]
+pub struct StickyIndex {
+    scope: Scope,
+    /// Side the position is associated with.
+    #[pyo3(get)]
+    assoc: i32,
+}
+
+/// What a `StickyIndex` is anchored to. Variants follow the order used by the Yjs encoding.
+#[derive(Clone, PartialEq)]
+enum Scope {
+    /// A single character, identified by its ID.
+    Item(ID),
+    /// Beginning (for negative `assoc`) or end of a root-level text with a given name.
+    Root(String),
+    /// Beginning or end of a nested text, identified by the ID of the item holding it.
+    Nested(ID),
+}
+
+#[pymethods]
+impl StickyIndex {
+    pub fn __repr__(&self) -> String {
+        let scope = match &self.scope {
+            Scope::Item(id) => format!("item={id}"),
+            Scope::Root(name) => format!("name={name:?}"),
+            Scope::Nested(id) => format!("type={id}"),
+        };
+        format!("StickyIndex({scope}, assoc={})", self.assoc)
+    }
+
+    /// Positions are equal when they are anchored to the same place with the same `assoc`, which
+    /// is why they are not hashable.
+    pub fn __richcmp__(&self, other: &Self, op: CompareOp) -> PyObject {
+        Python::with_gil(|py| match op {
+            CompareOp::Eq => (self == other).into_py(py),
+            CompareOp::Ne => (self != other).into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+}
+
+/// A run of text content in document order.
+enum Chunk {
+    /// String contents of a single block, together with the ID of its first character.
+    Text(ID, String),
+    Embed,
+}
+
+impl Chunk {
+    fn len(&self, offset_kind: OffsetKind) -> u32 {
+        match self {
+            Chunk::Text(_, s) => s.chars().map(|c| char_len(c, offset_kind)).sum(),
+            Chunk::Embed => 1,
+        }
+    }
+
+    /// Returns the offset (in offset kind units) of a character identified by `id`, together with
+    /// its length, if it belongs to this chunk.
+    fn locate(&self, id: &ID, offset_kind: OffsetKind) -> Option<(u32, u32)> {
+        match self {
+            Chunk::Text(start, s) if start.client == id.client && start.clock <= id.clock => {
+                let mut clock = start.clock;
+                let mut offset = 0;
+                for c in s.chars() {
+                    // block clocks are measured in UTF-16 code units
+                    clock += c.len_utf16() as u32;
+                    if clock > id.clock {
+                        return Some((offset, char_len(c, offset_kind)));
+                    }
+                    offset += char_len(c, offset_kind);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns all contents of a `text`, including deleted ones that weren't garbage collected yet,
+/// split into chunks corresponding to the underlying blocks, each one flagged with whether it's
+/// currently visible. yrs doesn't expose the blocks of a text, but a diff against an empty snapshot
+/// reports every string block separately, together with its ID. The snapshot carries no delete set,
+/// because yrs 0.12.2 panics while splitting blocks by one, so visibility is worked out separately.
+fn chunks(text: &Text, txn: &mut Transaction) -> Vec<(Chunk, bool)> {
+    let deleted = txn.snapshot().delete_set;
+    // visible elements in document order, with `true` standing for an embed
+    let visible: Vec<bool> = text
+        .diff(txn, YChange::identity)
+        .into_iter()
+        .flat_map(|diff| match diff.insert {
+            Value::Any(Any::String(s)) => vec![false; s.chars().count()],
+            _ => vec![true],
+        })
+        .collect();
+    let mut visible = visible.into_iter().peekable();
+    let all = Snapshot::new(txn.state_vector(), DeleteSet::new());
+    text.diff_range(txn, Some(&Snapshot::default()), Some(&all), |c| c.id)
+        .into_iter()
+        .map(|diff| match (diff.insert, diff.ychange) {
+            (Value::Any(Any::String(s)), Some(id)) => {
+                let is_visible = !deleted.is_deleted(&id);
+                if is_visible {
+                    visible.nth(s.chars().count() - 1);
+                }
+                (Chunk::Text(id, s.into()), is_visible)
+            }
+            _ => {
+                // embeds carry no IDs, but the ones left in place are all alike
+                let is_visible = visible.next_if(|&embed| embed).is_some();
+                (Chunk::Embed, is_visible)
+            }
+        })
+        .collect()
+}
+
+/// How an item of an encoded update is tied to its parent.
+enum Link {
+    /// The item shares the parent of an item with a given ID.
+    Origin(ID),
+    /// The item belongs to a given root or nested type.
+    Parent(Scope),
+    /// GC and skip structs carry no parent information.
+    Unknown,
+}
+
+/// Returns links of all items of an update, grouped by client and ordered by their clocks.
+fn decode_links(update: &[u8]) -> Result<HashMap<u64, Vec<(u32, Link)>>, lib0::error::Error> {
+    let mut decoder = DecoderV1::from(update);
+    let mut links = HashMap::new();
+    for _ in 0..decoder.read_var::<u32>()? {
+        let blocks_len: u32 = decoder.read_var()?;
+        let client = decoder.read_client()?;
+        let mut clock: u32 = decoder.read_var()?;
+        let blocks = links.entry(client).or_insert_with(Vec::new);
+        for _ in 0..blocks_len {
+            let (len, link) = match decoder.read_info()? {
+                BLOCK_SKIP_REF_NUMBER => (decoder.read_var()?, Link::Unknown),
+                BLOCK_GC_REF_NUMBER => (decoder.read_len()?, Link::Unknown),
+                info => {
+                    let origin = if info & HAS_ORIGIN != 0 {
+                        Some(decoder.read_left_id()?)
+                    } else {
+                        None
+                    };
+                    let right_origin = if info & HAS_RIGHT_ORIGIN != 0 {
+                        Some(decoder.read_right_id()?)
+                    } else {
+                        None
+                    };
+                    let link = match origin.or(right_origin) {
+                        Some(id) => Link::Origin(id),
+                        None => {
+                            let parent = if decoder.read_parent_info()? {
+                                Scope::Root(decoder.read_string()?.to_string())
+                            } else {
+                                Scope::Nested(decoder.read_left_id()?)
+                            };
+                            if info & HAS_PARENT_SUB != 0 {
+                                decoder.read_string()?;
+                            }
+                            Link::Parent(parent)
+                        }
+                    };
+                    let content = ItemContent::decode(&mut decoder, info)?;
+                    (content.len(OffsetKind::Utf16), link)
+                }
+            };
+            blocks.push((clock, link));
+            clock += len;
+        }
+    }
+    Ok(links)
+}
+
+/// Returns the scope of positions anchored to the edges of a `text`. Root-level texts are found in
+/// the registry of root types by their names. Nested texts are identified by items holding them,
+/// but yrs doesn't expose these, so they are recovered from an update encoding the whole document:
+/// items without origins carry their parent explicitly, while all the other ones share the parent
+/// of their origin. Following origins has to start from a character of the text, so `None` is
+/// returned for nested texts which never had any content.
+fn text_scope(text: &Text, txn: &mut YTransaction) -> Option<Scope> {
+    if let Some(root_types) = &txn.root_types {
+        if let Some(name) = root_types.borrow().name_of(text.as_ref()) {
+            return Some(Scope::Root(name.to_string()));
+        }
+    }
+    let mut id = chunks(text, txn)
+        .into_iter()
+        .find_map(|(chunk, _)| match chunk {
+            Chunk::Text(id, _) => Some(id),
+            Chunk::Embed => None,
+        })?;
+    let mut links = decode_links(&txn.encode_diff_v1(&StateVector::default())).ok()?;
+    // origins always point at items which existed before, so following them can't loop
+    loop {
+        let blocks = links.get_mut(&id.client)?;
+        let i = blocks.partition_point(|&(clock, _)| clock <= id.clock);
+        match std::mem::replace(&mut blocks[i.checked_sub(1)?].1, Link::Unknown) {
+            Link::Origin(origin) => id = origin,
+            Link::Parent(parent) => return Some(parent),
+            Link::Unknown => return None,
+        }
+    }
+}
+
+impl StickyIndex {
+    /// Anchors an `index` of a given `text`, measured in `offset_kind` units.
+    pub(crate) fn new(
+        text: &Text,
+        txn: &mut YTransaction,
+        index: u32,
+        assoc: i32,
+        offset_kind: OffsetKind,
+    ) -> PyResult<Self> {
+        if index > text.len() {
+            return Err(PyIndexError::new_err(format!(
+                "Index {index} is out of range of a text with length {}.",
+                text.len()
+            )));
+        }
+        let mut offset = 0;
+        let mut anchors = Vec::new();
+        for (chunk, _) in chunks(text, txn)
+            .into_iter()
+            .filter(|&(_, visible)| visible)
+        {
+            if let Chunk::Text(id, s) = chunk {
+                let mut clock = id.clock;
+                for c in s.chars() {
+                    anchors.push((offset, ID::new(id.client, clock)));
+                    offset += char_len(c, offset_kind);
+                    clock += c.len_utf16() as u32;
+                }
+            } else {
+                offset += 1;
+            }
+        }
+        // the character after the index, or the one before it for negative assoc
+        let anchor = if assoc >= 0 {
+            anchors.into_iter().find(|&(start, _)| start >= index)
+        } else {
+            anchors.into_iter().rev().find(|&(start, _)| start < index)
+        };
+        let scope = match anchor {
+            Some((_, id)) => Scope::Item(id),
+            None => text_scope(text, txn).ok_or_else(|| {
+                PyValueError::new_err(
+                    "Positions at the edges of a nested text can only be created once it has \
+                    some content.",
+                )
+            })?,
+        };
+        Ok(StickyIndex { scope, assoc })
+    }
+
+    /// Returns a current index of this position within a given `text`, measured in `offset_kind`
+    /// units. Returns `None` if the character it was anchored to cannot be found anymore, or if the
+    /// position belongs to a different text.
+    pub(crate) fn resolve(
+        &self,
+        text: &Text,
+        txn: &mut YTransaction,
+        offset_kind: OffsetKind,
+    ) -> Option<u32> {
+        let id = match &self.scope {
+            Scope::Item(id) => id,
+            scope if text_scope(text, txn).as_ref() != Some(scope) => return None,
+            _ if self.assoc >= 0 => return Some(text.len()),
+            _ => return Some(0),
+        };
+        let chunks = chunks(text, txn);
+        let mut index = 0;
+        for (chunk, _) in chunks.iter().filter(|&&(_, visible)| visible) {
+            if let Some((offset, len)) = chunk.locate(id, offset_kind) {
+                let after = if self.assoc >= 0 { 0 } else { len };
+                return Some(index + offset + after);
+            }
+            index += chunk.len(offset_kind);
+        }
+
+        // The anchor was deleted, so its position is right after the visible content preceding
+        // it. Deleted content is only available until it's garbage collected.
+        let mut index = 0;
+        for (chunk, visible) in chunks.iter() {
+            if chunk.locate(id, offset_kind).is_some() {
+                return Some(index);
+            }
+            if *visible {
+                index += chunk.len(offset_kind);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match &self.scope {
+            Scope::Item(id) => {
+                buf.write_var(0u8);
+                buf.write_var(id.client);
+                buf.write_var(id.clock);
+            }
+            Scope::Root(name) => {
+                buf.write_var(1u8);
+                buf.write_string(name);
+            }
+            Scope::Nested(id) => {
+                buf.write_var(2u8);
+                buf.write_var(id.client);
+                buf.write_var(id.clock);
+            }
+        }
+        buf.write_var(self.assoc);
+        buf
+    }
+
+    pub(crate) fn decode(data: &[u8]) -> Result<Self, lib0::error::Error> {
+        let mut cursor = Cursor::new(data);
+        let scope = match cursor.read_var::<u8>()? {
+            0 => Scope::Item(ID::new(cursor.read_var()?, cursor.read_var()?)),
+            1 => Scope::Root(cursor.read_string()?.to_string()),
+            2 => Scope::Nested(ID::new(cursor.read_var()?, cursor.read_var()?)),
+            _ => return Err(lib0::error::Error::UnexpectedValue),
+        };
+        // positions encoded by older Yjs versions don't carry assoc
+        let assoc = if cursor.has_content() {
+            cursor.read_var()?
+        } else {
+            0
+        };
+        Ok(StickyIndex { scope, assoc })
+    }
+}
+
+/// Encodes a given `StickyIndex` into a binary payload, which can be persisted or sent to other
+/// peers. The format is compatible with relative positions encoded by Yjs.
+#[pyfunction]
+pub fn encode_relative_position(position: &StickyIndex) -> PyObject {
+    Python::with_gil(|py| PyBytes::new(py, &position.encode()).into())
+}
+
+/// Decodes a `StickyIndex` from a binary payload created with `encode_relative_position`.
+#[pyfunction]
+pub fn decode_relative_position(data: Vec<u8>) -> PyResult<StickyIndex> {
+    try_decode(|| StickyIndex::decode(&data))
+        .map_err(|e| EncodingException::new_err(format!("Relative position is malformed: {e}")))
+}
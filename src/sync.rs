@@ -0,0 +1,112 @@
+use lib0::decoding::{Cursor, Read};
+use lib0::encoding::Write;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::StateVector;
+
+use crate::y_doc::YDoc;
+use crate::y_transaction::{try_decode, Binary, EncodingException};
+
+/// Sync step 1 carries a state vector of the peer which sends it.
+const MSG_SYNC_STEP_1: u32 = 0;
+/// Sync step 2 carries an update with all changes missing from a state vector of sync step 1.
+const MSG_SYNC_STEP_2: u32 = 1;
+/// Update message carries changes made after the peers were synchronized.
+const MSG_UPDATE: u32 = 2;
+
+/// Creates a sync step 1 message, which opens the synchronization with a remote peer by sending it
+/// a state vector of a given document. The peer answers with a sync step 2 message, created with
+/// `read_sync_step_1` (or `read_sync_message`).
+///
+/// Messages follow the format of the Yjs `y-protocols/sync` module. Note that providers like
+/// `y-websocket` wrap them into envelopes prefixed with their own message type (`0` for sync
+/// messages), which has to be added and stripped by the caller.
+#[pyfunction]
+pub fn sync_step_1(doc: &YDoc) -> PyResult<PyObject> {
+    let sv = doc.commit_with(None, |txn| txn.state_vector().encode_v1())?;
+    Ok(encode_message(MSG_SYNC_STEP_1, &sv))
+}
+
+/// Reads a sync step 1 message sent by a remote peer and returns a sync step 2 message with all
+/// changes of a given document the peer has not seen yet.
+#[pyfunction]
+pub fn read_sync_step_1(doc: &YDoc, msg: Binary) -> PyResult<PyObject> {
+    let sv = expect_message(&msg, MSG_SYNC_STEP_1)?;
+    sync_step_2(doc, &sv)
+}
+
+/// Reads a sync step 2 message sent by a remote peer and applies the update it carries to a given
+/// document. An optional `origin` is passed as `AfterTransactionEvent.origin` to the document's
+/// `observe_after_transaction` callbacks.
+#[pyfunction]
+pub fn read_sync_step_2(doc: &YDoc, msg: Binary, origin: Option<PyObject>) -> PyResult<()> {
+    let update = expect_message(&msg, MSG_SYNC_STEP_2)?;
+    doc.commit_with(origin, |txn| txn.apply_v1(Binary::Owned(update)))?
+}
+
+/// Wraps an update (eg. one received by `observe_after_transaction` callbacks) into a message,
+/// which can be broadcasted to synchronized peers.
+#[pyfunction]
+pub fn write_update(update: Vec<u8>) -> PyObject {
+    encode_message(MSG_UPDATE, &update)
+}
+
+/// Reads any sync protocol message sent by a remote peer. Sync step 1 messages are answered with
+/// a sync step 2 message, which is returned and should be sent back to the peer. Updates carried
+/// by sync step 2 and update messages are applied to a given document with an optional `origin`,
+/// in which case `None` is returned.
+#[pyfunction]
+pub fn read_sync_message(
+    doc: &YDoc,
+    msg: Binary,
+    origin: Option<PyObject>,
+) -> PyResult<Option<PyObject>> {
+    let (msg_type, payload) = decode_message(&msg)?;
+    match msg_type {
+        MSG_SYNC_STEP_1 => Ok(Some(sync_step_2(doc, &payload)?)),
+        MSG_SYNC_STEP_2 | MSG_UPDATE => {
+            doc.commit_with(origin, |txn| txn.apply_v1(Binary::Owned(payload)))??;
+            Ok(None)
+        }
+        other => Err(EncodingException::new_err(format!(
+            "Unknown sync message type: {other}"
+        ))),
+    }
+}
+
+fn sync_step_2(doc: &YDoc, sv: &[u8]) -> PyResult<PyObject> {
+    let sv = try_decode(|| StateVector::decode_v1(sv))
+        .map_err(|e| EncodingException::new_err(format!("Malformed state vector: {e}")))?;
+    let update = doc.commit_with(None, |txn| txn.encode_diff_v1(&sv))?;
+    Ok(encode_message(MSG_SYNC_STEP_2, &update))
+}
+
+fn encode_message(msg_type: u32, payload: &[u8]) -> PyObject {
+    let mut buf = Vec::with_capacity(payload.len() + 8);
+    buf.write_var(msg_type);
+    buf.write_buf(payload);
+    Python::with_gil(|py| PyBytes::new(py, &buf).into())
+}
+
+/// Splits a message into its type and payload.
+fn decode_message(msg: &[u8]) -> PyResult<(u32, Vec<u8>)> {
+    try_decode(|| {
+        let mut cursor = Cursor::new(msg);
+        let msg_type: u32 = cursor.read_var()?;
+        Ok((msg_type, cursor.read_buf()?.to_vec()))
+    })
+    .map_err(|e| EncodingException::new_err(format!("Sync message is malformed: {e}")))
+}
+
+/// Returns a payload of a message, which is expected to be of a given type.
+fn expect_message(msg: &[u8], expected_type: u32) -> PyResult<Vec<u8>> {
+    let (msg_type, payload) = decode_message(msg)?;
+    if msg_type != expected_type {
+        return Err(EncodingException::new_err(format!(
+            "Expected a sync message of type {expected_type}, found: {msg_type}"
+        )));
+    }
+    Ok(payload)
+}
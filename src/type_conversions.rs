@@ -1,31 +1,40 @@
 use lib0::any::Any;
 use pyo3::create_exception;
 use pyo3::exceptions::PyException;
+use pyo3::exceptions::PyOverflowError;
 use pyo3::exceptions::PyTypeError;
 use pyo3::prelude::*;
 use pyo3::types as pytypes;
 use pyo3::types::PyList;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::convert::TryInto;
 use std::ops::Deref;
 use yrs::block::{ItemContent, Prelim};
-use yrs::types::Events;
+use yrs::types::{Event, Events, Path, PathSegment};
 use yrs::types::{Attrs, Branch, BranchPtr, Change, Delta, EntryChange, Value};
 use yrs::{Array, Map, Text, Transaction};
 
-use crate::shared_types::CompatiblePyType;
+use crate::shared_types::{report_observer_error, CompatiblePyType};
 use crate::shared_types::{SharedType, YPyType};
 use crate::y_array::YArray;
 use crate::y_array::YArrayEvent;
 use crate::y_map::YMap;
 use crate::y_map::YMapEvent;
-use crate::y_text::YText;
+use crate::y_text::{PrelimChunk, YText};
 use crate::y_text::YTextEvent;
 use crate::y_xml::YXmlEvent;
 use crate::y_xml::YXmlTextEvent;
 use crate::y_xml::{YXmlElement, YXmlText};
 
+/// Key of a single-entry map, under which `datetime` values are stored as ISO 8601 strings. Such
+/// maps are read back as `datetime` objects.
+pub(crate) const DATETIME_TAG: &str = "__datetime__";
+
+/// Largest integer, which JavaScript numbers can represent exactly.
+const MAX_SAFE_INTEGER: i64 = 2_i64.pow(53) - 1;
+
 create_exception!(y_py, MultipleIntegrationError, PyException, "A Ypy data type instance cannot be integrated into multiple YDocs or the same YDoc multiple times");
 
 pub trait ToPython {
@@ -68,12 +77,26 @@ impl<'a> TryFrom<&'a PyAny> for CompatiblePyType<'a> {
             Ok(Self::Float(f))
         } else if let Ok(s) = py_any.downcast::<pytypes::PyString>() {
             Ok(Self::String(s))
+        } else if let Ok(dt) = py_any.downcast::<pytypes::PyDateTime>() {
+            Ok(Self::DateTime(dt))
+        } else if let Ok(b) = py_any.downcast::<pytypes::PyBytes>() {
+            Ok(Self::Bytes(b))
+        } else if let Ok(b) = py_any.downcast::<pytypes::PyByteArray>() {
+            Ok(Self::ByteArray(b))
         } else if let Ok(list) = py_any.downcast::<pytypes::PyList>() {
             Ok(Self::List(list))
         } else if let Ok(dict) = py_any.downcast::<pytypes::PyDict>() {
             Ok(Self::Dict(dict))
         } else if let Ok(v) = YPyType::try_from(py_any) {
             Ok(Self::YType(v))
+        } else if py_any.downcast::<pytypes::PySet>().is_ok()
+            || py_any.downcast::<pytypes::PyFrozenSet>().is_ok()
+        {
+            // Sets have no stable ordering, so there is no deterministic way to map them onto a
+            // Y sequence. Ask the caller to pick an ordering explicitly instead.
+            Err(PyTypeError::new_err(format!(
+                "Cannot integrate a set into a YDoc: {py_any}. Sets are unordered, convert it into a list first (e.g. `sorted(value)`)."
+            )))
         } else {
             Err(PyTypeError::new_err(format!(
                 "Cannot integrate this type into a YDoc: {py_any}"
@@ -88,6 +111,36 @@ impl<'a> FromPyObject<'a> for CompatiblePyType<'a> {
     }
 }
 
+impl<'a> CompatiblePyType<'a> {
+    /// Checks if this value can be inserted into a YDoc. Preliminary shared types are integrated
+    /// together with their contents, so these are checked as well. Since items cannot be rejected
+    /// once yrs starts integrating them, this has to be called before inserting a value.
+    pub(crate) fn ensure_integrable(&self) -> PyResult<()> {
+        match self {
+            CompatiblePyType::YType(YPyType::Array(v)) if v.borrow().prelim() => {
+                if let SharedType::Prelim(items) = &v.borrow().0 {
+                    let py = v.py();
+                    for item in items {
+                        CompatiblePyType::try_from(item.as_ref(py))?.ensure_integrable()?;
+                    }
+                }
+                Ok(())
+            }
+            CompatiblePyType::YType(YPyType::Map(v)) if v.borrow().prelim() => {
+                if let SharedType::Prelim(entries) = &v.borrow().0 {
+                    let py = v.py();
+                    for value in entries.values() {
+                        CompatiblePyType::try_from(value.as_ref(py))?.ensure_integrable()?;
+                    }
+                }
+                Ok(())
+            }
+            CompatiblePyType::YType(v) if v.is_prelim() => Ok(()),
+            value => Any::try_from(value.clone()).map(|_| ()),
+        }
+    }
+}
+
 
 impl ToPython for Delta {
     fn into_py(self, py: Python) -> PyObject {
@@ -118,7 +171,7 @@ impl ToPython for Delta {
     }
 }
 
-fn attrs_into_py(attrs: &Attrs) -> PyObject {
+pub(crate) fn attrs_into_py(attrs: &Attrs) -> PyObject {
     Python::with_gil(|py| {
         let o = pytypes::PyDict::new(py);
         for (key, value) in attrs.iter() {
@@ -228,6 +281,9 @@ impl<'a> From<CompatiblePyType<'a>> for PyObject {
             CompatiblePyType::Int(i) => i.into(),
             CompatiblePyType::Float(f) => f.into(),
             CompatiblePyType::String(s) => s.into(),
+            CompatiblePyType::Bytes(b) => b.into(),
+            CompatiblePyType::ByteArray(b) => b.into(),
+            CompatiblePyType::DateTime(dt) => dt.into(),
             CompatiblePyType::List(list) => list.into(),
             CompatiblePyType::Dict(dict) => dict.into(),
             CompatiblePyType::YType(y_type) => y_type.into(),
@@ -268,17 +324,34 @@ impl<'a> Prelim for CompatiblePyType<'a> {
                         let text = Text::from(inner_ref);
                         let mut y_text = v.borrow_mut();
 
-                        if let SharedType::Prelim(v) = y_text.0.to_owned() {
-                            text.push(txn, v.as_str());
+                        if let SharedType::Prelim(v) = &y_text.0 {
+                            // empty attributes prevent chunks from inheriting formatting of embeds
+                            for chunk in YText::prelim_chunks(v, &y_text.1) {
+                                let len = text.len();
+                                match chunk {
+                                    PrelimChunk::Text(v) => {
+                                        text.insert_with_attributes(txn, len, &v, Attrs::new())
+                                    }
+                                    PrelimChunk::Embed(embed, attrs) => {
+                                        let attrs = attrs.unwrap_or_default();
+                                        text.insert_embed_with_attributes(txn, len, embed, attrs)
+                                    }
+                                }
+                            }
                         }
                         y_text.0 = SharedType::Integrated(text.clone());
+                        y_text.1.clear();
                     }
                     YPyType::Array(v) => {
                         let array = Array::from(inner_ref);
                         let mut y_array = v.borrow_mut();
                         if let SharedType::Prelim(items) = y_array.0.to_owned() {
                             let len = array.len();
-                            YArray::insert_multiple_at(&array, txn, len, items);
+                            // integration can't fail, so the error is raised once the transaction
+                            // is committed, like the ones of observer callbacks
+                            if let Err(err) = YArray::insert_multiple_at(&array, txn, len, items) {
+                                Python::with_gil(|py| report_observer_error(py, err));
+                            }
                         }
                         y_array.0 = SharedType::Integrated(array.clone());
                     }
@@ -311,19 +384,28 @@ impl<'a> TryFrom<CompatiblePyType<'a>> for Any {
     type Error = PyErr;
 
     fn try_from(py_type: CompatiblePyType<'a>) -> Result<Self, Self::Error> {
-        const MAX_JS_NUMBER: i64 = 2_i64.pow(53) - 1;
         match py_type {
             CompatiblePyType::Bool(b) => Ok(Any::Bool(b.extract()?)),
             CompatiblePyType::String(s) => Ok(Any::String(s.extract::<String>()?.into_boxed_str())),
-            CompatiblePyType::Int(i) => {
-                let num: i64 = i.extract()?;
-                if num > MAX_JS_NUMBER {
-                    Ok(Any::BigInt(num))
-                } else {
+            // Integers are stored as numbers, so that Yjs peers can use them in arithmetic. Only
+            // the ones which numbers can't represent exactly are stored as `BigInt`s.
+            CompatiblePyType::Int(i) => match i.extract::<i64>() {
+                Ok(num) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&num) => {
                     Ok(Any::Number(num as f64))
                 }
-            }
+                Ok(num) => Ok(Any::BigInt(num)),
+                Err(_) => Err(PyOverflowError::new_err(format!(
+                    "Cannot integrate {i} into a YDoc: integers have to fit into 64 bits."
+                ))),
+            },
             CompatiblePyType::Float(f) => Ok(Any::Number(f.extract()?)),
+            CompatiblePyType::Bytes(b) => Ok(Any::Buffer(b.as_bytes().into())),
+            CompatiblePyType::ByteArray(b) => Ok(Any::Buffer(b.to_vec().into_boxed_slice())),
+            CompatiblePyType::DateTime(dt) => {
+                let iso: String = dt.call_method0("isoformat")?.extract()?;
+                let tagged = HashMap::from([(DATETIME_TAG.to_string(), Any::String(iso.into()))]);
+                Ok(Any::Map(Box::new(tagged)))
+            }
             CompatiblePyType::List(l) => {
                 let result: PyResult<Vec<Any>> = l
                     .into_iter()
@@ -343,6 +425,9 @@ impl<'a> TryFrom<CompatiblePyType<'a>> for Any {
                 result.map(|res| Any::Map(Box::new(res)))
             },
             CompatiblePyType::None => Ok(Any::Null),
+            CompatiblePyType::YType(v) if v.is_prelim() => Err(PyTypeError::new_err(format!(
+                    "Cannot convert a preliminary Ypy shared type into a plain value: {v}. Shared types can only be nested directly inside of a YArray or YMap."
+                ))),
             CompatiblePyType::YType(v) => Err(MultipleIntegrationError::new_err(format!(
                     "Cannot integrate a nested Ypy object because is already integrated into a YDoc: {v}"
                 ))),
@@ -386,18 +471,26 @@ impl<'a> TryFrom<&'a PyAny> for YPyType<'a> {
     }
 }
 
+/// Converts a number into an `int` when it has no fractional part and it's within the range of
+/// integers represented exactly, or into a `float` otherwise. Yjs has a single number type, so
+/// `float`s without a fractional part are read back as `int`s as well.
+pub(crate) fn number_into_py(py: Python, num: f64) -> PyObject {
+    if num.fract() == 0.0 && num.abs() <= MAX_SAFE_INTEGER as f64 {
+        (num as i64).into_py(py)
+    } else {
+        num.into_py(py)
+    }
+}
+
 impl ToPython for Any {
     fn into_py(self, py: Python) -> pyo3::PyObject {
         match self {
             Any::Null | Any::Undefined => py.None(),
             Any::Bool(v) => v.into_py(py),
-            Any::Number(v) => v.into_py(py),
+            Any::Number(v) => number_into_py(py, v),
             Any::BigInt(v) => v.into_py(py),
             Any::String(v) => v.into_py(py),
-            Any::Buffer(v) => {
-                let byte_array = pytypes::PyByteArray::new(py, v.as_ref());
-                byte_array.into()
-            }
+            Any::Buffer(v) => pytypes::PyBytes::new(py, v.as_ref()).into(),
             Any::Array(v) => {
                 let mut a = Vec::new();
                 for value in v.iter() {
@@ -406,6 +499,12 @@ impl ToPython for Any {
                 }
                 a.into_py(py)
             }
+            Any::Map(v) if v.len() == 1 && v.contains_key(DATETIME_TAG) => {
+                datetime_into_py(py, &v[DATETIME_TAG]).unwrap_or_else(|| {
+                    let m: HashMap<_, _> = v.iter().map(|(k, v)| (k, v.to_owned())).collect();
+                    m.into_py(py)
+                })
+            }
             Any::Map(v) => {
                 let mut m = HashMap::new();
                 for (k, v) in v.iter() {
@@ -418,6 +517,82 @@ impl ToPython for Any {
     }
 }
 
+/// Returns a value of an element of a preliminary shared type suitable for equality checks, with
+/// nested preliminary shared types replaced by their JSON-like contents.
+pub(crate) fn comparable_value(item: &PyAny) -> PyResult<PyObject> {
+    let py = item.py();
+    if let Ok(text) = item.extract::<PyRef<YText>>() {
+        Ok(text.__str__().into_py(py))
+    } else if let Ok(array) = item.extract::<PyRef<YArray>>() {
+        Ok(array.to_any()?.into_py(py))
+    } else if let Ok(map) = item.extract::<PyRef<YMap>>() {
+        Ok(map.to_any()?.into_py(py))
+    } else {
+        Ok(item.into())
+    }
+}
+
+/// Returns a kind of a value stored in a shared type: `"text"`, `"array"`, `"map"`, `"xml"` or
+/// `"primitive"` for values which are not shared types.
+pub(crate) fn value_kind(value: &Value) -> &'static str {
+    match value {
+        Value::YText(_) => "text",
+        Value::YArray(_) => "array",
+        Value::YMap(_) => "map",
+        Value::YXmlElement(_) | Value::YXmlText(_) => "xml",
+        Value::Any(_) => "primitive",
+    }
+}
+
+/// Returns a kind of an element of a preliminary shared type, as described by `value_kind`.
+pub(crate) fn prelim_value_kind(item: &PyAny) -> &'static str {
+    if item.downcast::<PyCell<YText>>().is_ok() {
+        "text"
+    } else if item.downcast::<PyCell<YArray>>().is_ok() {
+        "array"
+    } else if item.downcast::<PyCell<YMap>>().is_ok() {
+        "map"
+    } else if item.downcast::<PyCell<YXmlElement>>().is_ok()
+        || item.downcast::<PyCell<YXmlText>>().is_ok()
+    {
+        "xml"
+    } else {
+        "primitive"
+    }
+}
+
+/// Compares paths of shared types in the order in which they appear in a document, with parents
+/// preceding their children.
+fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    for (a, b) in a.iter().zip(b.iter()) {
+        let ordering = match (a, b) {
+            (PathSegment::Index(a), PathSegment::Index(b)) => a.cmp(b),
+            (PathSegment::Key(a), PathSegment::Key(b)) => a.cmp(b),
+            // siblings always share a parent, so this can't really happen
+            (PathSegment::Index(_), PathSegment::Key(_)) => Ordering::Less,
+            (PathSegment::Key(_), PathSegment::Index(_)) => Ordering::Greater,
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+/// Reconstructs a `datetime` from its tagged ISO 8601 representation. Returns `None` if the value
+/// is not a valid ISO 8601 string, in which case the map holding it is read as a regular dict.
+fn datetime_into_py(py: Python, iso: &Any) -> Option<PyObject> {
+    let iso = match iso {
+        Any::String(iso) => iso.as_ref(),
+        _ => return None,
+    };
+    let datetime = py.import("datetime").ok()?.getattr("datetime").ok()?;
+    datetime
+        .call_method1("fromisoformat", (iso,))
+        .map(|dt| dt.into())
+        .ok()
+}
+
 impl ToPython for Value {
     fn into_py(self, py: Python) -> pyo3::PyObject {
         match self {
@@ -431,9 +606,15 @@ impl ToPython for Value {
     }
 }
 
+/// Converts events passed to deep observers into a Python list, ordered the way they appear in
+/// a document: events of parent types come before the ones of their children, while events of
+/// siblings are ordered by their indexes in arrays and by their keys in maps. yrs only orders them
+/// by the depth of their targets.
 pub(crate) fn events_into_py(txn: &Transaction, events: &Events) -> PyObject {
+    let mut events: Vec<(Path, &Event)> = events.iter().map(|e| (e.path(), e)).collect();
+    events.sort_by(|(a, _), (b, _)| compare_paths(a, b));
     Python::with_gil(|py| {
-        let py_events = events.iter().map(|event| match event {
+        let py_events = events.into_iter().map(|(_, event)| match event {
             yrs::types::Event::Text(e_txt) => YTextEvent::new(e_txt, txn).into_py(py),
             yrs::types::Event::Array(e_arr) => YArrayEvent::new(e_arr, txn).into_py(py),
             yrs::types::Event::Map(e_map) => YMapEvent::new(e_map, txn).into_py(py),
@@ -1,23 +1,30 @@
+use std::cell::{Cell, RefCell};
 use std::convert::{TryFrom, TryInto};
+use std::rc::Rc;
 
-use crate::json_builder::JsonBuilder;
+use crate::json_builder::{json_value_into_py, JsonBuilder};
+use crate::msgpack;
 use crate::shared_types::{
-    CompatiblePyType, DeepSubscription, DefaultPyErr, PreliminaryObservationException,
-    ShallowSubscription, SubId,
+    normalize_index, report_observer_error, CompatiblePyType, DeepSubscription, DefaultPyErr,
+    IntegratedOperationException, Observed, PreliminaryObservationException, ShallowSubscription,
+    SubId,
 };
-use crate::type_conversions::events_into_py;
+use crate::type_conversions::{comparable_value, events_into_py, prelim_value_kind, value_kind};
+use crate::y_doc::{clock_ranges_into_py, YDoc};
+use crate::y_map::YMap;
 use crate::y_transaction::YTransaction;
 
 use super::shared_types::SharedType;
 use crate::type_conversions::ToPython;
 use lib0::any::Any;
-use pyo3::exceptions::PyIndexError;
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
 
 use crate::type_conversions::PyObjectWrapper;
+use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
-use pyo3::types::{PyList, PySlice, PySliceIndices};
+use pyo3::types::{PyBytes, PyList, PySlice, PySliceIndices};
 use yrs::types::array::ArrayEvent;
-use yrs::types::DeepObservable;
+use yrs::types::{DeepObservable, Value};
 use yrs::{Array, SubscriptionId, Transaction};
 
 /// A collection used to store data in an indexed sequence structure. This type is internally
@@ -34,6 +41,9 @@ use yrs::{Array, SubscriptionId, Transaction};
 /// - Embedded and binary values: they count as a single element even though they correspond of
 ///   multiple bytes.
 ///
+/// Both reading and editing methods accept negative indexes, which count from the end of the
+/// array, like in Python.
+///
 /// Like all Yrs shared data types, YArray is resistant to the problem of interleaving (situation
 /// when elements inserted one after another may interleave with other peers concurrent inserts
 /// after merging all updates together). In case of Yrs conflict resolution is solved by using
@@ -74,7 +84,33 @@ impl YArray {
         }
     }
 
-    /// Returns a number of elements stored within this instance of `YArray`.
+    /// Opens a new transaction on a given `doc` this array belongs to and calls `callback` with
+    /// that transaction and this array as arguments. The transaction is committed once `callback`
+    /// returns, and its result is passed through.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// array = doc.get_array('name')
+    /// array.transact(doc, lambda txn, a: a.extend(txn, [1, 2, 3]))
+    /// ```
+    pub fn transact(
+        slf: &PyCell<Self>,
+        doc: &PyCell<YDoc>,
+        callback: PyObject,
+    ) -> PyResult<PyObject> {
+        if slf.borrow().prelim() {
+            return Err(IntegratedOperationException::default_message());
+        }
+        YDoc::transact_with(doc, slf.into(), callback)
+    }
+
+    /// Returns a number of elements stored within this instance of `YArray`. It doesn't open a
+    /// transaction, so it can be used while another one is open, in which case it includes changes
+    /// made with that transaction so far.
     pub fn __len__(&self) -> usize {
         match &self.0 {
             SharedType::Integrated(v) => v.len() as usize,
@@ -100,6 +136,26 @@ impl YArray {
         format!("YArray({})", self.__str__())
     }
 
+    /// Arrays are equal when their elements are equal, with nested shared types compared using
+    /// their JSON-like contents. They can be compared with plain lists as well. Since equality is
+    /// based on contents, arrays are not hashable.
+    pub fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other: PyObject = if let Ok(array) = other.extract::<PyRef<YArray>>() {
+            array.comparable_items(py)?.into_py(py)
+        } else if other.downcast::<PyList>().is_ok() {
+            other.into()
+        } else {
+            return Ok(py.NotImplemented());
+        };
+        let this = PyList::new(py, self.comparable_items(py)?);
+        Ok(match op {
+            CompareOp::Eq => this.eq(other)?.into_py(py),
+            CompareOp::Ne => this.ne(other)?.into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
     /// Converts an underlying contents of this `YArray` instance into their JSON representation.
     pub fn to_json(&self) -> PyResult<String> {
         let mut json_builder = JsonBuilder::new();
@@ -109,34 +165,98 @@ impl YArray {
         }
         Ok(json_builder.into())
     }
+
+    /// Returns contents of this `YArray` instance as plain Python lists and dicts, which can be
+    /// serialized with `json.dumps`. Nested shared types are converted recursively, with texts read
+    /// as strings. Binary values are represented as base64 encoded strings and datetimes as ISO 8601
+    /// strings.
+    pub fn to_json_value(&self) -> PyResult<PyObject> {
+        let value = self.to_any()?;
+        Python::with_gil(|py| json_value_into_py(py, &value))
+    }
+
+    /// Returns contents of this `YArray` instance as plain Python lists and dicts, converting nested
+    /// shared types recursively in a single pass, with texts read as strings. Unlike
+    /// `to_json_value`, binary values and datetimes are kept as `bytes` and `datetime` objects.
+    pub fn to_python(&self) -> PyResult<PyObject> {
+        let value = self.to_any()?;
+        Ok(Python::with_gil(|py| value.into_py(py)))
+    }
+
+    /// Returns elements of this `YArray` instance as a Python list. Nested shared types are kept
+    /// as `YArray`, `YMap` or `YText` instances, unless `deep` is set, in which case they are
+    /// converted the same way as with `to_python`.
+    pub fn to_list(&self, deep: Option<bool>) -> PyResult<PyObject> {
+        if deep.unwrap_or(false) {
+            return self.to_python();
+        }
+        Python::with_gil(|py| {
+            let items: Vec<PyObject> = match &self.0 {
+                SharedType::Integrated(arr) => arr.iter().map(|v| v.into_py(py)).collect(),
+                SharedType::Prelim(items) => items.clone(),
+            };
+            Ok(items.into_py(py))
+        })
+    }
+
+    /// Serializes current contents of this `YArray` using MessagePack format, which is more compact
+    /// than JSON and widely supported across languages. Binary values are written using MessagePack
+    /// `bin` type. Only the materialized value gets serialized, not the CRDT metadata required to
+    /// merge it with other replicas.
+    pub fn to_msgpack(&self, _txn: &YTransaction) -> PyResult<PyObject> {
+        let payload = msgpack::encode(&self.to_any()?);
+        Ok(Python::with_gil(|py| PyBytes::new(py, &payload).into()))
+    }
+
+    /// Creates a new preliminary instance of `YArray` from a MessagePack-encoded list, as produced
+    /// by `YArray.to_msgpack`. Binary values are read as `bytes`.
+    #[staticmethod]
+    pub fn from_msgpack(data: Vec<u8>) -> PyResult<YArray> {
+        match msgpack::decode(&data)? {
+            Any::Array(items) => Python::with_gil(|py| {
+                let items = items.into_vec().into_iter().map(|item| item.into_py(py));
+                Ok(YArray(SharedType::prelim(items.collect())))
+            }),
+            other => Err(PyTypeError::new_err(format!(
+                "Cannot create a YArray from a MessagePack value, which is not a list: {other}"
+            ))),
+        }
+    }
     /// Adds a single item to the provided index in the array.
-    pub fn insert(&mut self, txn: &mut YTransaction, index: u32, item: PyObject) -> PyResult<()> {
+    pub fn insert(&mut self, txn: &mut YTransaction, index: i64, item: PyObject) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = normalize_index(index, self.__len__(), "an array")?;
+        self.check_range(index, 0)?;
         match &mut self.0 {
-            SharedType::Integrated(array) if array.len() >= index => {
+            SharedType::Integrated(array) => {
+                Python::with_gil(|py| {
+                    CompatiblePyType::try_from(item.as_ref(py))?.ensure_integrable()
+                })?;
                 array.insert(txn, index, PyObjectWrapper(item));
                 Ok(())
             }
-            SharedType::Prelim(vec) if vec.len() >= index as usize => {
-                Ok(vec.insert(index as usize, item))
+            SharedType::Prelim(vec) => {
+                vec.insert(index as usize, item);
+                Ok(())
             }
-            _ => Err(PyIndexError::default_message()),
         }
     }
 
     /// Inserts a given range of `items` into this `YArray` instance, starting at given `index`.
+    /// Consecutive plain values are inserted as a single block.
     pub fn insert_range(
         &mut self,
         txn: &mut YTransaction,
-        index: u32,
+        index: i64,
         items: PyObject,
     ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = normalize_index(index, self.__len__(), "an array")?;
+        self.check_range(index, 0)?;
         let items = Self::py_iter(items)?;
         match &mut self.0 {
-            SharedType::Integrated(array) if array.len() >= index => {
-                Self::insert_multiple_at(array, txn, index, items);
-                Ok(())
-            }
-            SharedType::Prelim(vec) if vec.len() >= index as usize => {
+            SharedType::Integrated(array) => Self::insert_multiple_at(array, txn, index, items),
+            SharedType::Prelim(vec) => {
                 let mut j = index;
                 for el in items {
                     vec.insert(j as usize, el);
@@ -144,56 +264,111 @@ impl YArray {
                 }
                 Ok(())
             }
-            _ => Err(PyIndexError::default_message()),
         }
     }
 
     /// Appends a range of `items` at the end of this `YArray` instance.
     pub fn extend(&mut self, txn: &mut YTransaction, items: PyObject) -> PyResult<()> {
-        let index = self.__len__() as u32;
+        txn.ensure_writable()?;
+        let index = self.__len__() as i64;
         self.insert_range(txn, index, items)
     }
     /// Adds a single item to the end of the array
-    pub fn append(&mut self, txn: &mut YTransaction, item: PyObject) {
+    pub fn append(&mut self, txn: &mut YTransaction, item: PyObject) -> PyResult<()> {
+        txn.ensure_writable()?;
         match &mut self.0 {
-            SharedType::Integrated(array) => array.push_back(txn, PyObjectWrapper(item)),
+            SharedType::Integrated(array) => {
+                Python::with_gil(|py| {
+                    CompatiblePyType::try_from(item.as_ref(py))?.ensure_integrable()
+                })?;
+                array.push_back(txn, PyObjectWrapper(item))
+            }
             SharedType::Prelim(vec) => vec.push(item),
         }
+        Ok(())
     }
     /// Removes the element that the given index from the list.
-    pub fn delete(&mut self, txn: &mut YTransaction, index: u32) -> PyResult<()> {
+    pub fn delete(&mut self, txn: &mut YTransaction, index: i64) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = normalize_index(index, self.__len__(), "an array")?;
+        self.check_range(index, 1)?;
         match &mut self.0 {
-            SharedType::Integrated(v) if index < v.len() => Ok(v.remove(txn, index)),
-            SharedType::Prelim(v) if index < v.len() as u32 => {
+            SharedType::Integrated(v) => v.remove(txn, index),
+            SharedType::Prelim(v) => {
                 v.remove(index as usize);
-                Ok(())
             }
-            _ => Err(PyIndexError::default_message()),
         }
+        Ok(())
     }
 
     /// Deletes a range of items of given `length` from current `YArray` instance,
     /// starting from given `index`.
-    pub fn delete_range(&mut self, txn: &mut YTransaction, index: u32, length: u32) {
+    pub fn delete_range(
+        &mut self,
+        txn: &mut YTransaction,
+        index: i64,
+        length: u32,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = normalize_index(index, self.__len__(), "an array")?;
+        self.check_range(index, length)?;
         match &mut self.0 {
             SharedType::Integrated(v) => v.remove_range(txn, index, length),
             SharedType::Prelim(v) => {
                 v.drain((index as usize)..(index + length) as usize);
             }
         }
+        Ok(())
     }
 
-    /// Moves the element from the index source to target.
-    pub fn move_to(&mut self, txn: &mut YTransaction, source: u32, target: u32) -> PyResult<()> {
+    /// Removes an element at a given `index` (the last one by default) and returns it. Like with
+    /// `YMap.pop`, removed shared types are returned as preliminary copies of their contents.
+    pub fn pop(&mut self, txn: &mut YTransaction, index: Option<isize>) -> PyResult<PyObject> {
+        txn.ensure_writable()?;
+        let index = Self::checked_index(self.__len__(), index.unwrap_or(-1))?;
         match &mut self.0 {
-            SharedType::Integrated(v) => {
-                v.move_to(txn, source, target);
-                Ok(())
+            SharedType::Integrated(array) => {
+                // read before removal, as removed shared types lose their contents
+                let value = array.get(index as u32).unwrap();
+                let value = Python::with_gil(|py| YMap::detach(value, py));
+                array.remove(txn, index as u32);
+                Ok(value)
             }
-            SharedType::Prelim(_) if source < 0 as u32 || target < 0 as u32 => {
-                Err(PyIndexError::default_message())
+            SharedType::Prelim(items) => Ok(items.remove(index)),
+        }
+    }
+
+    /// Removes all elements from this `YArray` instance.
+    pub fn clear(&mut self, txn: &mut YTransaction) -> PyResult<()> {
+        txn.ensure_writable()?;
+        match &mut self.0 {
+            SharedType::Integrated(v) => {
+                let len = v.len();
+                if len > 0 {
+                    v.remove_range(txn, 0, len);
+                }
             }
-            SharedType::Prelim(v) if source < v.len() as u32 && target < v.len() as u32 => {
+            SharedType::Prelim(v) => v.clear(),
+        }
+        Ok(())
+    }
+
+    /// Moves the element from the index `source` to `target`, so that it ends up right before the
+    /// element that was at `target` before the move. Use the length of the array as `target` to
+    /// move an element to its end.
+    ///
+    /// Unlike deleting an element and inserting it again, a moved element keeps its identity, so
+    /// concurrent moves of the same element made by different peers don't produce duplicates.
+    /// Raises `IndexError` if either index is out of range.
+    pub fn move_to(&mut self, txn: &mut YTransaction, source: u32, target: u32) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let len = self.__len__() as u32;
+        if source >= len || target > len {
+            return Err(PyIndexError::default_message());
+        }
+        match &mut self.0 {
+            SharedType::Integrated(v) => v.move_to(txn, source, target),
+            SharedType::Prelim(v) => {
                 if source < target {
                     let el = v.remove(source as usize);
                     v.insert((target - 1) as usize, el);
@@ -201,10 +376,9 @@ impl YArray {
                     let el = v.remove(source as usize);
                     v.insert(target as usize, el);
                 }
-                Ok(())
             }
-            _ => Err(PyIndexError::default_message()),
         }
+        Ok(())
     }
 
     /// Moves all elements found within `start`..`end` indexes range (both side inclusive) into
@@ -231,25 +405,20 @@ impl YArray {
         end: u32,
         target: u32,
     ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let len = self.__len__() as u32;
+        if start >= len || end >= len || target > len {
+            return Err(PyIndexError::default_message());
+        }
         match &mut self.0 {
             SharedType::Integrated(v) => {
                 v.move_range_to(txn, start, true, end, false, target);
                 Ok(())
             }
 
-            // y-rs does nothing if end < start
-            // SharedType::Prelim(_) if end < start => Err(PyIndexError::default_message()),
-            SharedType::Prelim(_) if start < 0 as u32 || end < 0 as u32 || target < 0 as u32 => {
-                Err(PyIndexError::default_message())
-            }
-            SharedType::Prelim(v)
-                if start > v.len() as u32 || end > v.len() as u32 || target > v.len() as u32 =>
-            {
-                Err(PyIndexError::default_message())
-            }
-
-            // It doesn't make sense to move a range into the same range (it's basically a no-op).
-            SharedType::Prelim(_) if target >= start && target <= end => Ok(()),
+            // Same as y-rs, do nothing if end < start. It doesn't make sense to move a range into
+            // the same range either (it's basically a no-op).
+            SharedType::Prelim(_) if end < start || (target >= start && target <= end) => Ok(()),
 
             SharedType::Prelim(v) => {
                 let mut i: usize = 0;
@@ -279,8 +448,117 @@ impl YArray {
         }
     }
 
+    /// Returns a kind of an element at a given `index`, without converting it into a Python object:
+    /// `"text"`, `"array"`, `"map"` or `"xml"` for nested shared types, and `"primitive"` for any
+    /// other value. Negative indexes count from the end of this array.
+    pub fn get_type(&self, index: isize) -> PyResult<&'static str> {
+        let index = Self::checked_index(self.__len__(), index)?;
+        match &self.0 {
+            SharedType::Integrated(v) => v
+                .get(index as u32)
+                .map(|value| value_kind(&value))
+                .ok_or_else(PyIndexError::default_message),
+            SharedType::Prelim(v) => Ok(Python::with_gil(|py| {
+                prelim_value_kind(v[index].as_ref(py))
+            })),
+        }
+    }
+
+    /// Replaces an element at a given `index`, or elements selected by a slice with items of a
+    /// given iterable. Only preliminary arrays can be modified this way: an integrated array doesn't
+    /// know the document it belongs to, so it can't open a transaction on its own.
+    pub fn __setitem__(&mut self, index: Index, value: PyObject) -> PyResult<()> {
+        let items = self.prelim_items_mut()?;
+        match index {
+            Index::Int(index) => {
+                let index = Self::checked_index(items.len(), index)?;
+                items[index] = value;
+            }
+            Index::Slice(slice) => {
+                let PySliceIndices {
+                    start,
+                    stop,
+                    step,
+                    slicelength,
+                } = slice.indices(items.len().try_into().unwrap())?;
+                let values = Self::py_iter(value)?;
+                if step == 1 {
+                    items.splice(start as usize..stop.max(start) as usize, values);
+                } else if values.len() as isize != slicelength {
+                    return Err(PyValueError::new_err(format!(
+                        "attempt to assign sequence of size {} to extended slice of size {}",
+                        values.len(),
+                        slicelength
+                    )));
+                } else {
+                    for (i, value) in values.into_iter().enumerate() {
+                        items[(start + i as isize * step) as usize] = value;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes an element at a given `index`, or all elements selected by a slice. Like
+    /// `__setitem__`, it only works with preliminary arrays.
+    pub fn __delitem__(&mut self, index: Index) -> PyResult<()> {
+        let items = self.prelim_items_mut()?;
+        match index {
+            Index::Int(index) => {
+                let index = Self::checked_index(items.len(), index)?;
+                items.remove(index);
+            }
+            Index::Slice(slice) => {
+                let PySliceIndices {
+                    start,
+                    step,
+                    slicelength,
+                    ..
+                } = slice.indices(items.len().try_into().unwrap())?;
+                let mut positions: Vec<usize> = (0..slicelength)
+                    .map(|i| (start + i * step) as usize)
+                    .collect();
+                // removing from the back keeps the remaining positions valid
+                positions.sort_unstable_by(|a, b| b.cmp(a));
+                for i in positions {
+                    items.remove(i);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the position of the first element of this `YArray` equal to a given `value`, raising
+    /// `ValueError` if there is none. Nested shared types are compared using their JSON-like
+    /// contents, so a nested `YArray` is equal to a list with the same elements.
+    pub fn index(&self, py: Python, value: PyObject) -> PyResult<usize> {
+        for (i, item) in self.comparable_items(py)?.iter().enumerate() {
+            if item.as_ref(py).eq(&value)? {
+                return Ok(i);
+            }
+        }
+        Err(PyValueError::new_err(format!(
+            "{} is not in YArray",
+            value.as_ref(py).repr()?
+        )))
+    }
+
+    /// Returns a number of elements of this `YArray` equal to a given `value`. Nested shared types
+    /// are compared the same way as in `YArray.index`.
+    pub fn count(&self, py: Python, value: PyObject) -> PyResult<usize> {
+        let mut count = 0;
+        for item in self.comparable_items(py)? {
+            if item.as_ref(py).eq(&value)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     /// Returns an iterator that can be used to traverse over the values stored withing this
-    /// instance of `YArray`.
+    /// instance of `YArray`. Values are read lazily one at a time, so the array is never copied as
+    /// a whole. If the array shrinks during the iteration, the iterator stops early.
     ///
     /// Example:
     ///
@@ -294,34 +572,90 @@ impl YArray {
     ///     print(item)
     ///     
     /// ```
-    pub fn __iter__(&self) -> PyObject {
-        Python::with_gil(|py| {
-            let list: PyObject = match &self.0 {
-                SharedType::Integrated(arr) => arr.to_json().into_py(py),
-                SharedType::Prelim(arr) => arr.clone().into_py(py),
-            };
-            let any = list.as_ref(py);
-            any.iter().unwrap().into_py(py)
-        })
+    pub fn __iter__(slf: PyRef<Self>) -> YArrayIterator {
+        let changed = Rc::new(Cell::new(false));
+        let subscription = match &slf.0 {
+            SharedType::Integrated(array) => {
+                let mut array = array.clone();
+                let flag = changed.clone();
+                let id: SubscriptionId = array.observe(move |_, _| flag.set(true)).into();
+                Some((array, id))
+            }
+            SharedType::Prelim(_) => None,
+        };
+        YArrayIterator {
+            array: slf.into(),
+            index: 0,
+            buffer: Vec::new(),
+            len: 0,
+            changed,
+            subscription,
+        }
     }
 
     /// Subscribes to all operations happening over this instance of `YArray`. All changes are
     /// batched and eventually triggered during transaction commit phase.
     /// Returns a `SubscriptionId` which can be used to cancel the callback with `unobserve`.
-    pub fn observe(&mut self, f: PyObject) -> PyResult<ShallowSubscription> {
+    ///
+    /// If `with_snapshot` is set, every event exposes a `before` list with the array contents from
+    /// before the transaction. This requires materializing the whole array on every change, which
+    /// is why it's disabled by default.
+    ///
+    /// If `deep` is set, the callback works the same way as with `observe_deep`, receiving a list
+    /// of events from this array and all of its nested children.
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        with_snapshot: Option<bool>,
+        deep: Option<bool>,
+    ) -> PyResult<PyObject> {
+        if deep.unwrap_or(false) {
+            if with_snapshot.unwrap_or(false) {
+                return Err(PyValueError::new_err(
+                    "Snapshots are not supported by deep observers.",
+                ));
+            }
+            return Ok(self.observe_deep(f)?.into_py(py));
+        }
         match &mut self.0 {
             SharedType::Integrated(array) => {
+                // Contents of the array as of the last observed transaction.
+                let snapshot = with_snapshot
+                    .unwrap_or(false)
+                    .then(|| Rc::new(RefCell::new(array.to_json())));
+                let before = snapshot.clone();
+                let f = Rc::new(f);
+                let callback = Rc::downgrade(&f);
                 let sub: SubscriptionId = array
                     .observe(move |txn, e| {
                         Python::with_gil(|py| {
-                            let event = YArrayEvent::new(e, txn);
+                            let mut event = YArrayEvent::new(e, txn);
+                            if let Some(before) = &before {
+                                event.before = Some(before.borrow().clone().into_py(py));
+                            }
                             if let Err(err) = f.call1(py, (event,)) {
-                                err.restore(py)
+                                report_observer_error(py, err)
                             }
                         })
                     })
                     .into();
-                Ok(ShallowSubscription(sub))
+                let sub = ShallowSubscription::new(sub, Observed::Array(array.clone()), callback);
+                Ok(match snapshot {
+                    // Deep observers run after all shallow ones, so refreshing the snapshot from
+                    // one also catches edits made inside nested types.
+                    Some(snapshot) => {
+                        let target = array.clone();
+                        let snapshot_id: SubscriptionId = array
+                            .observe_deep(move |_, _| {
+                                snapshot.replace(target.to_json());
+                            })
+                            .into();
+                        sub.with_snapshot(snapshot_id)
+                    }
+                    None => sub,
+                }
+                .into_py(py))
             }
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
@@ -330,17 +664,23 @@ impl YArray {
     pub fn observe_deep(&mut self, f: PyObject) -> PyResult<DeepSubscription> {
         match &mut self.0 {
             SharedType::Integrated(array) => {
+                let f = Rc::new(f);
+                let callback = Rc::downgrade(&f);
                 let sub: SubscriptionId = array
                     .observe_deep(move |txn, events| {
                         Python::with_gil(|py| {
                             let events = events_into_py(txn, events);
                             if let Err(err) = f.call1(py, (events,)) {
-                                err.restore(py)
+                                report_observer_error(py, err)
                             }
                         })
                     })
                     .into();
-                Ok(DeepSubscription(sub))
+                Ok(DeepSubscription::new(
+                    sub,
+                    Observed::Array(array.clone()),
+                    callback,
+                ))
             }
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
@@ -349,16 +689,56 @@ impl YArray {
     /// Cancels the callback of an observer using the Subscription ID returned from the `observe` method.
     pub fn unobserve(&mut self, subscription_id: SubId) -> PyResult<()> {
         match &mut self.0 {
-            SharedType::Integrated(arr) => Ok(match subscription_id {
-                SubId::Shallow(ShallowSubscription(id)) => arr.unobserve(id),
-                SubId::Deep(DeepSubscription(id)) => arr.unobserve_deep(id),
-            }),
+            SharedType::Integrated(_) => Ok(subscription_id.unobserve()),
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
     }
 }
 
 impl YArray {
+    /// Ensures that a range of `length` elements starting at a given `index` fits within this
+    /// array.
+    fn check_range(&self, index: u32, length: u32) -> PyResult<()> {
+        let len = self.__len__();
+        let end = index as usize + length as usize;
+        if end > len {
+            return Err(PyIndexError::new_err(if length <= 1 {
+                format!("Index {index} is out of range of an array with length {len}.")
+            } else {
+                format!("Range {index}..{end} is out of range of an array with length {len}.")
+            }));
+        }
+        Ok(())
+    }
+
+    /// Materializes contents of this array into a JSON-like value.
+    pub(crate) fn to_any(&self) -> PyResult<Any> {
+        match &self.0 {
+            SharedType::Integrated(array) => Ok(array.to_json()),
+            SharedType::Prelim(items) => Python::with_gil(|py| {
+                let items: PyResult<Vec<Any>> = items
+                    .iter()
+                    .map(|item| msgpack::py_to_any(item.as_ref(py)))
+                    .collect();
+                Ok(Any::Array(items?.into_boxed_slice()))
+            }),
+        }
+    }
+
+    /// Returns elements of this array as Python objects suitable for equality checks, with nested
+    /// shared types replaced by their JSON-like contents.
+    fn comparable_items(&self, py: Python) -> PyResult<Vec<PyObject>> {
+        match &self.0 {
+            SharedType::Integrated(arr) => {
+                Ok(arr.iter().map(|v| v.to_json().into_py(py)).collect())
+            }
+            SharedType::Prelim(items) => items
+                .iter()
+                .map(|item| comparable_value(item.as_ref(py)))
+                .collect(),
+        }
+    }
+
     /// Gets a single element from a YArray.
     fn get_element(&self, index: u32) -> PyResult<PyObject> {
         match &self.0 {
@@ -379,53 +759,48 @@ impl YArray {
         }
     }
 
-    /// Creates a new YArray from a range of values specified in a PySlice
+    /// Returns a list of values selected by a given `slice`, following Python slicing semantics.
     fn get_range(&self, slice: &PySlice) -> PyResult<PyObject> {
         let PySliceIndices {
-            start, stop, step, ..
-        } = slice.indices(self.__len__().try_into().unwrap()).unwrap();
-        match &self.0 {
-            SharedType::Integrated(arr) => Python::with_gil(|py| {
-                if step < 0 {
-                    let step = step.abs() as usize;
-                    let (start, stop) = ((stop + 1) as usize, (start + 1) as usize);
-                    let values: Vec<PyObject> = arr
-                        .iter()
-                        .enumerate()
-                        .skip(start)
-                        .step_by(step)
-                        .take_while(|(i, _)| i < &stop)
-                        .map(|(_, el)| el.into_py(py))
-                        .collect();
-                    let values: Vec<PyObject> = values.into_iter().rev().collect();
-                    Ok(values.into_py(py))
-                } else {
-                    let (start, stop, step) = (start as usize, stop as usize, step as usize);
-                    let values: Vec<PyObject> = arr
-                        .iter()
-                        .enumerate()
-                        .skip(start)
-                        .step_by(step)
-                        .take_while(|(i, _)| i < &stop)
-                        .map(|(_, el)| el.into_py(py))
-                        .collect();
-                    Ok(values.into_py(py))
-                }
-            }),
-            SharedType::Prelim(arr) => Python::with_gil(|py| {
-                if step < 0 {
-                    let step = step.abs() as usize;
-                    let (start, stop) = ((stop + 1) as usize, (start + 1) as usize);
-                    let list =
-                        PyList::new(py, arr[start..stop].iter().rev().step_by(step).cloned());
-                    Ok(list.into())
-                } else {
-                    let step = step as usize;
-                    let (start, stop) = (start as usize, stop as usize);
-                    let list = PyList::new(py, arr[start..stop].iter().step_by(step).cloned());
-                    Ok(list.into())
+            start,
+            step,
+            slicelength,
+            ..
+        } = slice.indices(self.__len__().try_into().unwrap())?;
+        let positions = (0..slicelength).map(|i| (start + i * step) as usize);
+        Python::with_gil(|py| {
+            let values: Vec<PyObject> = match &self.0 {
+                SharedType::Integrated(arr) => {
+                    let items: Vec<Value> = arr.iter().collect();
+                    positions.map(|i| items[i].clone().into_py(py)).collect()
                 }
-            }),
+                SharedType::Prelim(arr) => positions.map(|i| arr[i].clone()).collect(),
+            };
+            Ok(values.into_py(py))
+        })
+    }
+
+    /// Returns elements of a preliminary array, which can be modified without a transaction.
+    fn prelim_items_mut(&mut self) -> PyResult<&mut Vec<PyObject>> {
+        match &mut self.0 {
+            SharedType::Prelim(items) => Ok(items),
+            SharedType::Integrated(_) => Err(PyTypeError::new_err(
+                "Item assignment and deletion are only supported by preliminary arrays, use insert and delete methods within a transaction to change an integrated YArray.",
+            )),
+        }
+    }
+
+    /// Resolves a possibly negative `index` into a position within an array of a given `len`.
+    fn checked_index(len: usize, index: isize) -> PyResult<usize> {
+        let position = if index < 0 {
+            index + len as isize
+        } else {
+            index
+        };
+        if position < 0 || position as usize >= len {
+            Err(PyIndexError::default_message())
+        } else {
+            Ok(position as usize)
         }
     }
 
@@ -437,36 +812,48 @@ impl YArray {
         }
     }
 
-    pub fn insert_multiple_at(dst: &Array, txn: &mut Transaction, index: u32, src: Vec<PyObject>) {
-        let mut j = index;
-        let mut i = 0;
-        Python::with_gil(|py| {
-            while i < src.len() {
-                let mut anys: Vec<Any> = Vec::default();
-                while i < src.len() {
-                    let converted_item: PyResult<Any> =
-                        CompatiblePyType::try_from(src[i].as_ref(py)).and_then(Any::try_from);
-                    if let Ok(any) = converted_item {
-                        anys.push(any);
-                        i += 1;
-                    } else {
-                        println!("{converted_item:?}");
-                        break;
+    /// Inserts `src` items at a given `index` of `dst`. Every item is converted and validated
+    /// before any edit is made, so that a failed conversion leaves the array unchanged. Consecutive
+    /// plain values are inserted as a single block, while preliminary shared types are inserted one
+    /// by one.
+    pub fn insert_multiple_at(
+        dst: &Array,
+        txn: &mut Transaction,
+        index: u32,
+        src: Vec<PyObject>,
+    ) -> PyResult<()> {
+        let items: Vec<ArrayItem> = Python::with_gil(|py| {
+            src.into_iter()
+                .map(|item| match CompatiblePyType::try_from(item.as_ref(py))? {
+                    value @ CompatiblePyType::YType(_) => {
+                        value.ensure_integrable()?;
+                        Ok(ArrayItem::Prelim(item))
                     }
-                }
+                    value => Ok(ArrayItem::Value(Any::try_from(value)?)),
+                })
+                .collect::<PyResult<_>>()
+        })?;
 
-                if !anys.is_empty() {
-                    let len = anys.len() as u32;
-                    dst.insert_range(txn, j, anys);
-                    j += len;
-                } else {
-                    let wrapper = PyObjectWrapper(src[i].clone());
-                    dst.insert(txn, j, wrapper);
-                    i += 1;
+        let mut j = index;
+        let mut anys: Vec<Any> = Vec::new();
+        for item in items {
+            match item {
+                ArrayItem::Value(any) => anys.push(any),
+                ArrayItem::Prelim(item) => {
+                    if !anys.is_empty() {
+                        let len = anys.len() as u32;
+                        dst.insert_range(txn, j, std::mem::take(&mut anys));
+                        j += len;
+                    }
+                    dst.insert(txn, j, PyObjectWrapper(item));
                     j += 1;
                 }
             }
-        })
+        }
+        if !anys.is_empty() {
+            dst.insert_range(txn, j, anys);
+        }
+        Ok(())
     }
 
     fn py_iter(iterable: PyObject) -> PyResult<Vec<PyObject>> {
@@ -484,6 +871,84 @@ impl YArray {
         })
     }
 }
+
+/// An item converted for insertion into an integrated `YArray`.
+enum ArrayItem {
+    Value(Any),
+    Prelim(PyObject),
+}
+
+/// Smallest number of values read ahead by a `YArrayIterator` at once.
+const MIN_READ_AHEAD: u32 = 64;
+
+/// Iterator over values of a `YArray`. Values of an integrated array are read ahead in chunks, each
+/// in a single pass over the array. Since every pass starts from the beginning of the array, chunks
+/// grow along with the index, which keeps iterating over the whole array linear.
+#[pyclass(unsendable)]
+pub struct YArrayIterator {
+    array: Py<YArray>,
+    index: u32,
+    /// Values read ahead of the index, in reverse order.
+    buffer: Vec<PyObject>,
+    /// Length of the array at the time the buffer was filled.
+    len: u32,
+    /// Set whenever a transaction modifying the array is committed, which invalidates the buffer.
+    changed: Rc<Cell<bool>>,
+    subscription: Option<(Array, SubscriptionId)>,
+}
+
+#[pymethods]
+impl YArrayIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> Option<PyObject> {
+        let value = match &self.array.borrow(py).0 {
+            SharedType::Integrated(arr) => {
+                // Length check catches changes made by a transaction that is still open.
+                if self.changed.replace(false) || arr.len() != self.len {
+                    self.buffer.clear();
+                }
+                if self.buffer.is_empty() {
+                    self.len = arr.len();
+                    let end = self
+                        .len
+                        .min(self.index.saturating_add(self.index.max(MIN_READ_AHEAD)));
+                    let mut values: Vec<Value> = arr
+                        .iter()
+                        .skip(self.index as usize)
+                        .take(end.saturating_sub(self.index) as usize)
+                        .collect();
+                    // Array iterators of yrs end early at moved elements, the rest is read one by
+                    // one.
+                    while self.index + (values.len() as u32) < end {
+                        match arr.get(self.index + values.len() as u32) {
+                            Some(value) => values.push(value),
+                            None => break,
+                        }
+                    }
+                    self.buffer = values.into_iter().rev().map(|v| v.into_py(py)).collect();
+                }
+                self.buffer.pop()
+            }
+            SharedType::Prelim(arr) => arr.get(self.index as usize).cloned(),
+        };
+        if value.is_some() {
+            self.index += 1;
+        }
+        value
+    }
+}
+
+impl Drop for YArrayIterator {
+    fn drop(&mut self) {
+        if let Some((array, id)) = &mut self.subscription {
+            array.unobserve(*id)
+        }
+    }
+}
+
 #[derive(FromPyObject)]
 pub enum Index<'a> {
     Int(isize),
@@ -497,6 +962,7 @@ pub struct YArrayEvent {
     txn: *const Transaction,
     target: Option<PyObject>,
     delta: Option<PyObject>,
+    before: Option<PyObject>,
 }
 
 impl YArrayEvent {
@@ -508,6 +974,7 @@ impl YArrayEvent {
             txn,
             target: None,
             delta: None,
+            before: None,
         }
     }
 
@@ -542,12 +1009,21 @@ impl YArrayEvent {
         format!("YArrayEvent(target={target}, delta={delta}, path={path})")
     }
 
-    /// Returns an array of keys and indexes creating a path from root type down to current instance
-    /// of shared type (accessible via `target` getter).
+    /// Returns an array of keys and indexes creating a path from the observed shared type (a root
+    /// type for `YDoc.observe_deep`) down to current instance of shared type (accessible via
+    /// `target` getter).
     pub fn path(&self) -> PyObject {
         Python::with_gil(|py| self.inner().path().into_py(py))
     }
 
+    /// Returns a dictionary of clients, whose clocks have advanced during the transaction which
+    /// produced this event, mapped to a `(before, after)` pair of their clocks, like
+    /// `AfterTransactionEvent.clock_ranges` does.
+    pub fn clock_ranges(&self) -> PyObject {
+        let txn = self.txn();
+        clock_ranges_into_py(&txn.before_state, &txn.after_state)
+    }
+
     /// Returns a list of text changes made over corresponding `YArray` collection within
     /// bounds of current transaction. These changes follow a format:
     ///
@@ -571,6 +1047,14 @@ impl YArrayEvent {
             delta
         }
     }
+
+    /// Returns a list with the contents of the observed `YArray` from before current transaction,
+    /// or `None` if the observer was not registered with `with_snapshot=True`. Nested shared types
+    /// are represented by their JSON-like contents.
+    #[getter]
+    pub fn before(&self) -> Option<PyObject> {
+        self.before.clone()
+    }
 }
 
 impl DefaultPyErr for PyIndexError {
@@ -1,19 +1,39 @@
+use crate::shared_types::{raise_observer_error, report_observer_error};
+use crate::type_conversions::{events_into_py, value_kind};
 use crate::y_array::YArray;
 use crate::y_map::YMap;
 use crate::y_text::YText;
-use crate::y_transaction::YTransaction;
+use crate::y_transaction::{try_decode, Binary, EncodingException, YTransaction};
 use crate::y_xml::YXmlElement;
 use crate::y_xml::YXmlText;
+use lib0::decoding::Read;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
-use pyo3::types::PyTuple;
-use yrs::updates::encoder::Encode;
+use pyo3::types::PyDict;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+use std::rc::Rc;
+use yrs::block::{
+    ItemContent, BLOCK_GC_REF_NUMBER, BLOCK_SKIP_REF_NUMBER, HAS_ORIGIN, HAS_PARENT_SUB,
+    HAS_RIGHT_ORIGIN,
+};
+use yrs::types::{
+    Branch, DeepObservable, Value, TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_FRAGMENT,
+    TYPE_REFS_XML_HOOK, TYPE_REFS_XML_TEXT,
+};
+use yrs::updates::decoder::{Decode, Decoder, DecoderV1};
+use yrs::updates::encoder::{Encode, Encoder, EncoderV1};
 use yrs::AfterTransactionEvent as YrsAfterTransactionEvent;
 use yrs::Doc;
 use yrs::OffsetKind;
 use yrs::Options;
+use yrs::Snapshot;
+use yrs::StateVector;
 use yrs::SubscriptionId;
 use yrs::Transaction;
+use yrs::Update;
 
 /// A Ypy document type. Documents are most important units of collaborative resources management.
 /// All shared collections live within a scope of their corresponding documents. All updates are
@@ -23,6 +43,11 @@ use yrs::Transaction;
 /// Document manages so called root types, which are top-level shared types definitions (as opposed
 /// to recursively nested types).
 ///
+/// Observer callbacks are called while a transaction is committed. If a callback raises an
+/// exception, the remaining callbacks are still called, and the exception is raised once the
+/// commit is done by the call which committed the transaction. If more callbacks fail, only the
+/// first exception is raised, while the other ones are printed with their tracebacks.
+///
 /// A basic workflow sample:
 ///
 /// ```python
@@ -35,8 +60,125 @@ use yrs::Transaction;
 ///     output = text.to_string(txn)
 ///     print(output)
 /// ```
-#[pyclass(unsendable, subclass)]
-pub struct YDoc(pub Doc);
+#[pyclass(unsendable, subclass, module = "y_py")]
+pub struct YDoc {
+    pub inner: Doc,
+    offset_kind: OffsetKind,
+    skip_gc: bool,
+    debug_logger: Option<PyObject>,
+    /// Origin of a currently active transaction, shared with `observe_after_transaction` callbacks.
+    origin: Rc<RefCell<Option<PyObject>>>,
+    /// Set while a transaction started with `begin_transaction` is still open.
+    transaction_open: Rc<Cell<bool>>,
+    /// Weak reference to the transaction started with `begin_transaction`, valid while
+    /// `transaction_open` is set.
+    pending_transaction: RefCell<Option<PyObject>>,
+    history: Option<(Rc<RefCell<UpdateHistory>>, SubscriptionId)>,
+    /// Set once the first update observer of a given encoding is subscribed. yrs panics when
+    /// unsubscribing an update observer from a document which never had one.
+    observes_update_v1: bool,
+    observes_update_v2: bool,
+    /// Root types accessed so far and callbacks subscribed with `observe_deep`, shared with
+    /// transactions which record root types accessed later on.
+    root_types: Rc<RefCell<RootTypes>>,
+}
+
+/// Root types of a document accessed with one of the `get_*` methods, along with callbacks
+/// observing changes of all of them. yrs has no document-wide deep observers, so they are attached
+/// to every root type separately.
+#[derive(Default)]
+pub(crate) struct RootTypes {
+    values: BTreeMap<String, Value>,
+    callbacks: BTreeMap<SubscriptionId, PyObject>,
+    next_id: SubscriptionId,
+    /// Names of root types, which already pass their events to `callbacks`.
+    attached: HashSet<String>,
+}
+
+impl RootTypes {
+    /// Records a root type with a given `name`, replacing its previous value if it was accessed
+    /// as a different type before.
+    pub(crate) fn register(roots: &Rc<RefCell<Self>>, name: &str, value: Value) {
+        roots
+            .borrow_mut()
+            .values
+            .insert(name.to_string(), value.clone());
+        Self::attach(roots, name, value);
+    }
+
+    /// Returns the name of a root type backed by a given `branch`, if it was accessed before.
+    pub(crate) fn name_of(&self, branch: &Branch) -> Option<&str> {
+        self.values.iter().find_map(|(name, value)| {
+            let root: &Branch = match value {
+                Value::YText(v) => v.as_ref(),
+                Value::YArray(v) => v.as_ref(),
+                Value::YMap(v) => v.as_ref(),
+                Value::YXmlElement(v) => v.as_ref(),
+                Value::YXmlText(v) => v.as_ref(),
+                Value::Any(_) => return None,
+            };
+            std::ptr::eq(root, branch).then(|| name.as_str())
+        })
+    }
+
+    /// Passes events of a root type with a given `name` to subscribed callbacks, unless it was
+    /// attached before. Nothing is attached until the first callback is subscribed.
+    fn attach(roots: &Rc<RefCell<Self>>, name: &str, value: Value) {
+        {
+            let mut this = roots.borrow_mut();
+            if this.callbacks.is_empty() || !this.attached.insert(name.to_string()) {
+                return;
+            }
+        }
+        match value {
+            Value::YText(mut text) => Self::observe(roots, &mut text),
+            Value::YArray(mut array) => Self::observe(roots, &mut array),
+            Value::YMap(mut map) => Self::observe(roots, &mut map),
+            Value::YXmlElement(mut xml) => Self::observe(roots, &mut xml),
+            Value::YXmlText(mut xml) => Self::observe(roots, &mut xml),
+            Value::Any(_) => {}
+        }
+    }
+
+    fn observe(roots: &Rc<RefCell<Self>>, root: &mut impl DeepObservable) {
+        let roots = roots.clone();
+        let _: SubscriptionId = root
+            .observe_deep(move |txn, events| {
+                let callbacks: Vec<PyObject> = roots.borrow().callbacks.values().cloned().collect();
+                if callbacks.is_empty() {
+                    return;
+                }
+                Python::with_gil(|py| {
+                    let events = events_into_py(txn, events);
+                    for callback in callbacks {
+                        if let Err(err) = callback.call1(py, (events.clone_ref(py),)) {
+                            report_observer_error(py, err)
+                        }
+                    }
+                })
+            })
+            .into();
+    }
+}
+
+/// Ring buffer of the most recent incremental updates, filled by an internal update observer.
+struct UpdateHistory {
+    updates: VecDeque<Vec<u8>>,
+    max_updates: usize,
+}
+
+impl UpdateHistory {
+    fn push(&mut self, update: Vec<u8>) {
+        self.updates.push_back(update);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.updates.len() > self.max_updates {
+            self.updates.pop_front();
+        }
+    }
+}
 
 #[pymethods]
 impl YDoc {
@@ -72,13 +214,174 @@ impl YDoc {
             options.skip_gc = skip_gc;
         }
 
-        Ok(YDoc(Doc::with_options(options)))
+        Ok(YDoc::with_options(options))
     }
 
     /// Gets globally unique identifier of this `YDoc` instance.
     #[getter]
     pub fn client_id(&self) -> u64 {
-        self.0.client_id as u64
+        self.inner.client_id as u64
+    }
+
+    /// True if this document was created with `skip_gc=True`, which keeps contents of deleted
+    /// blocks around, as required by snapshots. The option can't be changed afterwards, since
+    /// collecting garbage would invalidate snapshots which were already taken.
+    #[getter]
+    pub fn skip_gc(&self) -> bool {
+        self.skip_gc
+    }
+
+    /// Returns the logical clock of a given `client_id` as currently known by this document. If
+    /// `client_id` was not provided, a clock of this document's own client is returned.
+    ///
+    /// Every change made by a client advances its clock by the length of that change, so comparing
+    /// clocks reveals which peer's edit was integrated later. A client that never made any change
+    /// visible to this document has a clock equal to 0.
+    pub fn current_clock(&self, client_id: Option<u64>) -> PyResult<u32> {
+        let client_id = client_id.unwrap_or(self.inner.client_id);
+        let state_vector = self.commit_with(None, |txn| txn.state_vector())?;
+        Ok(state_vector.get(&client_id))
+    }
+
+    /// Returns a state vector of this document as a dictionary mapping IDs of all clients whose
+    /// changes it has seen to their clocks. It's a decoded form of `encode_state_vector`.
+    pub fn state_vector(&self) -> PyResult<PyObject> {
+        let state_vector = self.commit_with(None, |txn| txn.state_vector())?;
+        Ok(state_vector_into_py(&state_vector))
+    }
+
+    /// Checks if this document and the `other` one have identical state vectors, meaning that both
+    /// of them have seen exactly the same changes from the same clients. This is a cheap way to
+    /// confirm that two peers are fully synchronized, as it doesn't need to read the contents of
+    /// either document.
+    pub fn converged_with(&self, other: &YDoc) -> PyResult<bool> {
+        let state_vector = self.commit_with(None, |txn| txn.state_vector())?;
+        Ok(state_vector == other.commit_with(None, |txn| txn.state_vector())?)
+    }
+
+    /// Checks if this document and the `other` one represent the same CRDT state, meaning that both
+    /// of them have seen the same changes and deleted the same contents, regardless of the order in
+    /// which these were applied. Unlike `converged_with`, it also compares deletions, which don't
+    /// advance the clocks of a state vector.
+    pub fn equal_state(&self, other: &YDoc) -> PyResult<bool> {
+        Ok(self.state_digest()? == other.state_digest()?)
+    }
+
+    /// Returns a state of this document used by `pickle`: its client ID, offset kind, garbage
+    /// collection setting and all of its contents encoded as a lib0 v1 update.
+    pub fn __getstate__(&self) -> PyResult<PyObject> {
+        let offset_kind = match self.offset_kind {
+            OffsetKind::Bytes => "utf8",
+            OffsetKind::Utf16 => "utf16",
+            OffsetKind::Utf32 => "utf32",
+        };
+        let update = self.commit_with(None, |txn| txn.diff_v1(None))??;
+        Python::with_gil(|py| {
+            let state = (self.client_id(), offset_kind, self.skip_gc, update);
+            Ok(state.into_py(py))
+        })
+    }
+
+    /// Restores a document from a state returned by `__getstate__`. It's meant to be called only
+    /// by `pickle` on a newly created document, since shared types obtained from this document
+    /// before are not valid anymore.
+    pub fn __setstate__(&mut self, state: (u64, String, bool, Vec<u8>)) -> PyResult<()> {
+        let (client_id, offset_kind, skip_gc, update) = state;
+        *self = YDoc::new(Some(client_id), Some(offset_kind), Some(skip_gc))?;
+        self.commit_with(None, |txn| txn.apply_v1(Binary::Owned(update)))?
+    }
+
+    /// Returns an update, encoded using lib0 v1 encoding, containing all changes known to this
+    /// document that the `other` document has not seen yet. Applying it to `other` makes it catch
+    /// up with this document.
+    pub fn missing_from(&self, other: &YDoc) -> PyResult<PyObject> {
+        let sv = other.commit_with(None, |txn| txn.state_vector())?;
+        let update = self.commit_with(None, |txn| txn.encode_diff_v1(&sv))?;
+        Ok(Python::with_gil(|py| PyBytes::new(py, &update).into()))
+    }
+
+    /// Returns a snapshot of this document, describing which changes were visible at this point in
+    /// time. It can be later passed to `restore` or `encode_state_from_snapshot` to read the
+    /// document contents as they were when the snapshot was taken.
+    ///
+    /// Snapshots are only available for documents created with `skip_gc=True`, since garbage
+    /// collection discards deleted contents a snapshot may still refer to.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(skip_gc=True)
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello')
+    /// snapshot = doc.snapshot()
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, ' world')
+    ///
+    /// assert str(doc.restore(snapshot).get_text('name')) == 'hello'
+    /// ```
+    pub fn snapshot(&self) -> PyResult<PyObject> {
+        self.ensure_skip_gc()?;
+        let snapshot = self.commit_with(None, |txn| txn.snapshot().encode_v1())?;
+        Ok(Python::with_gil(|py| PyBytes::new(py, &snapshot).into()))
+    }
+
+    /// Returns a new document with contents of this document as they were at the time when
+    /// a given `snapshot` was taken. Returned document is independent of this one: it uses the same
+    /// offset kind, but has a random client ID.
+    pub fn restore(&self, snapshot: Vec<u8>) -> PyResult<YDoc> {
+        let update = self.encode_state_from_snapshot(&snapshot)?;
+        let doc = YDoc::with_options(Options {
+            offset_kind: self.offset_kind,
+            ..Options::default()
+        });
+        doc.commit_with(None, |txn| txn.apply_v1(Binary::Owned(update)))??;
+        Ok(doc)
+    }
+
+    /// Returns a new document with the same contents and options as this one, which doesn't share
+    /// any state with it, so that it can be edited independently (eg. to preview changes). Observers
+    /// and update history are not copied. The fork gets a random client ID, unless `client_id` is
+    /// provided. Reusing the client ID of this document is only safe as long as at most one of
+    /// them is edited afterwards, otherwise their updates can't be merged anymore.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// fork = doc.fork()
+    /// with fork.begin_transaction() as txn:
+    ///     fork.get_text('name').extend(txn, 'preview')
+    ///
+    /// assert str(doc.get_text('name')) == ''
+    /// ```
+    pub fn fork(&self, client_id: Option<u64>) -> PyResult<YDoc> {
+        let update = self.commit_with(None, |txn| txn.encode_diff_v1(&StateVector::default()))?;
+        let mut options = Options {
+            offset_kind: self.offset_kind,
+            skip_gc: self.skip_gc,
+            ..Options::default()
+        };
+        if let Some(client_id) = client_id {
+            options.client_id = client_id;
+        }
+        let doc = YDoc::with_options(options);
+        doc.commit_with(None, |txn| txn.apply_v1(Binary::Owned(update)))??;
+        Ok(doc)
+    }
+
+    /// Returns a fork of this document with the same client ID, like `pickle` does.
+    pub fn __copy__(&self) -> PyResult<YDoc> {
+        self.fork(Some(self.client_id()))
+    }
+
+    /// Documents don't share state with their copies, so deep copies are the same as shallow ones.
+    pub fn __deepcopy__(&self, _memo: &PyAny) -> PyResult<YDoc> {
+        self.__copy__()
     }
 
     /// Returns a new transaction for this document. Ypy shared data types execute their
@@ -86,7 +389,11 @@ impl YDoc {
     /// transaction at the time - subsequent attempts will cause exception to be thrown.
     ///
     /// Transactions started with `doc.begin_transaction` can be released by deleting the transaction object
-    /// method.
+    /// method. Starting another transaction before that raises `RuntimeError`.
+    ///
+    /// An optional `origin` can be any Python object describing where the changes come from (eg. a
+    /// remote peer). It's passed as `AfterTransactionEvent.origin` to `observe_after_transaction`
+    /// callbacks, which can use it to avoid echoing remote changes back to their source.
     ///
     /// Example:
     ///
@@ -97,16 +404,74 @@ impl YDoc {
     /// with doc.begin_transaction() as txn:
     ///     text.insert(txn, 0, 'hello world')
     /// ```
-    pub fn begin_transaction(&self) -> YTransaction {
-        YTransaction::new(self.0.transact())
+    ///
+    /// All changes made within a transaction are committed together, producing a single update
+    /// passed to `observe_update_v1`/`observe_update_v2` callbacks, and a single call of
+    /// `observe_after_transaction` callbacks. Only an explicit `YTransaction.commit` splits them.
+    pub fn begin_transaction(
+        slf: PyRef<Self>,
+        py: Python,
+        origin: Option<PyObject>,
+    ) -> PyResult<Py<YTransaction>> {
+        let txn = slf
+            .transaction(origin)?
+            .exclusive(slf.transaction_open.clone());
+        let doc: Py<YDoc> = slf.into();
+        let txn = Py::new(py, txn.with_doc(doc.clone_ref(py)))?;
+        let weak_ref = py.import("weakref")?.getattr("ref")?;
+        let weak_ref = weak_ref.call1((txn.clone_ref(py),))?;
+        doc.borrow(py)
+            .pending_transaction
+            .replace(Some(weak_ref.into()));
+        Ok(txn)
     }
 
-    pub fn transact(&mut self, callback: PyObject) -> PyResult<PyObject> {
-        let txn = self.begin_transaction();
-        Python::with_gil(|py| {
-            let args = PyTuple::new(py, std::iter::once(txn.into_py(py)));
-            callback.call(py, args, None)
-        })
+    /// Returns the transaction started with `begin_transaction` (or `transact`), which hasn't been
+    /// committed yet, or `None` if there is no such transaction. Changes made with it are going to
+    /// be committed together, once it's done.
+    pub fn pending_transaction(&self, py: Python) -> PyResult<PyObject> {
+        match &*self.pending_transaction.borrow() {
+            Some(weak_ref) if self.transaction_open.get() => weak_ref.call0(py),
+            _ => Ok(py.None()),
+        }
+    }
+
+    /// Returns a new read-only transaction for this document. It can be passed to methods which
+    /// require a transaction only to read the document (like `YText.to_delta`), while any attempt
+    /// to modify the document with it raises `ReadOnlyTransactionException`. Read-only transactions
+    /// don't exclude one another, so many of them can be used at the same time, but none can be
+    /// started while a transaction from `begin_transaction` is open.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('name')
+    /// with doc.begin_read_transaction() as txn:
+    ///     delta = text.to_delta(txn)
+    /// ```
+    pub fn begin_read_transaction(slf: PyRef<Self>) -> PyResult<YTransaction> {
+        let mut txn = slf.transaction(None)?.with_doc(slf.into());
+        txn.read_only = true;
+        Ok(txn)
+    }
+
+    /// Calls a given `callback` with a new transaction, returning its result. The transaction is
+    /// committed as soon as `callback` returns. If `callback` raises an exception, changes it made
+    /// before are committed as well (transactions can't be rolled back) and the exception is
+    /// propagated afterwards.
+    pub fn transact(
+        slf: &PyCell<Self>,
+        callback: PyObject,
+        origin: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let py = slf.py();
+        let txn = YDoc::begin_transaction(slf.borrow(), py, origin)?;
+        let result = callback.call1(py, (txn.clone_ref(py),));
+        txn.borrow_mut(py).finish();
+        with_observer_error(py, result)
     }
 
     /// Returns a `YMap` shared data type, that's accessible for subsequent accesses using given
@@ -116,8 +481,8 @@ impl YDoc {
     ///
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YMap` instance.
-    pub fn get_map(&mut self, name: &str) -> YMap {
-        self.begin_transaction().get_map(name)
+    pub fn get_map(&mut self, name: &str) -> PyResult<YMap> {
+        self.with_transaction(|txn| txn.get_map(name))
     }
 
     /// Returns a `YXmlElement` shared data type, that's accessible for subsequent accesses using
@@ -127,8 +492,12 @@ impl YDoc {
     ///
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YXmlElement` instance.
-    pub fn get_xml_element(&mut self, name: &str) -> YXmlElement {
-        YXmlElement(self.begin_transaction().get_xml_element(name))
+    pub fn get_xml_element(&mut self, name: &str) -> PyResult<YXmlElement> {
+        self.with_transaction(|txn| {
+            let xml = txn.get_xml_element(name);
+            txn.register_root(name, Value::YXmlElement(xml.clone()));
+            YXmlElement(xml)
+        })
     }
 
     /// Returns a `YXmlText` shared data type, that's accessible for subsequent accesses using given
@@ -138,8 +507,12 @@ impl YDoc {
     ///
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YXmlText` instance.
-    pub fn get_xml_text(&mut self, name: &str) -> YXmlText {
-        YXmlText(self.begin_transaction().get_xml_text(name))
+    pub fn get_xml_text(&mut self, name: &str) -> PyResult<YXmlText> {
+        self.with_transaction(|txn| {
+            let xml = txn.get_xml_text(name);
+            txn.register_root(name, Value::YXmlText(xml.clone()));
+            YXmlText(xml)
+        })
     }
 
     /// Returns a `YArray` shared data type, that's accessible for subsequent accesses using given
@@ -149,8 +522,8 @@ impl YDoc {
     ///
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YArray` instance.
-    pub fn get_array(&mut self, name: &str) -> YArray {
-        self.begin_transaction().get_array(name)
+    pub fn get_array(&mut self, name: &str) -> PyResult<YArray> {
+        self.with_transaction(|txn| txn.get_array(name))
     }
 
     /// Returns a `YText` shared data type, that's accessible for subsequent accesses using given
@@ -160,23 +533,347 @@ impl YDoc {
     ///
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YText` instance.
-    pub fn get_text(&mut self, name: &str) -> YText {
-        self.begin_transaction().get_text(name)
+    pub fn get_text(&mut self, name: &str) -> PyResult<YText> {
+        self.with_transaction(|txn| txn.get_text(name))
+    }
+
+    /// Returns a list of `(name, kind)` pairs describing root-level shared types of this document,
+    /// sorted by name. Kind is one of `"text"`, `"array"`, `"map"` or `"xml"`.
+    ///
+    /// Roots received from other peers have no type until they are accessed with one of the `get_*`
+    /// methods, so their kind is inferred from their contents instead. It's `None` when the
+    /// contents don't tell (eg. when all of them were deleted). Text and XML text roots can't be
+    /// told apart this way, so both are reported as `"text"`.
+    pub fn roots(&self, txn: &YTransaction) -> Vec<(String, Option<&'static str>)> {
+        let mut roots = BTreeMap::new();
+        let update = txn.encode_diff_v1(&StateVector::default());
+        // the update was just encoded by yrs, so it can't be malformed
+        for (name, kind) in root_kinds(&update).unwrap_or_default() {
+            let entry = roots.entry(name).or_insert(None);
+            *entry = entry.or(kind);
+        }
+        // kinds of roots accessed before are known for sure
+        for (name, value) in self.root_types.borrow().values.iter() {
+            roots.insert(name.clone(), Some(value_kind(value)));
+        }
+        roots.into_iter().collect()
+    }
+
+    /// Starts recording incremental updates of this document (both local and remote ones) in a
+    /// ring buffer holding at most `max_updates` of the most recent ones, which can be read with
+    /// `history`. Calling this method again changes the limit, trimming already recorded updates if
+    /// necessary, while passing 0 stops recording and clears the history.
+    ///
+    /// Every recorded update is kept in memory until it gets trimmed, so the cost grows with both
+    /// the limit and the size of individual transactions. Unlike CRDT snapshots, a history doesn't
+    /// capture the full document state: it can be replayed in order only on top of a document,
+    /// which has already seen all changes made before the oldest recorded update.
+    pub fn keep_history(&mut self, max_updates: usize) {
+        if max_updates == 0 {
+            if let Some((_, subscription_id)) = self.history.take() {
+                self.inner.unobserve_update_v1(subscription_id);
+            }
+        } else if let Some((history, _)) = &self.history {
+            let mut history = history.borrow_mut();
+            history.max_updates = max_updates;
+            history.trim();
+        } else {
+            let history = Rc::new(RefCell::new(UpdateHistory {
+                updates: VecDeque::new(),
+                max_updates,
+            }));
+            let recorder = history.clone();
+            let subscription_id = self
+                .inner
+                .observe_update_v1(move |_, event| recorder.borrow_mut().push(event.update.clone()))
+                .into();
+            self.history = Some((history, subscription_id));
+        }
+    }
+
+    /// Returns a list of updates recorded since `keep_history` was called, ordered from the oldest
+    /// to the most recent one. Each update is encoded using lib0 v1 encoding.
+    pub fn history(&self) -> Vec<PyObject> {
+        Python::with_gil(|py| match &self.history {
+            Some((history, _)) => history
+                .borrow()
+                .updates
+                .iter()
+                .map(|update| PyBytes::new(py, update).into())
+                .collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// Sets a `callback` receiving diagnostic notifications about how updates applied to this
+    /// document got integrated, or removes it when `None` is passed. It's meant for reproducing
+    /// convergence issues only: notifications are verbose and computing them makes applying updates
+    /// slower, while with no logger set there's no additional cost.
+    ///
+    /// For every applied update, the callback is called with a dictionary per client, in order of
+    /// client identifiers, describing:
+    ///
+    /// - `{'action': 'integrate', 'client', 'clock', 'length'}` - a range of blocks that got
+    ///   integrated into the document.
+    /// - `{'action': 'delete', 'client', 'clock', 'length'}` - a range of blocks that got deleted.
+    /// - `{'action': 'pending', 'client', 'clock', 'length'}` - blocks that couldn't be integrated
+    ///   yet, because they depend on changes this document has not seen. The range starts at the
+    ///   first clock of a client missing in this document.
+    pub fn set_debug_logger(&mut self, callback: Option<PyObject>) {
+        self.debug_logger = callback;
     }
 
     /// Subscribes a callback to a `YDoc` lifecycle event.
     pub fn observe_after_transaction(&mut self, callback: PyObject) -> SubscriptionId {
-        self.0
+        let origin = self.origin.clone();
+        let skip_gc = self.skip_gc;
+        self.inner
             .observe_transaction_cleanup(move |txn, event| {
                 Python::with_gil(|py| {
-                    let event = AfterTransactionEvent::new(event, txn);
+                    let mut event = AfterTransactionEvent::new(event, txn);
+                    event.origin = origin.borrow().clone();
+                    event.skip_gc = skip_gc;
                     if let Err(err) = callback.call1(py, (event,)) {
-                        err.restore(py)
+                        report_observer_error(py, err)
                     }
                 })
             })
             .into()
     }
+
+    /// Subscribes a callback to changes of all root types of this document, including nested
+    /// shared types and root types created after subscribing. The callback is called with a list
+    /// of events, the same way as callbacks subscribed with `observe_deep` of individual types, so
+    /// each event has a `target` and a `path` leading to it from its root type. Returns an ID,
+    /// which can be used to cancel the callback with `unobserve_deep`.
+    ///
+    /// Events of different root types changed by the same transaction are passed by separate
+    /// calls, all made while the transaction is committed and before `observe_after_transaction`
+    /// callbacks are called. Root types received from other peers are observed once they are
+    /// accessed with one of the `get_*` methods, since until then yrs doesn't know their type.
+    pub fn observe_deep(&mut self, callback: PyObject) -> SubscriptionId {
+        let (id, roots) = {
+            let mut root_types = self.root_types.borrow_mut();
+            let id = root_types.next_id;
+            root_types.next_id += 1;
+            root_types.callbacks.insert(id, callback);
+            let roots: Vec<_> = root_types.values.clone().into_iter().collect();
+            (id, roots)
+        };
+        for (name, value) in roots {
+            RootTypes::attach(&self.root_types, &name, value);
+        }
+        id
+    }
+
+    /// Cancels a callback subscribed with `observe_deep`.
+    pub fn unobserve_deep(&mut self, subscription_id: SubscriptionId) {
+        self.root_types
+            .borrow_mut()
+            .callbacks
+            .remove(&subscription_id);
+    }
+
+    /// Subscribes a callback to incremental updates produced by transactions committed on this
+    /// document, encoded using lib0 v1 encoding. The callback is called with the update and the
+    /// origin of a transaction, and only for transactions which changed the document.
+    pub fn observe_update_v1(&mut self, callback: PyObject) -> SubscriptionId {
+        let origin = self.origin.clone();
+        self.observes_update_v1 = true;
+        self.inner
+            .observe_update_v1(move |_, event| notify_update(&callback, &event.update, &origin))
+            .into()
+    }
+
+    /// Cancels a callback subscribed with `observe_update_v1`.
+    pub fn unobserve_update_v1(&mut self, subscription_id: SubscriptionId) {
+        if self.observes_update_v1 {
+            self.inner.unobserve_update_v1(subscription_id);
+        }
+    }
+
+    /// Subscribes a callback to incremental updates produced by transactions committed on this
+    /// document, encoded using lib0 v2 encoding. Works the same way as `observe_update_v1`.
+    pub fn observe_update_v2(&mut self, callback: PyObject) -> SubscriptionId {
+        let origin = self.origin.clone();
+        self.observes_update_v2 = true;
+        self.inner
+            .observe_update_v2(move |_, event| notify_update(&callback, &event.update, &origin))
+            .into()
+    }
+
+    /// Cancels a callback subscribed with `observe_update_v2`.
+    pub fn unobserve_update_v2(&mut self, subscription_id: SubscriptionId) {
+        if self.observes_update_v2 {
+            self.inner.unobserve_update_v2(subscription_id);
+        }
+    }
+}
+
+/// State vector and merged deleted clock ranges per client, see `YDoc::state_digest`.
+type StateDigest = (StateVector, BTreeMap<u64, Vec<Range<u32>>>);
+
+/// Calls an update observer `callback` with an encoded `update` and the origin of the transaction
+/// which produced it.
+fn notify_update(callback: &PyObject, update: &[u8], origin: &RefCell<Option<PyObject>>) {
+    Python::with_gil(|py| {
+        let update = PyBytes::new(py, update);
+        let origin = origin.borrow().clone();
+        if let Err(err) = callback.call1(py, (update, origin)) {
+            report_observer_error(py, err)
+        }
+    })
+}
+
+impl YDoc {
+    /// Returns a new transaction for this document. Unlike `begin_transaction`, it isn't tied to a
+    /// Python object of this document, so it can't be restarted after an explicit `commit`. Fails
+    /// while a transaction started with `begin_transaction` is still open, since yrs doesn't allow
+    /// more than one transaction on a document at a time.
+    pub(crate) fn transaction(&self, origin: Option<PyObject>) -> PyResult<YTransaction> {
+        if self.transaction_open.get() {
+            return Err(PyRuntimeError::new_err(
+                "Transaction already open. Commit it with a `with` block or delete it before \
+                starting a new one.",
+            ));
+        }
+        let mut txn = YTransaction::new(
+            self.inner.transact(),
+            self.offset_kind,
+            self.debug_logger.clone(),
+        );
+        if let Some(origin) = origin {
+            txn.set_origin(origin, self.origin.clone());
+        }
+        txn.root_types = Some(self.root_types.clone());
+        Ok(txn)
+    }
+
+    /// Calls `f` with a new transaction, which is committed right after. An exception raised by an
+    /// observer callback during the commit is raised here, so that it isn't left behind for an
+    /// unrelated call.
+    pub(crate) fn commit_with<R>(
+        &self,
+        origin: Option<PyObject>,
+        f: impl FnOnce(&mut YTransaction) -> R,
+    ) -> PyResult<R> {
+        let result = f(&mut self.transaction(origin)?);
+        Python::with_gil(raise_observer_error)?;
+        Ok(result)
+    }
+
+    /// Calls `f` with the transaction started with `begin_transaction` if it's still open, or with
+    /// a new transaction otherwise. Root types are commonly accessed within a `with` block of
+    /// another transaction, where opening a new one would fail.
+    fn with_transaction<R>(&self, f: impl FnOnce(&mut YTransaction) -> R) -> PyResult<R> {
+        if !self.transaction_open.get() {
+            return self.commit_with(None, f);
+        }
+        Python::with_gil(|py| {
+            let txn = self.pending_transaction(py)?;
+            let txn: &PyCell<YTransaction> = txn.as_ref(py).downcast()?;
+            let mut txn = txn.try_borrow_mut()?;
+            Ok(f(&mut txn))
+        })
+    }
+
+    /// Returns the state vector and deleted clock ranges of this document, which together identify
+    /// its CRDT state. Ranges are merged, since the same deletions may be split into a different
+    /// number of blocks, depending on the order of updates.
+    fn state_digest(&self) -> PyResult<StateDigest> {
+        let snapshot = self.commit_with(None, |txn| txn.snapshot())?;
+        let mut deleted = BTreeMap::new();
+        for (client, range) in snapshot.delete_set.iter() {
+            let mut ranges: Vec<Range<u32>> = range.iter().cloned().collect();
+            ranges.sort_by_key(|r| r.start);
+            let mut merged: Vec<Range<u32>> = Vec::with_capacity(ranges.len());
+            for r in ranges {
+                match merged.last_mut() {
+                    Some(last) if last.end >= r.start => last.end = last.end.max(r.end),
+                    _ => merged.push(r),
+                }
+            }
+            if !merged.is_empty() {
+                deleted.insert(*client, merged);
+            }
+        }
+        Ok((snapshot.state_map, deleted))
+    }
+
+    fn ensure_skip_gc(&self) -> PyResult<()> {
+        if self.skip_gc {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(
+                "Snapshots require a document created with skip_gc=True. Garbage collection \
+                removes deleted contents, which past versions of a document depend on.",
+            ))
+        }
+    }
+
+    fn with_options(options: Options) -> Self {
+        YDoc {
+            offset_kind: options.offset_kind,
+            skip_gc: options.skip_gc,
+            inner: Doc::with_options(options),
+            debug_logger: None,
+            origin: Rc::default(),
+            transaction_open: Rc::default(),
+            pending_transaction: RefCell::default(),
+            history: None,
+            observes_update_v1: false,
+            observes_update_v2: false,
+            root_types: Rc::default(),
+        }
+    }
+
+    /// Encodes an update, which reproduces this document at the time when a given lib0 v1
+    /// encoded `snapshot` was taken.
+    fn encode_state_from_snapshot(&self, snapshot: &[u8]) -> PyResult<Vec<u8>> {
+        self.ensure_skip_gc()?;
+        let snapshot = decode_snapshot(snapshot)?;
+        let mut encoder = EncoderV1::new();
+        self.commit_with(None, |txn| {
+            txn.encode_state_from_snapshot(&snapshot, &mut encoder)
+        })?
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(encoder.to_vec())
+    }
+
+    /// Opens a new transaction on a given `doc` and calls `callback` with that transaction and
+    /// a shared type `handle`. The transaction is committed as soon as `callback` returns, even if
+    /// it raised an exception.
+    pub(crate) fn transact_with(
+        doc: &PyCell<YDoc>,
+        handle: PyObject,
+        callback: PyObject,
+    ) -> PyResult<PyObject> {
+        let py = doc.py();
+        let txn = YDoc::begin_transaction(doc.borrow(), py, None)?;
+        let result = callback.call1(py, (txn.clone_ref(py), handle));
+        txn.borrow_mut(py).finish();
+        with_observer_error(py, result)
+    }
+}
+
+/// Returns the `result` of a callback called within a transaction, unless an observer callback
+/// failed while the transaction was committed. An exception raised by the callback itself takes
+/// precedence, in which case the observer's one is printed.
+fn with_observer_error(py: Python, result: PyResult<PyObject>) -> PyResult<PyObject> {
+    match (result, raise_observer_error(py)) {
+        (Err(err), Err(observer_err)) => {
+            observer_err.print(py);
+            Err(err)
+        }
+        (Ok(_), Err(observer_err)) => Err(observer_err),
+        (result, Ok(())) => result,
+    }
+}
+
+/// Decodes a lib0 v1 encoded snapshot, as returned by `YDoc.snapshot`.
+pub(crate) fn decode_snapshot(snapshot: &[u8]) -> PyResult<Snapshot> {
+    try_decode(|| Snapshot::decode_v1(snapshot))
+        .map_err(|e| EncodingException::new_err(format!("Malformed snapshot: {e}")))
 }
 
 /// Encodes a state vector of a given Ypy document into its binary representation using lib0 v1
@@ -200,8 +897,8 @@ impl YDoc {
 /// apply_update(local_doc, remote_delta)
 /// ```
 #[pyfunction]
-pub fn encode_state_vector(doc: &mut YDoc) -> PyObject {
-    doc.begin_transaction().state_vector_v1()
+pub fn encode_state_vector(doc: &mut YDoc) -> PyResult<PyObject> {
+    doc.commit_with(None, |txn| txn.state_vector_v1())
 }
 
 /// Encodes all updates that have happened since a given version `vector` into a compact delta
@@ -225,8 +922,8 @@ pub fn encode_state_vector(doc: &mut YDoc) -> PyObject {
 /// apply_update(local_doc, remote_delta)
 /// ```
 #[pyfunction]
-pub fn encode_state_as_update(doc: &YDoc, vector: Option<Vec<u8>>) -> PyResult<PyObject> {
-    doc.begin_transaction().diff_v1(vector)
+pub fn encode_state_as_update(doc: &YDoc, vector: Option<Binary>) -> PyResult<PyObject> {
+    doc.commit_with(None, |txn| txn.diff_v1(vector))?
 }
 
 /// Applies delta update generated by the remote document replica to a current document. This
@@ -247,10 +944,135 @@ pub fn encode_state_as_update(doc: &YDoc, vector: Option<Vec<u8>>) -> PyResult<P
 ///
 /// apply_update(local_doc, remote_delta)
 /// ```
+///
+/// An optional `origin` is passed as `AfterTransactionEvent.origin` to the document's
+/// `observe_after_transaction` callbacks.
+#[pyfunction]
+pub fn apply_update(doc: &mut YDoc, diff: Binary, origin: Option<PyObject>) -> PyResult<()> {
+    doc.commit_with(origin, |txn| txn.apply_v1(diff))?
+}
+
+/// Applies a list of lib0 v1 encoded `updates` (eg. read from an append-only update log) in order,
+/// within a single transaction. Observers are called only once, after all of them were applied, and
+/// `observe_update_v1` callbacks receive them as a single update. All updates are decoded before
+/// applying any of them, so nothing is applied if any of them is malformed.
+///
+/// An optional `origin` is passed as `AfterTransactionEvent.origin` to the document's
+/// `observe_after_transaction` callbacks.
+#[pyfunction]
+pub fn apply_updates(
+    doc: &mut YDoc,
+    updates: Vec<Binary>,
+    origin: Option<PyObject>,
+) -> PyResult<()> {
+    let updates = updates
+        .iter()
+        .enumerate()
+        .map(|(i, update)| {
+            let update: &[u8] = update;
+            try_decode(|| Update::decode_v1(update)).map_err(|e| {
+                EncodingException::new_err(format!("Update at index {i} is malformed: {e}"))
+            })
+        })
+        .collect::<PyResult<Vec<Update>>>()?;
+    doc.commit_with(origin, |txn| {
+        updates
+            .into_iter()
+            .try_for_each(|update| txn.apply_decoded(update))
+    })?
+}
+
+/// Encodes a state vector of a given Ypy document into its binary representation using lib0 v2
+/// encoding. It's meant to be used together with `encode_state_as_update_v2`.
+#[pyfunction]
+pub fn encode_state_vector_v2(doc: &mut YDoc) -> PyResult<PyObject> {
+    doc.commit_with(None, |txn| txn.state_vector_v2())
+}
+
+/// Encodes all updates that have happened since a given version `vector` into a compact delta
+/// representation using lib0 v2 encoding. A `vector` is expected to be produced by
+/// `encode_state_vector_v2`. If it has not been provided, generated delta payload will contain
+/// all changes of a current Ypy document.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, encode_state_vector_v2, encode_state_as_update_v2, apply_update_v2
+///
+/// local_doc = YDoc()
+/// local_sv = encode_state_vector_v2(local_doc)
+///
+/// remote_doc = YDoc()
+/// remote_delta = encode_state_as_update_v2(remote_doc, local_sv)
+///
+/// apply_update_v2(local_doc, remote_delta)
+/// ```
+#[pyfunction]
+pub fn encode_state_as_update_v2(doc: &YDoc, vector: Option<Binary>) -> PyResult<PyObject> {
+    doc.commit_with(None, |txn| txn.diff_v2(vector))?
+}
+
+/// Applies delta update generated by the remote document replica to a current document. This
+/// method assumes that a payload maintains lib0 v2 encoding format.
+///
+/// An optional `origin` is passed as `AfterTransactionEvent.origin` to the document's
+/// `observe_after_transaction` callbacks.
+#[pyfunction]
+pub fn apply_update_v2(doc: &mut YDoc, diff: Binary, origin: Option<PyObject>) -> PyResult<()> {
+    doc.commit_with(origin, |txn| txn.apply_v2(diff))?
+}
+
+/// Encodes an update, which reproduces contents of a given `doc` at the time when a `snapshot`
+/// (obtained via `YDoc.snapshot`) was taken. The `doc` must have been created with
+/// `skip_gc=True`.
+#[pyfunction]
+pub fn encode_state_from_snapshot(doc: &YDoc, snapshot: Vec<u8>) -> PyResult<PyObject> {
+    let update = doc.encode_state_from_snapshot(&snapshot)?;
+    Ok(Python::with_gil(|py| PyBytes::new(py, &update).into()))
+}
+
+/// Merges a list of lib0 v1 encoded `updates` into a single update, which is equivalent to
+/// applying all of them one after another. It doesn't require any `YDoc` instance, which makes it
+/// useful for compacting persisted update logs.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, encode_state_as_update, merge_updates
+///
+/// doc = YDoc()
+/// text = doc.get_text('name')
+/// updates = []
+/// doc.observe_after_transaction(lambda e: updates.append(e.get_update()))
+/// with doc.begin_transaction() as txn:
+///     text.insert(txn, 0, 'hello')
+/// with doc.begin_transaction() as txn:
+///     text.insert(txn, 5, ' world')
+///
+/// compacted = merge_updates(updates)
+/// ```
+#[pyfunction]
+pub fn merge_updates(updates: Vec<Vec<u8>>) -> PyResult<PyObject> {
+    let mut decoded = Vec::with_capacity(updates.len());
+    for (i, update) in updates.iter().enumerate() {
+        let update = try_decode(|| Update::decode_v1(update)).map_err(|e| {
+            EncodingException::new_err(format!("Update at index {i} is malformed: {e}"))
+        })?;
+        decoded.push(update);
+    }
+    let merged = Update::merge_updates(decoded).encode_v1();
+    Ok(Python::with_gil(|py| PyBytes::new(py, &merged).into()))
+}
+
+/// Trims a lib0 v1 encoded `update` down to changes missing from a peer with a given lib0 v1
+/// encoded `state_vector`. Like `merge_updates`, it doesn't require any `YDoc` instance.
 #[pyfunction]
-pub fn apply_update(doc: &mut YDoc, diff: Vec<u8>) -> PyResult<()> {
-    doc.begin_transaction().apply_v1(diff)?;
-    Ok(())
+pub fn diff_updates(update: Vec<u8>, state_vector: Vec<u8>) -> PyResult<PyObject> {
+    try_decode(|| StateVector::decode_v1(&state_vector))
+        .map_err(|e| EncodingException::new_err(format!("Malformed state vector: {e}")))?;
+    let diff = try_decode(|| yrs::diff_updates_v1(&update, &state_vector))
+        .map_err(|e| EncodingException::new_err(format!("Malformed update: {e}")))?;
+    Ok(Python::with_gil(|py| PyBytes::new(py, &diff).into()))
 }
 
 #[pyclass(unsendable)]
@@ -260,6 +1082,9 @@ pub struct AfterTransactionEvent {
     before_state: Option<PyObject>,
     after_state: Option<PyObject>,
     delete_set: Option<PyObject>,
+    origin: Option<PyObject>,
+    /// Set when the document was created with `skip_gc`, so that no contents get collected.
+    skip_gc: bool,
 }
 
 impl AfterTransactionEvent {
@@ -272,6 +1097,8 @@ impl AfterTransactionEvent {
             before_state: None,
             after_state: None,
             delete_set: None,
+            origin: None,
+            skip_gc: false,
         }
     }
 
@@ -325,8 +1152,141 @@ impl AfterTransactionEvent {
         }
     }
 
+    /// Returns a decoded `before_state`, as a dictionary mapping client IDs to their clocks.
+    #[getter]
+    pub fn before_state_map(&self) -> PyObject {
+        state_vector_into_py(&self.inner().before_state)
+    }
+
+    /// Returns a decoded `after_state`, as a dictionary mapping client IDs to their clocks.
+    #[getter]
+    pub fn after_state_map(&self) -> PyObject {
+        state_vector_into_py(&self.inner().after_state)
+    }
+
+    /// Returns a decoded `delete_set`, as a dictionary mapping client IDs to lists of
+    /// `(clock, length)` pairs, describing ranges of blocks deleted within the transaction.
+    #[getter]
+    pub fn delete_set_map(&self) -> PyObject {
+        let mut deleted: HashMap<u64, Vec<(u32, u32)>> = HashMap::new();
+        for (client, range) in self.inner().delete_set.iter() {
+            let mut ranges: Vec<(u32, u32)> =
+                range.iter().map(|r| (r.start, r.end - r.start)).collect();
+            ranges.sort_unstable();
+            deleted.insert(*client, ranges);
+        }
+        Python::with_gil(|py| deleted.into_py(py))
+    }
+
+    /// Returns a dictionary of clients, whose clocks have advanced during the transaction, mapped
+    /// to a `(before, after)` pair of their clocks. Changes made by a client within this
+    /// transaction occupy clock values in range of `before..after`.
+    pub fn clock_ranges(&self) -> PyObject {
+        let event = self.inner();
+        clock_ranges_into_py(&event.before_state, &event.after_state)
+    }
+
+    /// Origin passed to a transaction which produced this event, or `None` when it was not given.
+    #[getter]
+    pub fn origin(&self) -> Option<PyObject> {
+        self.origin.clone()
+    }
+
+    /// True if yrs garbage collected contents deleted by the transaction, which happens on every
+    /// commit deleting anything, unless the document was created with `skip_gc`. Collected blocks
+    /// only keep their IDs and lengths, while their contents are discarded.
+    #[getter]
+    pub fn gc_performed(&self) -> bool {
+        !self.skip_gc && !self.inner().delete_set.is_empty()
+    }
+
     pub fn get_update(&self) -> PyObject {
         let update = self.txn().encode_update_v1();
         Python::with_gil(|py| PyBytes::new(py, &update).into())
     }
 }
+
+fn state_vector_into_py(state_vector: &StateVector) -> PyObject {
+    let clocks: HashMap<u64, u32> = state_vector.iter().map(|(&k, &v)| (k, v)).collect();
+    Python::with_gil(|py| clocks.into_py(py))
+}
+
+/// Maps clients, whose clocks differ between `before` and `after` states, to a pair of their
+/// clocks in both of them.
+pub(crate) fn clock_ranges_into_py(before: &StateVector, after: &StateVector) -> PyObject {
+    Python::with_gil(|py| {
+        let ranges = PyDict::new(py);
+        for (client, &clock) in after.iter() {
+            let start = before.get(client);
+            if start != clock {
+                ranges.set_item(client, (start, clock)).unwrap();
+            }
+        }
+        ranges.into()
+    })
+}
+
+/// Scans blocks of a lib0 v1 encoded `update` for root-level shared types, inferring their kinds
+/// from the contents inserted directly into them.
+fn root_kinds(update: &[u8]) -> Result<Vec<(String, Option<&'static str>)>, lib0::error::Error> {
+    let mut decoder = DecoderV1::from(update);
+    let mut roots = Vec::new();
+    for _ in 0..decoder.read_var::<u32>()? {
+        let blocks_len: u32 = decoder.read_var()?;
+        decoder.read_client()?;
+        decoder.read_var::<u32>()?;
+        for _ in 0..blocks_len {
+            match decoder.read_info()? {
+                BLOCK_SKIP_REF_NUMBER => {
+                    decoder.read_var::<u32>()?;
+                }
+                BLOCK_GC_REF_NUMBER => {
+                    decoder.read_len()?;
+                }
+                info => {
+                    if info & HAS_ORIGIN != 0 {
+                        decoder.read_left_id()?;
+                    }
+                    if info & HAS_RIGHT_ORIGIN != 0 {
+                        decoder.read_right_id()?;
+                    }
+                    // items with origins share a parent with them, so only the rest encode it
+                    let mut root = None;
+                    if info & (HAS_ORIGIN | HAS_RIGHT_ORIGIN) == 0 {
+                        if decoder.read_parent_info()? {
+                            root = Some(decoder.read_string()?.to_string());
+                        } else {
+                            decoder.read_left_id()?;
+                        }
+                        if info & HAS_PARENT_SUB != 0 {
+                            decoder.read_string()?;
+                            if let Some(name) = root.take() {
+                                roots.push((name, Some("map")));
+                            }
+                        }
+                    }
+                    let content = ItemContent::decode(&mut decoder, info)?;
+                    if let Some(name) = root {
+                        let kind = match content {
+                            ItemContent::Deleted(_) => None,
+                            ItemContent::String(_)
+                            | ItemContent::Embed(_)
+                            | ItemContent::Format(_, _) => Some("text"),
+                            ItemContent::Type(branch) if is_xml(branch.type_ref()) => Some("xml"),
+                            _ => Some("array"),
+                        };
+                        roots.push((name, kind));
+                    }
+                }
+            }
+        }
+    }
+    Ok(roots)
+}
+
+fn is_xml(type_ref: u8) -> bool {
+    matches!(
+        type_ref,
+        TYPE_REFS_XML_ELEMENT | TYPE_REFS_XML_FRAGMENT | TYPE_REFS_XML_HOOK | TYPE_REFS_XML_TEXT
+    )
+}
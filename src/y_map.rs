@@ -1,20 +1,32 @@
-use pyo3::exceptions::{PyKeyError, PyTypeError};
+use lib0::any::Any;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::types::{PyBytes, PyDict};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use std::mem::ManuallyDrop;
 use std::ops::DerefMut;
+use std::rc::Rc;
 use yrs::types::map::{MapEvent, MapIter};
-use yrs::types::DeepObservable;
+use yrs::types::{DeepObservable, Value};
 use yrs::{Map, SubscriptionId, Transaction};
 
-use crate::json_builder::JsonBuilder;
+use crate::json_builder::{json_value_into_py, JsonBuilder};
+use crate::msgpack;
 use crate::shared_types::{
-    DeepSubscription, DefaultPyErr, PreliminaryObservationException, ShallowSubscription,
+    report_observer_error, CompatiblePyType, DeepSubscription, DefaultPyErr,
+    IntegratedOperationException, Observed, PreliminaryObservationException, ShallowSubscription,
     SharedType, SubId,
 };
-use crate::type_conversions::{events_into_py, PyObjectWrapper, ToPython};
+use crate::type_conversions::{
+    comparable_value, events_into_py, prelim_value_kind, value_kind, PyObjectWrapper, ToPython,
+};
+use crate::y_array::YArray;
+use crate::y_doc::{clock_ranges_into_py, YDoc};
+use crate::y_text::YText;
 use crate::y_transaction::YTransaction;
 
 /// Collection used to store key-value entries in an unordered manner. Keys are always represented
@@ -66,6 +78,30 @@ impl YMap {
         }
     }
 
+    /// Opens a new transaction on a given `doc` this map belongs to and calls `callback` with
+    /// that transaction and this map as arguments. The transaction is committed once `callback`
+    /// returns, and its result is passed through.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// map = doc.get_map('name')
+    /// map.transact(doc, lambda txn, m: m.set(txn, 'key', 'value'))
+    /// ```
+    pub fn transact(
+        slf: &PyCell<Self>,
+        doc: &PyCell<YDoc>,
+        callback: PyObject,
+    ) -> PyResult<PyObject> {
+        if slf.borrow().prelim() {
+            return Err(IntegratedOperationException::default_message());
+        }
+        YDoc::transact_with(doc, slf.into(), callback)
+    }
+
     /// Returns a number of entries stored within this instance of `YMap`.
     pub fn __len__(&self) -> usize {
         match &self.0 {
@@ -98,6 +134,26 @@ impl YMap {
         format!("YMap({})", self.__str__())
     }
 
+    /// Maps are equal when they have the same keys with equal values, with nested shared types
+    /// compared using their JSON-like contents. They can be compared with plain dicts as well.
+    /// Since equality is based on contents, maps are not hashable.
+    pub fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let py = other.py();
+        let other: PyObject = if let Ok(map) = other.extract::<PyRef<YMap>>() {
+            map.comparable_entries(py)?.into()
+        } else if other.downcast::<PyDict>().is_ok() {
+            other.into()
+        } else {
+            return Ok(py.NotImplemented());
+        };
+        let this = self.comparable_entries(py)?;
+        Ok(match op {
+            CompareOp::Eq => this.eq(other)?.into_py(py),
+            CompareOp::Ne => this.ne(other)?.into_py(py),
+            _ => py.NotImplemented(),
+        })
+    }
+
     /// Converts contents of this `YMap` instance into a JSON representation.
     pub fn to_json(&self) -> PyResult<String> {
         let mut json_builder = JsonBuilder::new();
@@ -108,9 +164,77 @@ impl YMap {
         Ok(json_builder.into())
     }
 
+    /// Returns contents of this `YMap` instance as plain Python lists and dicts, which can be
+    /// serialized with `json.dumps`. Nested shared types are converted recursively, with texts read
+    /// as strings. Binary values are represented as base64 encoded strings and datetimes as ISO 8601
+    /// strings.
+    pub fn to_json_value(&self) -> PyResult<PyObject> {
+        let value = self.to_any()?;
+        Python::with_gil(|py| json_value_into_py(py, &value))
+    }
+
+    /// Returns contents of this `YMap` instance as plain Python lists and dicts, converting nested
+    /// shared types recursively in a single pass, with texts read as strings. Unlike
+    /// `to_json_value`, binary values and datetimes are kept as `bytes` and `datetime` objects.
+    pub fn to_python(&self) -> PyResult<PyObject> {
+        let value = self.to_any()?;
+        Ok(Python::with_gil(|py| value.into_py(py)))
+    }
+
+    /// Returns entries of this `YMap` instance as a Python dict. Nested shared types are kept as
+    /// `YArray`, `YMap` or `YText` instances, unless `deep` is set, in which case they are
+    /// converted the same way as with `to_python`.
+    pub fn to_dict(&self, deep: Option<bool>) -> PyResult<PyObject> {
+        if deep.unwrap_or(false) {
+            return self.to_python();
+        }
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            match &self.0 {
+                SharedType::Integrated(map) => {
+                    for (key, value) in map.iter() {
+                        dict.set_item(key, value.into_py(py))?;
+                    }
+                }
+                SharedType::Prelim(entries) => {
+                    for (key, value) in entries.iter() {
+                        dict.set_item(key, value)?;
+                    }
+                }
+            }
+            Ok(dict.into())
+        })
+    }
+
+    /// Serializes current entries of this `YMap` using MessagePack format, which is more compact
+    /// than JSON and widely supported across languages. Binary values are written using MessagePack
+    /// `bin` type. Only the materialized value gets serialized, not the CRDT metadata required to
+    /// merge it with other replicas.
+    pub fn to_msgpack(&self, _txn: &YTransaction) -> PyResult<PyObject> {
+        let payload = msgpack::encode(&self.to_any()?);
+        Ok(Python::with_gil(|py| PyBytes::new(py, &payload).into()))
+    }
+
+    /// Creates a new preliminary instance of `YMap` from a MessagePack-encoded map, as produced
+    /// by `YMap.to_msgpack`. Binary values are read as `bytes`.
+    #[staticmethod]
+    pub fn from_msgpack(data: Vec<u8>) -> PyResult<YMap> {
+        match msgpack::decode(&data)? {
+            Any::Map(entries) => Python::with_gil(|py| {
+                let entries = entries.into_iter().map(|(k, v)| (k, v.into_py(py)));
+                Ok(YMap(SharedType::prelim(entries.collect())))
+            }),
+            other => Err(PyTypeError::new_err(format!(
+                "Cannot create a YMap from a MessagePack value, which is not a map: {other}"
+            ))),
+        }
+    }
+
     /// Sets a given `key`-`value` entry within this instance of `YMap`. If another entry was
     /// already stored under given `key`, it will be overridden with new `value`.
-    pub fn set(&mut self, txn: &mut YTransaction, key: &str, value: PyObject) {
+    pub fn set(&mut self, txn: &mut YTransaction, key: &str, value: PyObject) -> PyResult<()> {
+        txn.ensure_writable()?;
+        Python::with_gil(|py| CompatiblePyType::try_from(value.as_ref(py))?.ensure_integrable())?;
         match &mut self.0 {
             SharedType::Integrated(v) => {
                 v.insert(txn, key.to_string(), PyObjectWrapper(value));
@@ -119,48 +243,58 @@ impl YMap {
                 v.insert(key.to_string(), value);
             }
         }
+        Ok(())
     }
-    /// Updates `YMap` with the key value pairs in the `items` object.
+
+    /// Returns a value stored under a given `key`, like `dict.setdefault` does. If there's no such
+    /// entry, `default` is inserted first. Shared types are returned in their integrated form, so
+    /// nested structures can be lazily initialized and then modified within the same transaction.
+    pub fn setdefault(
+        &mut self,
+        txn: &mut YTransaction,
+        key: &str,
+        default: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        if let Ok(value) = self.__getitem__(key) {
+            return Ok(value);
+        }
+        let default = default.unwrap_or_else(|| Python::with_gil(|py| py.None()));
+        self.set(txn, key, default)?;
+        self.__getitem__(key)
+    }
+
+    /// Updates `YMap` with the key value pairs in the `items` object. Like `dict.update`, `items`
+    /// can be either a mapping (any object with a `keys` method) or an iterable of key-value pairs,
+    /// and entries override existing ones stored under the same keys. All entries are validated
+    /// before any of them is inserted, so an invalid entry leaves this map unchanged.
     pub fn update(&mut self, txn: &mut YTransaction, items: PyObject) -> PyResult<()> {
-        Python::with_gil(|py| {
-            // Handle collection types
-            if let Ok(dict) = items.extract::<HashMap<String, PyObject>>(py) {
-                dict.into_iter().for_each(|(k, v)| self.set(txn, &k, v));
-                return Ok(());
-            }
-            // Handle iterable of tuples
-            match items.as_ref(py).iter() {
-                Ok(iterable) => {
-                    for value in iterable {
-                        match value {
-                            Ok(kv_pair) => {
-                                if let Ok((key, value)) = kv_pair.extract::<(String, PyObject)>() {
-                                    self.set(txn, &key, value);
-                                } else {
-                                    return Err(PyTypeError::new_err(format!("Update items should be formatted as (str, value) tuples, found: {}", kv_pair)));
-                                }
-                            }
-                            Err(err) => return Err(err),
-                        }
-                    }
-                    Ok(())
-                }
-                Err(err) => Err(err),
-            }
-        })
+        txn.ensure_writable()?;
+        let entries = Python::with_gil(|py| Self::update_entries(items.as_ref(py)))?;
+        entries
+            .into_iter()
+            .try_for_each(|(key, value)| self.set(txn, &key, value))
     }
 
     /// Removes an entry identified by a given `key` from this instance of `YMap`, if such exists.
+    /// The value is read before it's removed, with shared types returned as preliminary copies of
+    /// their contents.
     pub fn pop(
         &mut self,
         txn: &mut YTransaction,
         key: &str,
         fallback: Option<PyObject>,
     ) -> PyResult<PyObject> {
+        txn.ensure_writable()?;
         let popped = match &mut self.0 {
-            SharedType::Integrated(v) => v
-                .remove(txn, key)
-                .map(|v| Python::with_gil(|py| v.into_py(py))),
+            SharedType::Integrated(v) => {
+                let value = v
+                    .get(key)
+                    .map(|value| Python::with_gil(|py| Self::detach(value, py)));
+                if value.is_some() {
+                    v.remove(txn, key);
+                }
+                value
+            }
             SharedType::Prelim(v) => v.remove(key),
         };
         if let Some(value) = popped {
@@ -172,7 +306,18 @@ impl YMap {
         }
     }
 
-    /// Retrieves an item from the map. If the item isn't found, the fallback value is returned.
+    /// Removes all entries from this instance of `YMap`.
+    pub fn clear(&mut self, txn: &mut YTransaction) -> PyResult<()> {
+        txn.ensure_writable()?;
+        match &mut self.0 {
+            SharedType::Integrated(v) => v.clear(txn),
+            SharedType::Prelim(v) => v.clear(),
+        }
+        Ok(())
+    }
+
+    /// Retrieves an item from the map. If the item isn't found, the fallback value (or `None`) is
+    /// returned, like `dict.get` does. Entries storing `None` are returned as is.
     pub fn get(&self, key: &str, fallback: Option<PyObject>) -> PyObject {
         self.__getitem__(key)
             .ok()
@@ -192,6 +337,44 @@ impl YMap {
         entry.ok_or_else(|| PyKeyError::new_err(format!("{key}")))
     }
 
+    /// Returns a kind of a value stored under a given `key`, without converting it into a Python
+    /// object: `"text"`, `"array"`, `"map"` or `"xml"` for nested shared types, and `"primitive"`
+    /// for any other value.
+    pub fn get_type(&self, key: &str) -> PyResult<&'static str> {
+        let kind = match &self.0 {
+            SharedType::Integrated(y_map) => y_map.get(key).map(|value| value_kind(&value)),
+            SharedType::Prelim(hash_map) => hash_map
+                .get(key)
+                .map(|value| Python::with_gil(|py| prelim_value_kind(value.as_ref(py)))),
+        };
+        kind.ok_or_else(|| PyKeyError::new_err(key.to_string()))
+    }
+
+    /// Removes an entry stored under a given `key`. Only preliminary maps can be modified this way,
+    /// as changes of integrated ones have to be made within a transaction.
+    pub fn __delitem__(&mut self, key: &str) -> PyResult<()> {
+        match &mut self.0 {
+            SharedType::Prelim(v) => v
+                .remove(key)
+                .map(|_| ())
+                .ok_or_else(|| PyKeyError::new_err(key.to_string())),
+            SharedType::Integrated(_) => Err(PyTypeError::new_err(
+                "Entries of an integrated YMap can only be removed within a transaction, use pop method instead.",
+            )),
+        }
+    }
+
+    /// Checks if an entry is stored under a given `key`, without reading its value.
+    pub fn __contains__(&self, key: &PyAny) -> bool {
+        match key.extract::<&str>() {
+            Ok(key) => match &self.0 {
+                SharedType::Integrated(v) => v.contains(key),
+                SharedType::Prelim(v) => v.contains_key(key),
+            },
+            Err(_) => false,
+        }
+    }
+
     /// Returns an item view that can be used to traverse over all entries stored within this
     /// instance of `YMap`. Order of entry is not specified.
     ///
@@ -206,42 +389,90 @@ impl YMap {
     /// with doc.begin_transaction() as txn:
     ///     map.set(txn, 'key1', 'value1')
     ///     map.set(txn, 'key2', true)
-    ///     for (key, value) in map.entries(txn)):
-    ///         print(key, value)
+    /// for (key, value) in map.items():
+    ///     print(key, value)
     /// ```
-    pub fn items(&self) -> ItemView {
-        ItemView(&self.0)
+    pub fn items(slf: PyRef<Self>) -> ItemView {
+        ItemView(slf.into())
     }
 
-    pub fn keys(&self) -> KeyView {
-        let inner: *const _ = &self.0;
-        KeyView(inner)
+    /// Returns a view over all keys stored within this instance of `YMap`. Order of keys is not
+    /// specified, but stays the same for as long as the map is not modified.
+    pub fn keys(slf: PyRef<Self>) -> KeyView {
+        KeyView(slf.into())
     }
 
-    pub fn __iter__(&self) -> KeyIterator {
-        let inner: *const _ = &self.0;
-        KeyIterator(YMapIterator::from(inner))
+    /// Returns an iterator over all keys stored within this instance of `YMap`, so that it can be
+    /// traversed like a dictionary.
+    pub fn __iter__(slf: PyRef<Self>) -> KeyIterator {
+        KeyIterator(YMapIterator::from(Py::from(slf)))
     }
 
-    pub fn values(&self) -> ValueView {
-        let inner: *const _ = &self.0;
-        ValueView(inner)
+    /// Returns a view over all values stored within this instance of `YMap`. Values are listed in
+    /// the same order as keys returned by `YMap.keys`.
+    pub fn values(slf: PyRef<Self>) -> ValueView {
+        ValueView(slf.into())
     }
 
-    pub fn observe(&mut self, f: PyObject) -> PyResult<ShallowSubscription> {
+    /// Subscribes to all operations happening over this instance of `YMap`. If `with_snapshot` is
+    /// set, every event exposes a `before` dictionary with the map entries from before the
+    /// transaction. This requires materializing the whole map on every change.
+    ///
+    /// If `deep` is set, the callback works the same way as with `observe_deep`, receiving a list
+    /// of events from this map and all of its nested children.
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        with_snapshot: Option<bool>,
+        deep: Option<bool>,
+    ) -> PyResult<PyObject> {
+        if deep.unwrap_or(false) {
+            if with_snapshot.unwrap_or(false) {
+                return Err(PyValueError::new_err(
+                    "Snapshots are not supported by deep observers.",
+                ));
+            }
+            return Ok(self.observe_deep(f)?.into_py(py));
+        }
         match &mut self.0 {
             SharedType::Integrated(v) => {
+                // Entries of the map as of the last observed transaction.
+                let snapshot = with_snapshot
+                    .unwrap_or(false)
+                    .then(|| Rc::new(RefCell::new(v.to_json())));
+                let before = snapshot.clone();
+                let f = Rc::new(f);
+                let callback = Rc::downgrade(&f);
                 let sub_id: SubscriptionId = v
                     .observe(move |txn, e| {
                         Python::with_gil(|py| {
-                            let e = YMapEvent::new(e, txn);
+                            let mut e = YMapEvent::new(e, txn);
+                            if let Some(before) = &before {
+                                e.before = Some(before.borrow().clone().into_py(py));
+                            }
                             if let Err(err) = f.call1(py, (e,)) {
-                                err.restore(py)
+                                report_observer_error(py, err)
                             }
                         })
                     })
                     .into();
-                Ok(ShallowSubscription(sub_id))
+                let sub = ShallowSubscription::new(sub_id, Observed::Map(v.clone()), callback);
+                Ok(match snapshot {
+                    // Deep observers run after all shallow ones, so refreshing the snapshot from
+                    // one also catches edits made inside nested types.
+                    Some(snapshot) => {
+                        let map = v.clone();
+                        let snapshot_id: SubscriptionId = v
+                            .observe_deep(move |_, _| {
+                                snapshot.replace(map.to_json());
+                            })
+                            .into();
+                        sub.with_snapshot(snapshot_id)
+                    }
+                    None => sub,
+                }
+                .into_py(py))
             }
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
@@ -250,17 +481,23 @@ impl YMap {
     pub fn observe_deep(&mut self, f: PyObject) -> PyResult<DeepSubscription> {
         match &mut self.0 {
             SharedType::Integrated(map) => {
+                let f = Rc::new(f);
+                let callback = Rc::downgrade(&f);
                 let sub: SubscriptionId = map
                     .observe_deep(move |txn, events| {
                         Python::with_gil(|py| {
                             let events = events_into_py(txn, events);
                             if let Err(err) = f.call1(py, (events,)) {
-                                err.restore(py)
+                                report_observer_error(py, err)
                             }
                         })
                     })
                     .into();
-                Ok(DeepSubscription(sub))
+                Ok(DeepSubscription::new(
+                    sub,
+                    Observed::Map(map.clone()),
+                    callback,
+                ))
             }
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
@@ -268,35 +505,122 @@ impl YMap {
     /// Cancels the observer callback associated with the `subscripton_id`.
     pub fn unobserve(&mut self, subscription_id: SubId) -> PyResult<()> {
         match &mut self.0 {
-            SharedType::Integrated(map) => Ok(match subscription_id {
-                SubId::Shallow(ShallowSubscription(id)) => map.unobserve(id),
-                SubId::Deep(DeepSubscription(id)) => map.unobserve_deep(id),
-            }),
+            SharedType::Integrated(_) => Ok(subscription_id.unobserve()),
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
     }
 }
 
+impl YMap {
+    /// Converts a `value`, which is about to be removed from a map or an array, into a Python
+    /// object that stays valid afterwards. Removed shared types lose their contents, so they are
+    /// copied into preliminary instances instead.
+    pub(crate) fn detach(value: Value, py: Python) -> PyObject {
+        match value {
+            Value::YText(text) => YText::new(Some(text.to_string())).into_py(py),
+            Value::YArray(array) => match array.to_json() {
+                Any::Array(items) => {
+                    let items = items.into_vec().into_iter().map(|item| item.into_py(py));
+                    YArray(SharedType::prelim(items.collect())).into_py(py)
+                }
+                other => other.into_py(py),
+            },
+            Value::YMap(map) => match map.to_json() {
+                Any::Map(entries) => {
+                    let entries = entries.into_iter().map(|(k, v)| (k, v.into_py(py)));
+                    YMap(SharedType::prelim(entries.collect())).into_py(py)
+                }
+                other => other.into_py(py),
+            },
+            other => other.into_py(py),
+        }
+    }
+
+    /// Returns entries of this map as a dict suitable for equality checks, with nested shared types
+    /// replaced by their JSON-like contents.
+    fn comparable_entries<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        match &self.0 {
+            SharedType::Integrated(map) => {
+                for (key, value) in map.iter() {
+                    dict.set_item(key, value.to_json().into_py(py))?;
+                }
+            }
+            SharedType::Prelim(entries) => {
+                for (key, value) in entries.iter() {
+                    dict.set_item(key, comparable_value(value.as_ref(py))?)?;
+                }
+            }
+        }
+        Ok(dict)
+    }
+
+    /// Collects and validates entries passed to `update`.
+    fn update_entries(items: &PyAny) -> PyResult<Vec<(String, PyObject)>> {
+        let mut entries = Vec::new();
+        if items.hasattr("keys")? {
+            for key in items.call_method0("keys")?.iter()? {
+                let key = key?;
+                let value = items.get_item(key)?;
+                entries.push((Self::update_key(key)?, value.into()));
+            }
+        } else {
+            for item in items.iter()? {
+                let item = item?;
+                match item.extract::<(&PyAny, PyObject)>() {
+                    Ok((key, value)) => entries.push((Self::update_key(key)?, value)),
+                    Err(_) => {
+                        return Err(PyTypeError::new_err(format!(
+                            "Update items should be formatted as (str, value) tuples, found: {}",
+                            item
+                        )))
+                    }
+                }
+            }
+        }
+        for (_, value) in entries.iter() {
+            CompatiblePyType::try_from(value.as_ref(items.py()))?.ensure_integrable()?;
+        }
+        Ok(entries)
+    }
+
+    fn update_key(key: &PyAny) -> PyResult<String> {
+        key.extract()
+            .map_err(|_| PyTypeError::new_err(format!("YMap keys must be strings, found: {}", key)))
+    }
+
+    /// Materializes entries of this map into a JSON-like value.
+    pub(crate) fn to_any(&self) -> PyResult<Any> {
+        match &self.0 {
+            SharedType::Integrated(map) => Ok(map.to_json()),
+            SharedType::Prelim(entries) => Python::with_gil(|py| {
+                let entries: PyResult<HashMap<String, Any>> = entries
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), msgpack::py_to_any(v.as_ref(py))?)))
+                    .collect();
+                Ok(Any::Map(Box::new(entries?)))
+            }),
+        }
+    }
+}
+
+/// A view over entries of a `YMap`, which reflects all changes made to it afterwards.
 #[pyclass(unsendable)]
-pub struct ItemView(*const SharedType<Map, HashMap<String, PyObject>>);
+pub struct ItemView(Py<YMap>);
 
 #[pymethods]
 impl ItemView {
-    fn __iter__(slf: PyRef<Self>) -> YMapIterator {
-        YMapIterator::from(slf.0)
+    fn __iter__(&self) -> YMapIterator {
+        YMapIterator::from(self.0.clone())
     }
 
-    fn __len__(&self) -> usize {
-        unsafe {
-            match &*self.0 {
-                SharedType::Integrated(map) => map.len() as usize,
-                SharedType::Prelim(map) => map.len(),
-            }
-        }
+    fn __len__(&self, py: Python) -> usize {
+        self.0.borrow(py).__len__()
     }
 
     fn __str__(&self) -> String {
-        let vals: String = YMapIterator::from(self.0)
+        let vals: String = self
+            .__iter__()
             .map(|(key, val)| format!("({key}, {val})"))
             .collect::<Vec<String>>()
             .join(", ");
@@ -308,44 +632,38 @@ impl ItemView {
         format!("ItemView({data})")
     }
 
-    fn __contains__(&self, el: PyObject) -> bool {
-        let kv: Result<(String, PyObject), _> = Python::with_gil(|py| el.extract(py));
+    fn __contains__(&self, py: Python, el: PyObject) -> bool {
+        let kv: Result<(String, PyObject), _> = el.extract(py);
+        let map = self.0.borrow(py);
         kv.ok()
-            .and_then(|(key, value)| unsafe {
-                match &*self.0 {
-                    SharedType::Integrated(map) if map.contains(&key) => map.get(&key).map(|v| {
-                        Python::with_gil(|py| v.into_py(py).as_ref(py).eq(value)).unwrap_or(false)
-                    }),
-                    SharedType::Prelim(map) if map.contains_key(&key) => map
-                        .get(&key)
-                        .map(|v| Python::with_gil(|py| v.as_ref(py).eq(value).unwrap_or(false))),
-                    _ => None,
-                }
+            .and_then(|(key, value)| match &map.0 {
+                SharedType::Integrated(map) => map
+                    .get(&key)
+                    .map(|v| v.into_py(py).as_ref(py).eq(value).unwrap_or(false)),
+                SharedType::Prelim(map) => map
+                    .get(&key)
+                    .map(|v| v.as_ref(py).eq(value).unwrap_or(false)),
             })
             .unwrap_or(false)
     }
 }
 
+/// A view over keys of a `YMap`, which reflects all changes made to it afterwards.
 #[pyclass(unsendable)]
-pub struct KeyView(*const SharedType<Map, HashMap<String, PyObject>>);
+pub struct KeyView(Py<YMap>);
 
 #[pymethods]
 impl KeyView {
-    fn __iter__(slf: PyRef<Self>) -> KeyIterator {
-        KeyIterator(YMapIterator::from(slf.0))
+    fn __iter__(&self) -> KeyIterator {
+        KeyIterator(YMapIterator::from(self.0.clone()))
     }
 
-    fn __len__(&self) -> usize {
-        unsafe {
-            match &*self.0 {
-                SharedType::Integrated(map) => map.len() as usize,
-                SharedType::Prelim(map) => map.len(),
-            }
-        }
+    fn __len__(&self, py: Python) -> usize {
+        self.0.borrow(py).__len__()
     }
 
     fn __str__(&self) -> String {
-        let vals: String = YMapIterator::from(self.0)
+        let vals: String = YMapIterator::from(self.0.clone())
             .map(|(key, _)| key)
             .collect::<Vec<String>>()
             .join(", ");
@@ -357,39 +675,33 @@ impl KeyView {
         format!("KeyView({data})")
     }
 
-    fn __contains__(&self, el: PyObject) -> bool {
-        let key: Result<String, _> = Python::with_gil(|py| el.extract(py));
+    fn __contains__(&self, py: Python, el: PyObject) -> bool {
+        let key: Result<String, _> = el.extract(py);
         key.ok()
-            .map(|key| unsafe {
-                match &*self.0 {
-                    SharedType::Integrated(map) => map.contains(&key),
-                    SharedType::Prelim(map) => map.contains_key(&key),
-                }
+            .map(|key| match &self.0.borrow(py).0 {
+                SharedType::Integrated(map) => map.contains(&key),
+                SharedType::Prelim(map) => map.contains_key(&key),
             })
             .unwrap_or(false)
     }
 }
 
+/// A view over values of a `YMap`, which reflects all changes made to it afterwards.
 #[pyclass(unsendable)]
-pub struct ValueView(*const SharedType<Map, HashMap<String, PyObject>>);
+pub struct ValueView(Py<YMap>);
 
 #[pymethods]
 impl ValueView {
-    fn __iter__(slf: PyRef<Self>) -> ValueIterator {
-        ValueIterator(YMapIterator::from(slf.0))
+    fn __iter__(&self) -> ValueIterator {
+        ValueIterator(YMapIterator::from(self.0.clone()))
     }
 
-    fn __len__(&self) -> usize {
-        unsafe {
-            match &*self.0 {
-                SharedType::Integrated(map) => map.len() as usize,
-                SharedType::Prelim(map) => map.len(),
-            }
-        }
+    fn __len__(&self, py: Python) -> usize {
+        self.0.borrow(py).__len__()
     }
 
     fn __str__(&self) -> String {
-        let vals: String = YMapIterator::from(self.0)
+        let vals: String = YMapIterator::from(self.0.clone())
             .map(|(_, v)| v.to_string())
             .collect::<Vec<String>>()
             .join(", ");
@@ -400,6 +712,10 @@ impl ValueView {
         let data = self.__str__();
         format!("ValueView({data})")
     }
+
+    fn __contains__(&self, py: Python, el: PyObject) -> bool {
+        YMapIterator::from(self.0.clone()).any(|(_, v)| v.as_ref(py).eq(&el).unwrap_or(false))
+    }
 }
 
 pub enum InnerYMapIterator {
@@ -407,30 +723,39 @@ pub enum InnerYMapIterator {
     Prelim(std::collections::hash_map::Iter<'static, String, PyObject>),
 }
 
+/// Iterator over entries of a `YMap`. It keeps a reference to the map it came from, since the
+/// inner iterator borrows from it.
 #[pyclass(unsendable)]
-pub struct YMapIterator(ManuallyDrop<InnerYMapIterator>);
+pub struct YMapIterator {
+    iter: ManuallyDrop<InnerYMapIterator>,
+    _map: Py<YMap>,
+}
 
 impl Drop for YMapIterator {
     fn drop(&mut self) {
-        unsafe { ManuallyDrop::drop(&mut self.0) }
+        unsafe { ManuallyDrop::drop(&mut self.iter) }
     }
 }
 
-impl From<*const SharedType<Map, HashMap<String, PyObject>>> for YMapIterator {
-    fn from(inner_map_ptr: *const SharedType<Map, HashMap<String, PyObject>>) -> Self {
-        unsafe {
+impl From<Py<YMap>> for YMapIterator {
+    fn from(map: Py<YMap>) -> Self {
+        let inner_map_ptr: *const SharedType<Map, HashMap<String, PyObject>> =
+            Python::with_gil(|py| &map.borrow(py).0 as *const _);
+        let iter = unsafe {
             match &*inner_map_ptr {
                 SharedType::Integrated(val) => {
                     let this: *const Map = val;
-                    let shared_iter = InnerYMapIterator::Integrated((*this).iter());
-                    YMapIterator(ManuallyDrop::new(shared_iter))
+                    InnerYMapIterator::Integrated((*this).iter())
                 }
                 SharedType::Prelim(val) => {
                     let this: *const HashMap<String, PyObject> = val;
-                    let shared_iter = InnerYMapIterator::Prelim((*this).iter());
-                    YMapIterator(ManuallyDrop::new(shared_iter))
+                    InnerYMapIterator::Prelim((*this).iter())
                 }
             }
+        };
+        YMapIterator {
+            iter: ManuallyDrop::new(iter),
+            _map: map,
         }
     }
 }
@@ -439,7 +764,7 @@ impl Iterator for YMapIterator {
     type Item = (String, PyObject);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.0.deref_mut() {
+        match self.iter.deref_mut() {
             InnerYMapIterator::Integrated(iter) => {
                 Python::with_gil(|py| iter.next().map(|(k, v)| (k.to_string(), v.into_py(py))))
             }
@@ -491,6 +816,7 @@ pub struct YMapEvent {
     txn: *const Transaction,
     target: Option<PyObject>,
     keys: Option<PyObject>,
+    before: Option<PyObject>,
 }
 
 impl YMapEvent {
@@ -502,6 +828,7 @@ impl YMapEvent {
             txn,
             target: None,
             keys: None,
+            before: None,
         }
     }
 
@@ -536,12 +863,21 @@ impl YMapEvent {
         format!("YMapEvent(target={target}, keys={keys}, path={path})")
     }
 
-    /// Returns an array of keys and indexes creating a path from root type down to current instance
-    /// of shared type (accessible via `target` getter).
+    /// Returns an array of keys and indexes creating a path from the observed shared type (a root
+    /// type for `YDoc.observe_deep`) down to current instance of shared type (accessible via
+    /// `target` getter).
     pub fn path(&self) -> PyObject {
         Python::with_gil(|py| self.inner().path().into_py(py))
     }
 
+    /// Returns a dictionary of clients, whose clocks have advanced during the transaction which
+    /// produced this event, mapped to a `(before, after)` pair of their clocks, like
+    /// `AfterTransactionEvent.clock_ranges` does.
+    pub fn clock_ranges(&self) -> PyObject {
+        let txn = self.txn();
+        clock_ranges_into_py(&txn.before_state, &txn.after_state)
+    }
+
     /// Returns a list of key-value changes made over corresponding `YMap` collection within
     /// bounds of current transaction. These changes follow a format:
     ///
@@ -565,4 +901,12 @@ impl YMapEvent {
             keys
         }
     }
+
+    /// Returns a dictionary with the entries of the observed `YMap` from before current transaction,
+    /// or `None` if the observer was not registered with `with_snapshot=True`. Nested shared types
+    /// are represented by their JSON-like contents.
+    #[getter]
+    pub fn before(&self) -> Option<PyObject> {
+        self.before.clone()
+    }
 }
@@ -1,19 +1,28 @@
+use crate::json_builder::push_json_string;
 use crate::shared_types::{
-    CompatiblePyType, DeepSubscription, DefaultPyErr, IntegratedOperationException,
-    PreliminaryObservationException, ShallowSubscription, SharedType, SubId,
+    normalize_index, report_observer_error, CompatiblePyType, DeepSubscription, DefaultPyErr,
+    IntegratedOperationException, Observed, PreliminaryObservationException, ShallowSubscription,
+    SharedType, SubId,
 };
-use crate::type_conversions::{events_into_py, ToPython};
+use crate::sticky_index::StickyIndex;
+use crate::type_conversions::{attrs_into_py, events_into_py, ToPython};
+use crate::y_array::Index;
+use crate::y_doc::{clock_ranges_into_py, decode_snapshot};
 use crate::y_transaction::YTransaction;
 use lib0::any::Any;
+use lib0::decoding::Read;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyList;
+use pyo3::types::{PyDict, PyList, PySliceIndices, PyString};
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
+use std::os::raw::c_long;
 use std::rc::Rc;
-use yrs::types::text::TextEvent;
-use yrs::types::Attrs;
-use yrs::types::DeepObservable;
-use yrs::{Text, Transaction};
+use yrs::types::text::{ChangeKind, TextEvent, YChange};
+use yrs::types::{Attrs, BranchPtr, DeepObservable, Delta, Value};
+use yrs::updates::decoder::DecoderV1;
+use yrs::{Doc, Map, OffsetKind, Snapshot, StateVector, Text, Transaction};
 
 /// A shared data type used for collaborative text editing. It enables multiple users to add and
 /// remove chunks of text in efficient manner. This type is internally represented as a mutable
@@ -24,19 +33,61 @@ use yrs::{Text, Transaction};
 /// `YText` structure internally uses UTF-8 encoding and its length is described in a number of
 /// bytes rather than individual characters (a single UTF-8 code point can consist of many bytes).
 ///
+/// Editing methods accept negative indexes, which count from the end of the text, like in Python.
+///
 /// Like all Yrs shared data types, `YText` is resistant to the problem of interleaving (situation
 /// when characters inserted one after another may interleave with other peers concurrent inserts
 /// after merging all updates together). In case of Yrs conflict resolution is solved by using
 /// unique document id to determine correct and consistent ordering.
 #[pyclass(unsendable)]
 #[derive(Clone)]
-pub struct YText(pub SharedType<Text, String>);
+pub struct YText(pub SharedType<Text, String>, pub(crate) Vec<PrelimEmbed>);
 impl From<Text> for YText {
     fn from(v: Text) -> Self {
-        YText(SharedType::new(v))
+        YText(SharedType::new(v), Vec::new())
+    }
+}
+
+/// An embed inserted into a preliminary `YText`, together with its index and formatting
+/// attributes. Embeds are inserted after the text, in the same order, once it gets integrated.
+pub(crate) type PrelimEmbed = (u32, Any, Option<Attrs>);
+
+/// A chunk of contents of a preliminary `YText`, as returned by `YText::prelim_chunks`.
+pub(crate) enum PrelimChunk {
+    Text(String),
+    Embed(Any, Option<Attrs>),
+}
+
+impl PrelimChunk {
+    fn len(&self) -> usize {
+        match self {
+            PrelimChunk::Text(text) => text.len(),
+            PrelimChunk::Embed(_, _) => 1,
+        }
     }
 }
 
+/// A run of characters or a single embed, as returned by `YText::segments`.
+enum Segment {
+    Text(String),
+    Embed(Value),
+}
+
+impl Segment {
+    fn len(&self, offset_kind: OffsetKind) -> u32 {
+        match self {
+            Segment::Text(run) => run.chars().map(|c| char_len(c, offset_kind)).sum(),
+            Segment::Embed(_) => 1,
+        }
+    }
+}
+
+/// A character or an embed starting at a given unit of a text.
+enum Unit<'a> {
+    Char(char),
+    Embed(&'a Value),
+}
+
 #[pymethods]
 impl YText {
     /// Creates a new preliminary instance of a `YText` shared data type, with its state initialized
@@ -47,7 +98,7 @@ impl YText {
     /// document store and cannot be nested again: attempt to do so will result in an exception.
     #[new]
     pub fn new(init: Option<String>) -> Self {
-        YText(SharedType::prelim(init.unwrap_or_default()))
+        YText(SharedType::prelim(init.unwrap_or_default()), Vec::new())
     }
 
     /// Returns true if this is a preliminary instance of `YText`.
@@ -75,28 +126,437 @@ impl YText {
         format!("YText({})", self.__str__())
     }
 
-    /// Returns length of an underlying string stored in this `YText` instance,
-    /// understood as a number of UTF-8 encoded bytes.
+    /// Texts are equal when their strings are equal, so they can be compared with plain `str`
+    /// values as well. Embeds and formatting are not taken into account. Since equality is based on
+    /// contents, texts are not hashable.
+    pub fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        let py = other.py();
+        let other = if let Ok(text) = other.extract::<PyRef<YText>>() {
+            text.__str__()
+        } else if let Ok(other) = other.extract::<String>() {
+            other
+        } else {
+            return py.NotImplemented();
+        };
+        match op {
+            CompareOp::Eq => (self.__str__() == other).into_py(py),
+            CompareOp::Ne => (self.__str__() != other).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    /// Returns length of an underlying string stored in this `YText` instance, measured in units of
+    /// the document's offset kind (UTF-8 bytes by default and for preliminary instances). Embeds
+    /// count as a single unit. It doesn't open a transaction, so it can be used while another one
+    /// is open, in which case it includes changes made with that transaction so far.
     pub fn __len__(&self) -> usize {
         match &self.0 {
             SharedType::Integrated(v) => v.len() as usize,
-            SharedType::Prelim(v) => v.len(),
+            SharedType::Prelim(v) => v.len() + self.1.len(),
+        }
+    }
+
+    /// Returns a number of Unicode code points of this text, independent of the document's offset
+    /// kind. Embeds count as a single character.
+    pub fn char_len(&self) -> usize {
+        self.len_in(OffsetKind::Utf32)
+    }
+
+    /// Returns a number of UTF-16 code units of this text, which is how JavaScript clients measure
+    /// string lengths. Embeds count as a single unit.
+    pub fn code_unit_len(&self) -> usize {
+        self.len_in(OffsetKind::Utf16)
+    }
+
+    /// Returns a character at a given index or a substring selected by a given slice. Indexes are
+    /// measured in units of the document's offset kind (UTF-8 bytes for preliminary instances),
+    /// so they match the ones used by `insert` or `delete_range`. Characters spanning multiple
+    /// units are selected by the index of their first unit, while slices are clamped the same way
+    /// as Python strings do. Embeds take a single unit: an integer index returns the embedded
+    /// value, while slices skip them, the same way as the string representation of a text does.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(offset_kind="utf32")
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    ///
+    /// assert text[4] == 'o'
+    /// assert text[-5:] == 'world'
+    /// ```
+    pub fn __getitem__(&self, index: Index) -> PyResult<PyObject> {
+        let (segments, offset_kind) = self.segments();
+        // every unit of the text mapped to a character or an embed starting at it
+        let mut units: Vec<Option<Unit>> = Vec::new();
+        for segment in segments.iter() {
+            match segment {
+                Segment::Text(run) => {
+                    for c in run.chars() {
+                        units.push(Some(Unit::Char(c)));
+                        units.extend((1..char_len(c, offset_kind)).map(|_| None));
+                    }
+                }
+                Segment::Embed(value) => units.push(Some(Unit::Embed(value))),
+            }
+        }
+        Python::with_gil(|py| match index {
+            Index::Int(index) => {
+                let len = units.len() as isize;
+                let index = if index < 0 { len + index } else { index };
+                if index < 0 || index >= len {
+                    return Err(PyIndexError::new_err("string index out of range"));
+                }
+                let start = units[..=index as usize]
+                    .iter()
+                    .rposition(Option::is_some)
+                    .unwrap();
+                Ok(match units[start].as_ref().unwrap() {
+                    Unit::Char(c) => c.to_string().into_py(py),
+                    Unit::Embed(value) => (*value).clone().into_py(py),
+                })
+            }
+            Index::Slice(slice) => {
+                let PySliceIndices {
+                    start, stop, step, ..
+                } = slice.indices(units.len() as c_long)?;
+                let mut result = String::new();
+                let mut i = start;
+                while (step > 0 && i < stop) || (step < 0 && i > stop) {
+                    if let Some(Unit::Char(c)) = units[i as usize] {
+                        result.push(c);
+                    }
+                    i += step;
+                }
+                Ok(result.into_py(py))
+            }
+        })
+    }
+
+    /// Checks if a given `substring` occurs anywhere in this text.
+    pub fn __contains__(&self, substring: &str) -> bool {
+        substring.is_empty()
+            || self.segments().0.iter().any(|segment| match segment {
+                Segment::Text(run) => run.contains(substring),
+                Segment::Embed(_) => false,
+            })
+    }
+
+    /// Returns the index of the first occurrence of a `substring` in this text, starting the search
+    /// at an optional `start` index, or -1 if it wasn't found. Like `__getitem__`, indexes are
+    /// measured in units of the document's offset kind, so they can be passed directly to `insert`
+    /// or `delete_range`.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    ///
+    /// assert text.find('o') == 4
+    /// assert text.find('o', 5) == 7
+    /// assert text.find('xyz') == -1
+    /// ```
+    pub fn find(&self, substring: &str, start: Option<i64>) -> i64 {
+        let (segments, offset_kind) = self.segments();
+        let units = |s: &str| -> i64 { s.chars().map(|c| char_len(c, offset_kind) as i64).sum() };
+        let len: i64 = segments.iter().map(|s| s.len(offset_kind) as i64).sum();
+        let start = match start {
+            Some(start) if start < 0 => (len + start).max(0),
+            Some(start) if start > len => return -1,
+            Some(start) => start,
+            None => 0,
+        };
+        if substring.is_empty() {
+            return start;
+        }
+        let mut offset = 0;
+        for segment in segments.iter() {
+            let run = match segment {
+                Segment::Text(run) => run,
+                Segment::Embed(_) => {
+                    offset += 1;
+                    continue;
+                }
+            };
+            let end = offset + units(run);
+            if end > start {
+                // search from the first character which starts at or after `start`
+                let mut from = run.len();
+                let mut from_offset = end;
+                let mut unit = offset;
+                for (i, c) in run.char_indices() {
+                    if unit >= start {
+                        from = i;
+                        from_offset = unit;
+                        break;
+                    }
+                    unit += char_len(c, offset_kind) as i64;
+                }
+                if let Some(i) = run[from..].find(substring) {
+                    return from_offset + units(&run[from..from + i]);
+                }
+            }
+            offset = end;
+        }
+        -1
+    }
+
+    /// Works like `find`, but raises a `ValueError` when the `substring` wasn't found.
+    pub fn index(&self, substring: &str, start: Option<i64>) -> PyResult<i64> {
+        match self.find(substring, start) {
+            -1 => Err(PyValueError::new_err("substring not found")),
+            index => Ok(index),
         }
     }
 
     /// Returns an underlying shared string stored in this data type.
     pub fn to_json(&self) -> String {
-        format!("\"{}\"", self.__str__())
+        let mut json = String::new();
+        push_json_string(&mut json, &self.__str__());
+        json
+    }
+
+    /// Returns the whole content of this text as a list of Quill-style delta operations. Each
+    /// operation is a dictionary with an `insert` key holding either a run of text sharing the same
+    /// formatting or a single embed, and an optional `attributes` key with that formatting.
+    ///
+    /// Unlike `YTextEvent.delta`, which describes changes made by a single transaction, this
+    /// describes the current state of the text and can be used to initialize an editor.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    ///     text.format(txn, 0, 5, {'bold': True})
+    ///     delta = text.to_delta(txn)
+    ///
+    /// assert delta == [
+    ///     {'insert': 'hello', 'attributes': {'bold': True}},
+    ///     {'insert': ' world'},
+    /// ]
+    /// ```
+    pub fn to_delta(&self, txn: &mut YTransaction) -> PyObject {
+        Python::with_gil(|py| {
+            let ops = PyList::empty(py);
+            match &self.0 {
+                SharedType::Integrated(text) => {
+                    for chunk in text.diff(txn, YChange::identity) {
+                        let op = PyDict::new(py);
+                        op.set_item("insert", chunk.insert.into_py(py)).unwrap();
+                        if let Some(attrs) = chunk.attributes {
+                            op.set_item("attributes", attrs_into_py(&attrs)).unwrap();
+                        }
+                        ops.append(op).unwrap();
+                    }
+                }
+                SharedType::Prelim(text) => {
+                    for chunk in Self::prelim_chunks(text, &self.1) {
+                        let op = PyDict::new(py);
+                        match chunk {
+                            PrelimChunk::Text(text) => op.set_item("insert", text).unwrap(),
+                            PrelimChunk::Embed(embed, attrs) => {
+                                op.set_item("insert", embed.into_py(py)).unwrap();
+                                if let Some(attrs) = attrs {
+                                    op.set_item("attributes", attrs_into_py(&attrs)).unwrap();
+                                }
+                            }
+                        }
+                        ops.append(op).unwrap();
+                    }
+                }
+            }
+            ops.into()
+        })
+    }
+
+    /// Returns a list of Quill-style delta operations, which turn this text as it was when an `old`
+    /// snapshot was taken into the one from a `new` snapshot (or its current state, if not given).
+    /// Operations have the same shape as the ones accepted by `apply_delta`: unchanged text is
+    /// skipped with `retain`, removed text is deleted with `delete`, while added text is inserted
+    /// with `insert` together with its formatting attributes. Changes of formatting alone and
+    /// removed embeds are not reported.
+    ///
+    /// Snapshots are created with `YDoc.snapshot`, which requires a document created with
+    /// `skip_gc=True`. yrs can't compare them once a peer made just one or two changes of a single
+    /// element each (like typing one character) to the document, so `RuntimeError` is raised then.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(skip_gc=True)
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    /// old = doc.snapshot()
+    /// with doc.begin_transaction() as txn:
+    ///     text.delete_range(txn, 0, 6)
+    ///     text.extend(txn, '!')
+    ///     delta = text.diff(txn, old)
+    /// assert delta == [{'delete': 6}, {'retain': 5}, {'insert': '!'}]
+    /// ```
+    pub fn diff(
+        &self,
+        txn: &mut YTransaction,
+        old: Vec<u8>,
+        new: Option<Vec<u8>>,
+    ) -> PyResult<PyObject> {
+        let text = match &self.0 {
+            SharedType::Integrated(text) => text,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let old = decode_snapshot(&old)?;
+        let new = match new {
+            Some(new) => decode_snapshot(&new)?,
+            None => txn.snapshot(),
+        };
+        check_splittable(txn, &[&old, &new])?;
+        let offset_kind = txn.offset_kind;
+        let chunks = text.diff_range(txn, Some(&new), Some(&old), YChange::identity);
+        Python::with_gil(|py| {
+            let ops = PyList::empty(py);
+            let mut retain = 0;
+            let mut delete = 0;
+            let flush = |key: &str, len: &mut u32| -> PyResult<()> {
+                if *len > 0 {
+                    let op = PyDict::new(py);
+                    op.set_item(key, *len)?;
+                    ops.append(op)?;
+                    *len = 0;
+                }
+                Ok(())
+            };
+            for chunk in chunks {
+                let len = match &chunk.insert {
+                    Value::Any(Any::String(s)) => s.chars().map(|c| char_len(c, offset_kind)).sum(),
+                    _ => 1,
+                };
+                match chunk.ychange.map(|change| change.kind) {
+                    None => {
+                        flush("delete", &mut delete)?;
+                        retain += len;
+                    }
+                    Some(ChangeKind::Removed) => {
+                        flush("retain", &mut retain)?;
+                        delete += len;
+                    }
+                    Some(ChangeKind::Added) => {
+                        flush("retain", &mut retain)?;
+                        flush("delete", &mut delete)?;
+                        let op = PyDict::new(py);
+                        op.set_item("insert", chunk.insert.into_py(py))?;
+                        if let Some(attrs) = chunk.attributes {
+                            op.set_item("attributes", attrs_into_py(&attrs))?;
+                        }
+                        ops.append(op)?;
+                    }
+                }
+            }
+            // trailing retains don't change anything
+            flush("delete", &mut delete)?;
+            Ok(ops.into())
+        })
+    }
+
+    /// Applies a list of Quill-style delta operations to this text, moving through it from the
+    /// beginning. Each operation is a dictionary with exactly one of the following keys:
+    ///
+    /// - `retain`: skips over a given number of characters, formatting them with `attributes` if
+    ///   these were provided.
+    /// - `insert`: inserts a string or an embed object, formatted with `attributes`. Unlike
+    ///   `YText.insert`, formatting of preceding text is not inherited when `attributes` are absent.
+    /// - `delete`: removes a given number of characters.
+    ///
+    /// All operations are validated before any change is made. This method only works for `YText`
+    /// instances that already have been integrated into document store.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    ///     text.apply_delta(txn, [
+    ///         {'retain': 5, 'attributes': {'bold': True}},
+    ///         {'delete': 6},
+    ///         {'insert': '!'},
+    ///     ])
+    ///     delta = text.to_delta(txn)
+    ///
+    /// assert delta == [{'insert': 'hello', 'attributes': {'bold': True}}, {'insert': '!'}]
+    /// ```
+    pub fn apply_delta(&mut self, txn: &mut YTransaction, ops: &PyAny) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let ops: Vec<DeltaOp> = ops
+            .iter()?
+            .map(|op| Self::parse_delta_op(op?))
+            .collect::<PyResult<_>>()?;
+        let text = match &mut self.0 {
+            SharedType::Integrated(text) => text,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        Self::check_delta(&ops, text.len(), txn.offset_kind)?;
+        let mut index = 0;
+        for op in ops {
+            match op {
+                DeltaOp::Retain(len, attrs) => {
+                    if let Some(attrs) = attrs {
+                        text.format(txn, index, len, attrs);
+                    }
+                    index += len;
+                }
+                DeltaOp::Delete(len) => {
+                    text.remove_range(txn, index, len);
+                }
+                DeltaOp::InsertText(chunk, _) if chunk.is_empty() => {}
+                DeltaOp::InsertText(chunk, attrs) => {
+                    let len = text.len();
+                    text.insert_with_attributes(txn, index, &chunk, attrs);
+                    index += text.len() - len;
+                }
+                DeltaOp::InsertEmbed(embed, attrs) => {
+                    text.insert_embed_with_attributes(txn, index, embed, attrs);
+                    index += 1;
+                }
+            }
+        }
+        Ok(())
     }
 
     /// Inserts a given `chunk` of text into this `YText` instance, starting at a given `index`.
+    ///
+    /// Inserted text is anchored to the characters around `index`, so there's nothing to associate
+    /// it with: chunks inserted concurrently by different peers at the same index are ordered by
+    /// their client IDs, the same way on every peer. Which side of such inserts a cursor ends up on
+    /// is decided by the `assoc` of its `sticky_index` instead.
     pub fn insert(
         &mut self,
         txn: &mut YTransaction,
-        index: u32,
-        chunk: &str,
+        index: i64,
+        chunk: &PyAny,
         attributes: Option<HashMap<String, PyObject>>,
     ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let chunk = extract_chunk(chunk)?;
+        let index = self.normalize_index(index)?;
+        self.check_range(txn, index, 0)?;
         let attributes: Option<PyResult<Attrs>> = attributes.map(Self::parse_attrs);
 
         if let Some(Ok(attributes)) = attributes {
@@ -120,18 +580,55 @@ impl YText {
         }
     }
 
+    /// Inserts text produced by an iterable of string `chunks` (eg. a generator reading a file),
+    /// starting at a given `index`. Chunks are joined first and inserted as a single operation,
+    /// which is much faster than inserting them one by one.
+    pub fn insert_many(
+        &mut self,
+        txn: &mut YTransaction,
+        index: i64,
+        chunks: &PyAny,
+        attributes: Option<HashMap<String, PyObject>>,
+    ) -> PyResult<()> {
+        let mut content = String::new();
+        for chunk in chunks.iter()? {
+            content.push_str(extract_chunk(chunk?)?);
+        }
+        if content.is_empty() {
+            return Ok(());
+        }
+        let content = PyString::new(chunks.py(), &content);
+        self.insert(txn, index, content, attributes)
+    }
+
     /// Inserts a given `embed` object into this `YText` instance, starting at a given `index`.
     ///
     /// Optional object with defined `attributes` will be used to wrap provided `embed`
-    /// with a formatting blocks.`attributes` are only supported for a `YText` instance which
-    /// already has been integrated into document store.
+    /// with a formatting blocks.
+    ///
+    /// Embeds inserted into a preliminary instance are kept aside from its string and inserted in
+    /// the same order once it gets integrated, after the string. Their indexes are not shifted by
+    /// later edits of the string, and those which no longer fit are placed at the end.
     pub fn insert_embed(
         &mut self,
         txn: &mut YTransaction,
-        index: u32,
+        index: i64,
         embed: PyObject,
         attributes: Option<HashMap<String, PyObject>>,
     ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = self.normalize_index(index)?;
+        match &mut self.0 {
+            SharedType::Integrated(_) => self.check_range(txn, index, 0)?,
+            SharedType::Prelim(_) => {
+                if index as usize > self.__len__() {
+                    return Err(PyIndexError::new_err(format!(
+                        "Index {index} is out of range of a text with length {}.",
+                        self.__len__()
+                    )));
+                }
+            }
+        }
         match &mut self.0 {
             SharedType::Integrated(text) => {
                 let content: PyResult<Any> = Python::with_gil(|py| {
@@ -145,20 +642,85 @@ impl YText {
                 }
                 Ok(())
             }
-            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+            SharedType::Prelim(_) => {
+                let content: Any = Python::with_gil(|py| {
+                    let py_type: CompatiblePyType = embed.extract(py)?;
+                    py_type.try_into()
+                })?;
+                let attrs = attributes.map(Self::parse_attrs).transpose()?;
+                self.1.push((index, content, attrs));
+                Ok(())
+            }
         }
     }
 
+    /// Returns formatting attributes of a character (or an embed) found at a given `index`, as a
+    /// dictionary. Unformatted characters, as well as the end of the text, have no attributes.
+    pub fn attributes_at(&self, txn: &mut YTransaction, index: i64) -> PyResult<PyObject> {
+        let index = self.normalize_index(index)?;
+        if index as usize > self.__len__() {
+            return Err(PyIndexError::new_err(format!(
+                "Index {index} is out of range of a text with length {}.",
+                self.__len__()
+            )));
+        }
+        let attrs = match &self.0 {
+            SharedType::Integrated(text) => {
+                let offset_kind = txn.offset_kind;
+                let mut offset = 0;
+                text.diff(txn, YChange::identity)
+                    .into_iter()
+                    .find(|chunk| {
+                        offset += match &chunk.insert {
+                            Value::Any(Any::String(s)) => {
+                                s.chars().map(|c| char_len(c, offset_kind)).sum()
+                            }
+                            _ => 1,
+                        };
+                        index < offset
+                    })
+                    .and_then(|chunk| chunk.attributes)
+            }
+            SharedType::Prelim(_) => None,
+        };
+        Ok(match attrs {
+            Some(attrs) => attrs_into_py(&attrs),
+            None => Python::with_gil(|py| PyDict::new(py).into()),
+        })
+    }
+
     /// Wraps an existing piece of text within a range described by `index`-`length` parameters with
     /// formatting blocks containing provided `attributes` metadata. This method only works for
     /// `YText` instances that already have been integrated into document store.
+    ///
+    /// The range is measured in the document's offset units. A range extending past the end of
+    /// the text is clamped to it, while formatting an empty range raises a `ValueError`. An `index`
+    /// past the end of the text raises an `IndexError`.
     pub fn format(
         &mut self,
         txn: &mut YTransaction,
-        index: u32,
+        index: i64,
         length: u32,
         attributes: HashMap<String, PyObject>,
     ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = self.normalize_index(index)?;
+        let len = match &self.0 {
+            SharedType::Integrated(text) => text.len(),
+            SharedType::Prelim(text) => text.len() as u32,
+        };
+        if index > len {
+            return Err(PyIndexError::new_err(format!(
+                "Index {index} is out of range of a text with length {len}."
+            )));
+        }
+        let length = length.min(len - index);
+        if length == 0 {
+            return Err(PyValueError::new_err(format!(
+                "Cannot format an empty range at index {index}."
+            )));
+        }
+        self.check_range(txn, index, length)?;
         match Self::parse_attrs(attributes) {
             Ok(attrs) => match &mut self.0 {
                 SharedType::Integrated(text) => {
@@ -171,44 +733,521 @@ impl YText {
         }
     }
 
+    /// Removes formatting attributes with given `keys` from a range described by `index`-`length`
+    /// parameters. If `keys` are not provided, all formatting found within that range is removed.
+    /// This method only works for `YText` instances that already have been integrated into
+    /// document store.
+    pub fn remove_format(
+        &mut self,
+        txn: &mut YTransaction,
+        index: i64,
+        length: u32,
+        keys: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = self.normalize_index(index)?;
+        self.check_range(txn, index, length)?;
+        let text = match &mut self.0 {
+            SharedType::Integrated(text) => text,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let keys = match keys {
+            Some(keys) => keys,
+            None => {
+                let offset_kind = txn.offset_kind;
+                let mut keys = Vec::new();
+                let mut offset = 0;
+                for chunk in text.diff(txn, YChange::identity) {
+                    let start = offset;
+                    offset += match &chunk.insert {
+                        Value::Any(Any::String(s)) => {
+                            s.chars().map(|c| char_len(c, offset_kind)).sum()
+                        }
+                        _ => 1,
+                    };
+                    if start >= index + length || offset <= index {
+                        continue;
+                    }
+                    let found = chunk.attributes.iter().flat_map(|attrs| attrs.keys());
+                    for key in found {
+                        if !keys.iter().any(|k: &String| k == key.as_ref()) {
+                            keys.push(key.to_string());
+                        }
+                    }
+                }
+                keys
+            }
+        };
+        if !keys.is_empty() {
+            // formatting is removed by setting attributes to null
+            let attrs: Attrs = keys
+                .into_iter()
+                .map(|key| (Rc::from(key), Any::Null))
+                .collect();
+            text.format(txn, index, length, attrs);
+        }
+        Ok(())
+    }
+
     /// Appends a given `chunk` of text at the end of current `YText` instance.
-    pub fn extend(&mut self, txn: &mut YTransaction, chunk: &str) {
+    pub fn extend(&mut self, txn: &mut YTransaction, chunk: &PyAny) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let chunk = extract_chunk(chunk)?;
         match &mut self.0 {
             SharedType::Integrated(v) => v.push(txn, chunk),
             SharedType::Prelim(v) => v.push_str(chunk),
         }
+        Ok(())
     }
     /// Deletes character at the specified index.
-    pub fn delete(&mut self, txn: &mut YTransaction, index: u32) {
-        self.delete_range(txn, index, 1);
+    pub fn delete(&mut self, txn: &mut YTransaction, index: i64) -> PyResult<()> {
+        self.delete_range(txn, index, 1)
     }
 
     /// Deletes a specified range of of characters, starting at a given `index`.
-    /// Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
-    pub fn delete_range(&mut self, txn: &mut YTransaction, index: u32, length: u32) {
+    /// Both `index` and `length` are measured in offset units of the document (see `offset_kind`
+    /// option of `YDoc`).
+    pub fn delete_range(
+        &mut self,
+        txn: &mut YTransaction,
+        index: i64,
+        length: u32,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let index = self.normalize_index(index)?;
+        self.check_range(txn, index, length)?;
         match &mut self.0 {
             SharedType::Integrated(v) => v.remove_range(txn, index, length),
             SharedType::Prelim(v) => {
                 v.drain((index as usize)..(index + length) as usize);
             }
         }
+        Ok(())
+    }
+
+    /// Removes all contents of this `YText` instance, including embeds.
+    pub fn clear(&mut self, txn: &mut YTransaction) -> PyResult<()> {
+        txn.ensure_writable()?;
+        match &mut self.0 {
+            SharedType::Integrated(v) => {
+                let len = v.len();
+                if len > 0 {
+                    v.remove_range(txn, 0, len);
+                }
+            }
+            SharedType::Prelim(v) => {
+                v.clear();
+                self.1.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a `StickyIndex` pointing at a given `index` of this text. Unlike the index itself, it
+    /// stays in place when the text before it gets edited, so it can be used to track cursors and
+    /// selections of remote peers. Negative `assoc` associates the position with the character
+    /// before the `index` instead of the one after it, which decides whether text inserted exactly
+    /// at the position ends up after or before it.
+    ///
+    /// A position with no character next to it in direction of `assoc` is anchored to the text
+    /// itself. For a nested text this requires it to have some content, otherwise a `ValueError`
+    /// is raised.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    ///     cursor = text.sticky_index(txn, 6)
+    ///     text.insert(txn, 0, 'well, ')
+    ///     assert text.resolve_index(txn, cursor) == 12
+    /// ```
+    pub fn sticky_index(
+        &self,
+        txn: &mut YTransaction,
+        index: u32,
+        assoc: Option<i32>,
+    ) -> PyResult<StickyIndex> {
+        match &self.0 {
+            SharedType::Integrated(text) => {
+                let offset_kind = txn.offset_kind;
+                StickyIndex::new(text, txn, index, assoc.unwrap_or(0), offset_kind)
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Returns a current index of a position created with `sticky_index`. If the character the
+    /// position was anchored to has been deleted, it resolves to where that character used to be,
+    /// as long as it wasn't garbage collected yet (see `YDoc(skip_gc=True)`) - otherwise `None` is
+    /// returned. `None` is also returned for positions created by a different text.
+    pub fn resolve_index(
+        &self,
+        txn: &mut YTransaction,
+        position: &StickyIndex,
+    ) -> PyResult<Option<u32>> {
+        match &self.0 {
+            SharedType::Integrated(text) => {
+                let offset_kind = txn.offset_kind;
+                Ok(position.resolve(text, txn, offset_kind))
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Anchors a comment identified by `comment_id` to a range of text between `start_index`
+    /// (inclusive) and `end_index` (exclusive), attaching arbitrary `data` to it. Negative indexes
+    /// count from the end of the text. The range is tracked as text gets edited: insertions made
+    /// inside of it extend it, while deletions shrink it. Adding a comment with an already used
+    /// `comment_id` replaces its range and data.
+    ///
+    /// Comments are stored inside of the document, next to the text contents rather than as their
+    /// formatting, so they are synchronized with other peers together with the text itself.
+    pub fn add_comment(
+        &mut self,
+        txn: &mut YTransaction,
+        start_index: i64,
+        end_index: i64,
+        comment_id: &str,
+        data: PyObject,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let start_index = self.normalize_index(start_index)?;
+        let end_index = self.normalize_index(end_index)?;
+        if start_index >= end_index {
+            return Err(PyIndexError::new_err(format!(
+                "Cannot anchor a comment to an empty range {start_index}..{end_index}."
+            )));
+        }
+        self.check_range(txn, start_index, end_index - start_index)?;
+        let data =
+            Python::with_gil(|py| Any::try_from(CompatiblePyType::try_from(data.as_ref(py))?))?;
+        match &mut self.0 {
+            SharedType::Integrated(text) => {
+                let offset_kind = txn.offset_kind;
+                // the range is anchored to its first and last character, so text inserted right
+                // next to it stays outside
+                let start = StickyIndex::new(text, txn, start_index, 0, offset_kind)?;
+                let end = StickyIndex::new(text, txn, end_index, -1, offset_kind)?;
+                let comment = HashMap::from([
+                    ("data".to_string(), data),
+                    ("start".to_string(), Any::Buffer(start.encode().into())),
+                    ("end".to_string(), Any::Buffer(end.encode().into())),
+                ]);
+                let key = format!("{COMMENT_PREFIX}{comment_id}");
+                Self::comments_registry(text).insert(txn, key, Any::Map(Box::new(comment)));
+                Ok(())
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Returns a list of comments added to this `YText` instance, ordered by their position. Each
+    /// comment is described by a dictionary with its `id`, `data` and a current `start`-`end` range.
+    ///
+    /// When all text a comment was anchored to gets deleted, the comment is not dropped, but
+    /// reported as `orphaned` (with `start` and `end` set to `None`) until it's removed with
+    /// `remove_comment`.
+    pub fn comments(&self, txn: &mut YTransaction) -> PyResult<PyObject> {
+        match &self.0 {
+            SharedType::Integrated(text) => {
+                let offset_kind = txn.offset_kind;
+                let mut comments: Vec<_> = Self::comments_registry(text)
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        let id = key.strip_prefix(COMMENT_PREFIX)?;
+                        let mut comment = match value {
+                            Value::Any(Any::Map(comment)) => *comment,
+                            _ => return None,
+                        };
+                        let anchor = |name: &str| match comment.get(name) {
+                            Some(Any::Buffer(data)) => StickyIndex::decode(data).ok(),
+                            _ => None,
+                        };
+                        let (start, end) = (anchor("start")?, anchor("end")?);
+                        let range = match (
+                            start.resolve(text, txn, offset_kind),
+                            end.resolve(text, txn, offset_kind),
+                        ) {
+                            (Some(start), Some(end)) if start < end => Some((start, end)),
+                            _ => None,
+                        };
+                        let data = comment.remove("data").unwrap_or(Any::Null);
+                        Some((range, id.to_string(), data))
+                    })
+                    .collect();
+                // orphaned comments (without a range) are put at the end
+                comments.sort_by(|(a, a_id, _), (b, b_id, _)| {
+                    (a.is_none(), a, a_id).cmp(&(b.is_none(), b, b_id))
+                });
+                Python::with_gil(|py| {
+                    let comments = comments.into_iter().map(|(range, id, data)| {
+                        let comment = PyDict::new(py);
+                        comment.set_item("id", id)?;
+                        comment.set_item("start", range.map(|(start, _)| start))?;
+                        comment.set_item("end", range.map(|(_, end)| end))?;
+                        comment.set_item("data", data.into_py(py))?;
+                        comment.set_item("orphaned", range.is_none())?;
+                        Ok(comment)
+                    });
+                    Ok(PyList::new(py, comments.collect::<PyResult<Vec<_>>>()?).into())
+                })
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Removes a comment identified by `comment_id` together with its range anchors. Raises a
+    /// `KeyError` if there was no such comment.
+    pub fn remove_comment(&mut self, txn: &mut YTransaction, comment_id: &str) -> PyResult<()> {
+        txn.ensure_writable()?;
+        match &mut self.0 {
+            SharedType::Integrated(text) => {
+                let key = format!("{COMMENT_PREFIX}{comment_id}");
+                match Self::comments_registry(text).remove(txn, &key) {
+                    Some(_) => Ok(()),
+                    None => Err(PyKeyError::new_err(comment_id.to_string())),
+                }
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Replaces the contents of this `YText` instance with `content`, editing only the part between
+    /// their common prefix and suffix. Unchanged text keeps its identity, which makes this method
+    /// cheaper and friendlier to concurrent edits than deleting everything and inserting `content`.
+    /// If this `YText` contains embeds, its whole contents are replaced.
+    pub fn set_content(&mut self, txn: &mut YTransaction, content: &str) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let offset_kind = txn.offset_kind;
+        let len = |s: &str| -> u32 { s.chars().map(|c| char_len(c, offset_kind)).sum() };
+        match &mut self.0 {
+            SharedType::Integrated(text) => {
+                let current = text.to_string();
+                if len(&current) != text.len() {
+                    // embeds are not a part of the string, so offsets computed from it are invalid
+                    text.remove_range(txn, 0, text.len());
+                    text.insert(txn, 0, content);
+                    return Ok(());
+                }
+                let prefix = current
+                    .char_indices()
+                    .zip(content.chars())
+                    .find(|((_, a), b)| a != b)
+                    .map(|((i, _), _)| i)
+                    .unwrap_or_else(|| current.len().min(content.len()));
+                let (old_rest, new_rest) = (&current[prefix..], &content[prefix..]);
+                let suffix: usize = old_rest
+                    .chars()
+                    .rev()
+                    .zip(new_rest.chars().rev())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(c, _)| c.len_utf8())
+                    .sum();
+                let removed = &old_rest[..old_rest.len() - suffix];
+                let inserted = &new_rest[..new_rest.len() - suffix];
+                let index = len(&current[..prefix]);
+                if !removed.is_empty() {
+                    text.remove_range(txn, index, len(removed));
+                }
+                if !inserted.is_empty() {
+                    text.insert(txn, index, inserted);
+                }
+            }
+            SharedType::Prelim(v) => *v = content.to_string(),
+        }
+        Ok(())
+    }
+
+    /// Replaces non-overlapping occurrences of `old` with `new`, searching from the beginning of
+    /// the text. When `count` is given, only that many first occurrences are replaced. All edits are
+    /// applied within a given transaction. Returns the number of replaced occurrences.
+    pub fn replace(
+        &mut self,
+        txn: &mut YTransaction,
+        old: &str,
+        new: &str,
+        count: Option<usize>,
+    ) -> PyResult<u32> {
+        txn.ensure_writable()?;
+        if old.is_empty() {
+            return Err(PyValueError::new_err("Cannot replace an empty substring."));
+        }
+        let offset_kind = txn.offset_kind;
+        let len = |s: &str| -> u32 { s.chars().map(|c| char_len(c, offset_kind)).sum() };
+        let count = count.unwrap_or(usize::MAX);
+        match &mut self.0 {
+            SharedType::Integrated(text) => {
+                let current = text.to_string();
+                if len(&current) != text.len() {
+                    return Err(PyValueError::new_err(
+                        "Cannot replace text in a YText containing embeds.",
+                    ));
+                }
+                let matches: Vec<usize> = current
+                    .match_indices(old)
+                    .take(count)
+                    .map(|(i, _)| i)
+                    .collect();
+                // going back to front keeps indexes of the remaining matches valid
+                for &i in matches.iter().rev() {
+                    let index = len(&current[..i]);
+                    text.remove_range(txn, index, len(old));
+                    if !new.is_empty() {
+                        text.insert(txn, index, new);
+                    }
+                }
+                Ok(matches.len() as u32)
+            }
+            SharedType::Prelim(v) => {
+                let replaced = v.matches(old).take(count).count();
+                *v = v.replacen(old, new, replaced);
+                Ok(replaced as u32)
+            }
+        }
+    }
+
+    /// Splits this text on `\n` and returns its lines (without the line breaks) together with their
+    /// start indexes. Indexes are measured using the offset kind of the document, so that they can
+    /// be passed to `insert` or `delete_range` directly. Embeds are not a part of the lines' text,
+    /// but they still occupy a single index.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'first\nsecond')
+    ///     assert text.lines(txn) == [('first', 0), ('second', 6)]
+    /// ```
+    pub fn lines(&self, txn: &mut YTransaction) -> Vec<(String, u32)> {
+        // chunks of text, with `None` standing for embeds
+        let (chunks, offset_kind): (Vec<Option<String>>, _) = match &self.0 {
+            SharedType::Integrated(text) => {
+                let chunks = text.diff(txn, YChange::identity).into_iter();
+                let chunks = chunks.map(|chunk| match chunk.insert {
+                    Value::Any(Any::String(s)) => Some(s.to_string()),
+                    _ => None,
+                });
+                (chunks.collect(), txn.offset_kind)
+            }
+            SharedType::Prelim(text) => {
+                let chunks = Self::prelim_chunks(text, &self.1).into_iter();
+                let chunks = chunks.map(|chunk| match chunk {
+                    PrelimChunk::Text(s) => Some(s),
+                    PrelimChunk::Embed(_, _) => None,
+                });
+                (chunks.collect(), OffsetKind::Bytes)
+            }
+        };
+        let mut lines = vec![(String::new(), 0)];
+        let mut offset = 0;
+        for chunk in chunks {
+            match chunk {
+                Some(s) => {
+                    for c in s.chars() {
+                        offset += char_len(c, offset_kind);
+                        if c == '\n' {
+                            lines.push((String::new(), offset));
+                        } else {
+                            lines.last_mut().unwrap().0.push(c);
+                        }
+                    }
+                }
+                None => offset += 1,
+            }
+        }
+        lines
+    }
+
+    /// Inserts `indent_str` (four spaces by default) at the beginning of every line overlapping
+    /// with a range between `start_index` and `end_index`, including lines which are only partially
+    /// selected. All edits are applied within a given transaction.
+    pub fn indent_lines(
+        &mut self,
+        txn: &mut YTransaction,
+        start_index: u32,
+        end_index: u32,
+        indent_str: Option<&str>,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let indent_str = indent_str.unwrap_or(DEFAULT_INDENT);
+        match &mut self.0 {
+            SharedType::Integrated(text) => {
+                for (line_start, _) in Self::selected_lines(text, txn, start_index, end_index)? {
+                    text.insert(txn, line_start, indent_str);
+                }
+                Ok(())
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Removes a single level of indentation from every line overlapping with a range between
+    /// `start_index` and `end_index`, including lines which are only partially selected. Lines
+    /// starting with `indent_str` (four spaces by default) lose that prefix, while the remaining
+    /// ones lose at most as many leading spaces or tabs as there are characters in `indent_str`.
+    pub fn dedent_lines(
+        &mut self,
+        txn: &mut YTransaction,
+        start_index: u32,
+        end_index: u32,
+        indent_str: Option<&str>,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let indent_str = indent_str.unwrap_or(DEFAULT_INDENT);
+        let offset_kind = txn.offset_kind;
+        match &mut self.0 {
+            SharedType::Integrated(text) => {
+                let lines = Self::selected_lines(text, txn, start_index, end_index)?;
+                for (line_start, indent) in lines {
+                    let removed: u32 = if indent.starts_with(indent_str) {
+                        indent_str.chars().map(|c| char_len(c, offset_kind)).sum()
+                    } else {
+                        indent
+                            .chars()
+                            .take(indent_str.chars().count())
+                            .map(|c| char_len(c, offset_kind))
+                            .sum()
+                    };
+                    if removed > 0 {
+                        text.remove_range(txn, line_start, removed);
+                    }
+                }
+                Ok(())
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        }
     }
 
     /// Observes updates from the `YText` instance.
     pub fn observe(&mut self, f: PyObject) -> PyResult<ShallowSubscription> {
         match &mut self.0 {
             SharedType::Integrated(text) => {
+                let f = Rc::new(f);
+                let callback = Rc::downgrade(&f);
                 let sub_id = text
                     .observe(move |txn, e| {
                         Python::with_gil(|py| {
                             let e = YTextEvent::new(e, txn);
                             if let Err(err) = f.call1(py, (e,)) {
-                                err.restore(py)
+                                report_observer_error(py, err)
                             }
                         });
                     })
                     .into();
-                Ok(ShallowSubscription(sub_id))
+                Ok(ShallowSubscription::new(
+                    sub_id,
+                    Observed::Text(text.clone()),
+                    callback,
+                ))
             }
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
@@ -218,17 +1257,23 @@ impl YText {
     pub fn observe_deep(&mut self, f: PyObject) -> PyResult<DeepSubscription> {
         match &mut self.0 {
             SharedType::Integrated(text) => {
+                let f = Rc::new(f);
+                let callback = Rc::downgrade(&f);
                 let sub = text
                     .observe_deep(move |txn, events| {
                         Python::with_gil(|py| {
                             let events = events_into_py(txn, events);
                             if let Err(err) = f.call1(py, (events,)) {
-                                err.restore(py)
+                                report_observer_error(py, err)
                             }
                         })
                     })
                     .into();
-                Ok(DeepSubscription(sub))
+                Ok(DeepSubscription::new(
+                    sub,
+                    Observed::Text(text.clone()),
+                    callback,
+                ))
             }
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
@@ -236,17 +1281,403 @@ impl YText {
     /// Cancels the observer callback associated with the `subscripton_id`.
     pub fn unobserve(&mut self, subscription_id: SubId) -> PyResult<()> {
         match &mut self.0 {
-            SharedType::Integrated(text) => Ok(match subscription_id {
-                SubId::Shallow(ShallowSubscription(id)) => text.unobserve(id),
-                SubId::Deep(DeepSubscription(id)) => text.unobserve_deep(id),
-            }),
+            SharedType::Integrated(_) => Ok(subscription_id.unobserve()),
             SharedType::Prelim(_) => Err(PreliminaryObservationException::default_message()),
         }
     }
 }
 
+/// Extracts a `chunk` of text passed to one of the insertion methods. Other values are rejected
+/// with a clearer message than the one of a default argument conversion.
+pub(crate) fn extract_chunk(chunk: &PyAny) -> PyResult<&str> {
+    match chunk.downcast::<PyString>() {
+        Ok(chunk) => chunk.to_str(),
+        Err(_) => Err(PyTypeError::new_err(format!(
+            "chunk must be str, got {}",
+            chunk.get_type().name()?
+        ))),
+    }
+}
+
+/// Fails when yrs 0.12.2 would panic while splitting blocks of the document by given `snapshots`.
+/// It looks up blocks of a client starting from the index guessed as `clock / (state - 1)` times
+/// the index of the last one, which divides by zero or overshoots the list for clients with a
+/// single block one element long, or with two blocks, as long as both are one element long.
+fn check_splittable(txn: &Transaction, snapshots: &[&Snapshot]) -> PyResult<()> {
+    let state = txn.state_vector();
+    for snapshot in snapshots {
+        for (&client, &clock) in snapshot.state_map.iter() {
+            let fails = match state.get(&client) {
+                0 => false,
+                1 => clock > 0,
+                end => clock / (end - 1) >= 2 && block_count(txn, &state, client) > 1,
+            };
+            if fails {
+                return Err(PyRuntimeError::new_err(format!(
+                    "Cannot compare snapshots of a document, in which client {client} made only \
+                    one or two changes of a single element each."
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the number of blocks stored for a given `client`, read from an update encoding all of
+/// them, since yrs doesn't expose its block store.
+fn block_count(txn: &Transaction, state: &StateVector, client: u64) -> u32 {
+    let mut others = StateVector::default();
+    for (&other, &clock) in state.iter().filter(|&(&other, _)| other != client) {
+        others.set_max(other, clock);
+    }
+    let update = txn.encode_diff_v1(&others);
+    let mut decoder = DecoderV1::from(update.as_slice());
+    // the update was just encoded by yrs and holds blocks of a single client
+    decoder
+        .read_var::<u32>()
+        .and_then(|_| decoder.read_var())
+        .unwrap_or_default()
+}
+
+/// Returns a length of a character in a given offset units.
+pub(crate) fn char_len(c: char, offset_kind: OffsetKind) -> u32 {
+    match offset_kind {
+        OffsetKind::Bytes => c.len_utf8() as u32,
+        OffsetKind::Utf16 => c.len_utf16() as u32,
+        OffsetKind::Utf32 => 1,
+    }
+}
+
+/// Prefix of the keys under which comments are stored in the map component of a text.
+const COMMENT_PREFIX: &str = "comment:";
+
+/// Indentation used by `indent_lines` and `dedent_lines` when none was provided.
+const DEFAULT_INDENT: &str = "    ";
+
 impl YText {
-    fn parse_attrs(attrs: HashMap<String, PyObject>) -> PyResult<Attrs> {
+    /// Resolves an `index` passed to one of the editing methods, where negative indexes count
+    /// from the end of this text, in offset units of the document.
+    fn normalize_index(&self, index: i64) -> PyResult<u32> {
+        let len = match &self.0 {
+            SharedType::Integrated(text) => text.len() as usize,
+            SharedType::Prelim(text) => text.len(),
+        };
+        normalize_index(index, len, "a text")
+    }
+
+    /// Ensures that a range of `length` characters starting at a given `index` fits within this
+    /// text and doesn't split a character spanning more than one offset unit (eg. an emoji in
+    /// a document measuring offsets in UTF-16 code units). Preliminary texts are indexed by UTF-8
+    /// bytes.
+    fn check_range(&self, txn: &mut YTransaction, index: u32, length: u32) -> PyResult<()> {
+        let len = match &self.0 {
+            SharedType::Integrated(text) => text.len(),
+            SharedType::Prelim(text) => text.len() as u32,
+        };
+        let end = index as u64 + length as u64;
+        if end > len as u64 {
+            return Err(PyIndexError::new_err(if length <= 1 {
+                format!("Index {index} is out of range of a text with length {len}.")
+            } else {
+                format!("Range {index}..{end} is out of range of a text with length {len}.")
+            }));
+        }
+        match &self.0 {
+            SharedType::Integrated(text) => {
+                let end = end as u32;
+                let indexes: &[u32] = if length == 0 { &[index] } else { &[index, end] };
+                for &index in indexes {
+                    if !Self::is_char_boundary(text, txn, index) {
+                        let unit = match txn.offset_kind {
+                            OffsetKind::Bytes => "UTF-8 bytes",
+                            _ => "UTF-16 code units",
+                        };
+                        return Err(PyValueError::new_err(format!(
+                            "Index {index} is not on a character boundary of a text measured in \
+                            {unit}."
+                        )));
+                    }
+                }
+            }
+            SharedType::Prelim(text) => {
+                let end = end as usize;
+                if !text.is_char_boundary(index as usize) || !text.is_char_boundary(end) {
+                    return Err(PyValueError::new_err(format!(
+                        "Range {index}..{end} splits a multi-byte character of a preliminary text."
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks if a given `index` of an integrated text falls between two characters (or embeds),
+    /// rather than into the middle of a character spanning more than one offset unit.
+    fn is_char_boundary(text: &Text, txn: &mut YTransaction, index: u32) -> bool {
+        let offset_kind = txn.offset_kind;
+        if index == 0 || index >= text.len() {
+            return true;
+        }
+        match offset_kind {
+            OffsetKind::Utf32 => return true,
+            // yrs measures blocks of a text in UTF-16 code units, which only ASCII characters
+            // take as many of as UTF-8 bytes, so no character of such text can be split
+            OffsetKind::Bytes if text.as_ref().len() == text.len() => return true,
+            _ => {}
+        }
+        let mut offset = 0;
+        for chunk in text.diff(txn, YChange::identity) {
+            match &chunk.insert {
+                Value::Any(Any::String(s)) if matches!(offset_kind, OffsetKind::Bytes) => {
+                    if offset + s.len() as u32 > index {
+                        return s.is_char_boundary((index - offset) as usize);
+                    }
+                    offset += s.len() as u32;
+                }
+                Value::Any(Any::String(s)) => {
+                    for c in s.chars() {
+                        if offset >= index {
+                            return offset == index;
+                        }
+                        offset += c.len_utf16() as u32;
+                    }
+                }
+                _ => offset += 1,
+            }
+            if offset >= index {
+                return offset == index;
+            }
+        }
+        true
+    }
+
+    /// Returns contents of a preliminary text as chunks of its string interleaved with `embeds`,
+    /// which are placed one after another at their indexes. Embeds with indexes out of range are
+    /// placed at the end, while the ones pointing into the middle of a character follow it.
+    pub(crate) fn prelim_chunks(text: &str, embeds: &[PrelimEmbed]) -> Vec<PrelimChunk> {
+        let mut chunks = Vec::new();
+        if !text.is_empty() {
+            chunks.push(PrelimChunk::Text(text.to_string()));
+        }
+        for (index, embed, attrs) in embeds {
+            let index = *index as usize;
+            let mut offset = 0;
+            let mut i = 0;
+            while i < chunks.len() && offset < index {
+                if let PrelimChunk::Text(text) = &mut chunks[i] {
+                    if offset + text.len() > index {
+                        let mut at = index - offset;
+                        while !text.is_char_boundary(at) {
+                            at += 1;
+                        }
+                        let rest = text.split_off(at);
+                        if !rest.is_empty() {
+                            chunks.insert(i + 1, PrelimChunk::Text(rest));
+                        }
+                    }
+                }
+                offset += chunks[i].len();
+                i += 1;
+            }
+            chunks.insert(i, PrelimChunk::Embed(embed.clone(), attrs.clone()));
+        }
+        chunks
+    }
+
+    /// Returns a length of this text measured in a given offset kind, rather than the document's
+    /// own one.
+    fn len_in(&self, offset_kind: OffsetKind) -> usize {
+        let segments = self.segments().0;
+        segments.iter().map(|s| s.len(offset_kind) as usize).sum()
+    }
+
+    /// Returns contents of this text as runs of characters separated by embeds, together with an
+    /// offset kind used to measure its indexes (UTF-8 bytes for preliminary instances).
+    fn segments(&self) -> (Vec<Segment>, OffsetKind) {
+        let mut segments = Vec::new();
+        let mut push = |segment| match (segments.last_mut(), segment) {
+            (Some(Segment::Text(run)), Segment::Text(s)) => run.push_str(&s),
+            (_, segment) => segments.push(segment),
+        };
+        match &self.0 {
+            SharedType::Integrated(text) => {
+                // yrs only reads the transaction when diffing against snapshots, so the one of an
+                // empty document is enough to read the contents without opening a transaction on
+                // the text's own document
+                let mut txn = Doc::with_client_id(0).transact();
+                for chunk in text.diff(&mut txn, YChange::identity) {
+                    push(match chunk.insert {
+                        Value::Any(Any::String(s)) => Segment::Text(s.into()),
+                        value => Segment::Embed(value),
+                    });
+                }
+                let offset_kind = Self::offset_kind(text, &segments);
+                (segments, offset_kind)
+            }
+            SharedType::Prelim(text) => {
+                for chunk in Self::prelim_chunks(text, &self.1) {
+                    push(match chunk {
+                        PrelimChunk::Text(s) => Segment::Text(s),
+                        PrelimChunk::Embed(any, _) => Segment::Embed(Value::Any(any)),
+                    });
+                }
+                (segments, OffsetKind::Bytes)
+            }
+        }
+    }
+
+    /// Shared types don't have access to their document's options outside of a transaction, but
+    /// the length of a text is measured in its document's offset kind, which is the one giving
+    /// the same length to its `segments`. When more offset kinds do, they measure all indexes of
+    /// the text the same way (eg. UTF-8 bytes and UTF-16 code units of an ASCII text).
+    fn offset_kind(text: &Text, segments: &[Segment]) -> OffsetKind {
+        [OffsetKind::Bytes, OffsetKind::Utf16, OffsetKind::Utf32]
+            .iter()
+            .copied()
+            .find(|&kind| segments.iter().map(|s| s.len(kind)).sum::<u32>() == text.len())
+            .unwrap_or(OffsetKind::Utf32)
+    }
+
+    /// Comment data lives in the map component of the text's own branch, which is not a part of
+    /// the text contents.
+    fn comments_registry(text: &Text) -> Map {
+        Map::from(BranchPtr::from(text.as_ref()))
+    }
+
+    /// Returns all lines of a text as pairs of their start offset and leading whitespace. Embeds
+    /// are treated as non-whitespace content.
+    fn line_indents(text: &Text, txn: &mut YTransaction) -> Vec<(u32, String)> {
+        let offset_kind = txn.offset_kind;
+        let mut lines = vec![(0, String::new())];
+        let mut in_indent = true;
+        let mut offset = 0;
+        for chunk in text.diff(txn, YChange::identity) {
+            match &chunk.insert {
+                Value::Any(Any::String(s)) => {
+                    for c in s.chars() {
+                        offset += char_len(c, offset_kind);
+                        if c == '\n' {
+                            lines.push((offset, String::new()));
+                            in_indent = true;
+                        } else if in_indent && (c == ' ' || c == '\t') {
+                            lines.last_mut().unwrap().1.push(c);
+                        } else {
+                            in_indent = false;
+                        }
+                    }
+                }
+                _ => {
+                    offset += 1;
+                    in_indent = false;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Returns lines overlapping with a given range, starting from the last one, so that they can
+    /// be edited without shifting offsets of the remaining ones.
+    fn selected_lines(
+        text: &Text,
+        txn: &mut YTransaction,
+        start_index: u32,
+        end_index: u32,
+    ) -> PyResult<Vec<(u32, String)>> {
+        if start_index > end_index || end_index > text.len() {
+            return Err(PyIndexError::new_err(format!(
+                "Range {start_index}..{end_index} is outside of a text with length {}.",
+                text.len()
+            )));
+        }
+        // a selection ending right at the beginning of a line doesn't include that line
+        let last_index = end_index.saturating_sub(1).max(start_index);
+        let mut lines = Self::line_indents(text, txn);
+        lines.retain(|(line_start, _)| *line_start <= last_index);
+        let first = lines
+            .iter()
+            .rposition(|(line_start, _)| *line_start <= start_index)
+            .unwrap_or(0);
+        Ok(lines.drain(first..).rev().collect())
+    }
+
+    fn parse_delta_op(op: &PyAny) -> PyResult<DeltaOp> {
+        let malformed = |reason: &str| {
+            PyTypeError::new_err(format!("Malformed delta operation {}: {}", op, reason))
+        };
+        let op: &PyDict = op
+            .downcast()
+            .map_err(|_| malformed("operations should be dictionaries"))?;
+        let attrs = match op.get_item("attributes") {
+            Some(attrs) if !attrs.is_none() => {
+                let attrs: HashMap<String, PyObject> = attrs
+                    .extract()
+                    .map_err(|_| malformed("attributes should be a dictionary with string keys"))?;
+                Some(Self::parse_attrs(attrs)?)
+            }
+            _ => None,
+        };
+        let len = |value: &PyAny| {
+            value
+                .extract::<u32>()
+                .map_err(|_| malformed("lengths should be non-negative integers"))
+        };
+        let mut ops = ["insert", "retain", "delete"]
+            .iter()
+            .filter_map(|&key| op.get_item(key).map(|value| (key, value)));
+        let result = match (ops.next(), ops.next()) {
+            (Some(("insert", value)), None) => match value.extract::<String>() {
+                Ok(chunk) => DeltaOp::InsertText(chunk, attrs.unwrap_or_default()),
+                Err(_) => {
+                    let embed: CompatiblePyType = value.extract()?;
+                    DeltaOp::InsertEmbed(embed.try_into()?, attrs.unwrap_or_default())
+                }
+            },
+            (Some(("retain", value)), None) => DeltaOp::Retain(len(value)?, attrs),
+            (Some((_, value)), None) if attrs.is_none() => DeltaOp::Delete(len(value)?),
+            (Some(_), None) => return Err(malformed("delete operations cannot have attributes")),
+            _ => {
+                return Err(malformed(
+                    "expected exactly one of 'insert', 'retain' or 'delete' keys",
+                ))
+            }
+        };
+        Ok(result)
+    }
+
+    /// Ensures that none of delta `ops` reaches past the end of a text with a given length, by
+    /// simulating changes of its length before any of them is applied.
+    fn check_delta(ops: &[DeltaOp], len: u32, offset_kind: OffsetKind) -> PyResult<()> {
+        let mut len = len as u64;
+        let mut index = 0_u64;
+        for op in ops {
+            match op {
+                DeltaOp::Retain(n, _) | DeltaOp::Delete(n) => {
+                    if index + *n as u64 > len {
+                        return Err(PyIndexError::new_err(
+                            "Delta operation exceeds the length of the text",
+                        ));
+                    }
+                    if let DeltaOp::Retain(_, _) = op {
+                        index += *n as u64;
+                    } else {
+                        len -= *n as u64;
+                    }
+                }
+                DeltaOp::InsertText(chunk, _) => {
+                    let n: u64 = chunk.chars().map(|c| char_len(c, offset_kind) as u64).sum();
+                    index += n;
+                    len += n;
+                }
+                DeltaOp::InsertEmbed(_, _) => {
+                    index += 1;
+                    len += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn parse_attrs(attrs: HashMap<String, PyObject>) -> PyResult<Attrs> {
         Python::with_gil(|py| {
             attrs
                 .into_iter()
@@ -260,6 +1691,14 @@ impl YText {
     }
 }
 
+/// A single operation of a delta passed to `YText.apply_delta`.
+enum DeltaOp {
+    InsertText(String, Attrs),
+    InsertEmbed(Any, Attrs),
+    Retain(u32, Option<Attrs>),
+    Delete(u32),
+}
+
 /// Event generated by `YYText.observe` method. Emitted during transaction commit phase.
 #[pyclass(unsendable)]
 pub struct YTextEvent {
@@ -305,12 +1744,21 @@ impl YTextEvent {
         }
     }
 
-    /// Returns an array of keys and indexes creating a path from root type down to current instance
-    /// of shared type (accessible via `target` getter).
+    /// Returns an array of keys and indexes creating a path from the observed shared type (a root
+    /// type for `YDoc.observe_deep`) down to current instance of shared type (accessible via
+    /// `target` getter).
     pub fn path(&self) -> PyObject {
         Python::with_gil(|py| self.inner().path().into_py(py))
     }
 
+    /// Returns a dictionary of clients, whose clocks have advanced during the transaction which
+    /// produced this event, mapped to a `(before, after)` pair of their clocks, like
+    /// `AfterTransactionEvent.clock_ranges` does.
+    pub fn clock_ranges(&self) -> PyObject {
+        let txn = self.txn();
+        clock_ranges_into_py(&txn.before_state, &txn.after_state)
+    }
+
     /// Returns a list of text changes made over corresponding `YText` collection within
     /// bounds of current transaction. These changes follow a format:
     ///
@@ -336,6 +1784,21 @@ impl YTextEvent {
         }
     }
 
+    /// Returns only the `retain` operations of `delta` which carry formatting `attributes`, ie.
+    /// changes of formatting of a text which was already there. An event with no other kinds of
+    /// operations (no `insert` or `delete`) only restyles the text, without changing its contents.
+    #[getter]
+    pub fn attributes_changed(&self) -> PyObject {
+        Python::with_gil(|py| {
+            let delta = self.inner().delta(self.txn()).iter();
+            let ops: Vec<PyObject> = delta
+                .filter(|d| matches!(d, Delta::Retain(_, Some(_))))
+                .map(|d| d.clone().into_py(py))
+                .collect();
+            PyList::new(py, ops).into()
+        })
+    }
+
     fn __repr__(&mut self) -> String {
         let target = self.target();
         let delta = self.delta();
@@ -1,23 +1,37 @@
-use crate::{y_array::YArray, y_map::YMap, y_text::YText};
-use pyo3::exceptions::PyException;
-use pyo3::types::PyBytes;
+use crate::shared_types::raise_observer_error;
+use crate::y_doc::RootTypes;
+use crate::{y_array::YArray, y_doc::YDoc, y_map::YMap, y_text::YText};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::types::{PyBytes, PyDict};
 use pyo3::{create_exception, prelude::*};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
+use std::panic::{AssertUnwindSafe, UnwindSafe};
+use std::rc::Rc;
+use yrs::types::Value;
 use yrs::updates::decoder::Decode;
 use yrs::updates::encoder::{Encode, Encoder};
 use yrs::{
-    updates::{decoder::DecoderV1, encoder::EncoderV1},
-    StateVector, Transaction, Update,
+    updates::encoder::{EncoderV1, EncoderV2},
+    OffsetKind, StateVector, Transaction, Update, ID,
 };
 
 create_exception!(
     y_py,
     EncodingException,
-    PyException,
+    PyValueError,
     "Occurs due to issues in the encoding/decoding process of y_py updates."
 );
 
+create_exception!(
+    y_py,
+    ReadOnlyTransactionException,
+    PyException,
+    "Occurs when a transaction started with `YDoc.begin_read_transaction` is used to modify a document."
+);
+
 /// A transaction that serves as a proxy to document block store. Ypy shared data types execute
 /// their operations in a context of a given transaction. Each document can have only one active
 /// transaction at the time - subsequent attempts will cause exception to be thrown.
@@ -34,10 +48,28 @@ create_exception!(
 /// with doc.begin_transaction() as txn:
 ///     text.insert(txn, 0, 'hello world')
 /// ```
-#[pyclass(unsendable)]
+#[pyclass(unsendable, weakref)]
 pub struct YTransaction {
     pub inner: Transaction,
     pub cached_before_state: Option<PyObject>,
+    /// Unit in which indexes and lengths of text operations are measured in this document.
+    pub offset_kind: OffsetKind,
+    /// Read-only transactions, started with `YDoc.begin_read_transaction`, reject all changes.
+    pub read_only: bool,
+    /// Receives notifications about integration of updates applied within this transaction.
+    pub debug_logger: Option<PyObject>,
+    origin: Option<PyObject>,
+    /// Document-wide origin slot read by after-transaction observers, cleared once this
+    /// transaction commits.
+    origin_cell: Option<Rc<RefCell<Option<PyObject>>>>,
+    /// Document used to start a new transaction once this one gets committed with `commit`.
+    doc: Option<Py<YDoc>>,
+    /// Set when `inner` was started by `commit`, rather than together with this transaction.
+    restarted: bool,
+    /// Document-wide flag marking that a transaction is open, cleared once this one is finished.
+    open_flag: Option<Rc<Cell<bool>>>,
+    /// Callbacks of `YDoc.observe_deep`, attached to root types accessed through this transaction.
+    pub(crate) root_types: Option<Rc<RefCell<RootTypes>>>,
 }
 
 impl Deref for YTransaction {
@@ -54,12 +86,228 @@ impl DerefMut for YTransaction {
     }
 }
 
+/// Runs a `decode` function over an untrusted payload. yrs decoders may panic on malformed input
+/// instead of returning an error, so panics are reported as errors as well.
+pub(crate) fn try_decode<T>(
+    decode: impl FnOnce() -> Result<T, lib0::error::Error> + UnwindSafe,
+) -> Result<T, String> {
+    match std::panic::catch_unwind(decode) {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("malformed payload".to_string()),
+    }
+}
+
+/// Binary payload (like an update or a state vector) passed from Python. Objects supporting the
+/// buffer protocol (`bytes`, `bytearray`, `memoryview` etc.) are read in place, without copying
+/// them. Lists of integers are still accepted for compatibility, but have to be copied.
+pub enum Binary<'a> {
+    Bytes(&'a [u8]),
+    Buffer(PyBuffer<u8>),
+    Owned(Vec<u8>),
+}
+
+impl<'a> FromPyObject<'a> for Binary<'a> {
+    fn extract(obj: &'a PyAny) -> PyResult<Self> {
+        if let Ok(bytes) = obj.downcast::<PyBytes>() {
+            return Ok(Binary::Bytes(bytes.as_bytes()));
+        }
+        match PyBuffer::<u8>::get(obj) {
+            Ok(buffer) if buffer.is_c_contiguous() => Ok(Binary::Buffer(buffer)),
+            Ok(buffer) => Ok(Binary::Owned(buffer.to_vec(obj.py())?)),
+            Err(_) => Ok(Binary::Owned(obj.extract()?)),
+        }
+    }
+}
+
+impl Deref for Binary<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Binary::Bytes(bytes) => bytes,
+            // Safety: the buffer is contiguous and its memory stays valid for as long as the
+            // buffer is held. Payloads are only read while holding the GIL, so Python code can't
+            // modify them in the meantime.
+            Binary::Buffer(buffer) => unsafe {
+                std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, buffer.len_bytes())
+            },
+            Binary::Owned(vec) => vec,
+        }
+    }
+}
+
+fn decode_v1<T: Decode>(data: &[u8]) -> PyResult<T> {
+    try_decode(|| T::decode_v1(data)).map_err(EncodingException::new_err)
+}
+
+fn decode_v2<T: Decode>(data: &[u8]) -> PyResult<T> {
+    try_decode(|| T::decode_v2(data)).map_err(EncodingException::new_err)
+}
+
 impl YTransaction {
-    pub fn new(txn: Transaction) -> Self {
+    pub fn new(txn: Transaction, offset_kind: OffsetKind, debug_logger: Option<PyObject>) -> Self {
         YTransaction {
             inner: txn,
             cached_before_state: None,
+            offset_kind,
+            read_only: false,
+            debug_logger,
+            origin: None,
+            origin_cell: None,
+            doc: None,
+            restarted: false,
+            open_flag: None,
+            root_types: None,
+        }
+    }
+
+    /// Marks the document as having an open transaction through a shared `flag`, until this one
+    /// is finished.
+    pub(crate) fn exclusive(mut self, flag: Rc<Cell<bool>>) -> Self {
+        flag.set(true);
+        self.open_flag = Some(flag);
+        self
+    }
+
+    /// Ties this transaction to a given `doc`, allowing it to continue after `commit`.
+    pub(crate) fn with_doc(mut self, doc: Py<YDoc>) -> Self {
+        self.doc = Some(doc);
+        self
+    }
+
+    /// Commits changes made so far, unless this transaction was already committed and nothing has
+    /// changed since. Unlike `commit`, it doesn't start a new transaction afterwards.
+    pub(crate) fn finish(&mut self) {
+        if self.read_only {
+            // yrs commits every transaction against the state it started from, which would publish
+            // changes committed by other transactions since then once again
+            self.inner.before_state = self.inner.state_vector();
+        }
+        if !self.restarted || self.has_changes() {
+            self.inner.commit();
+        }
+        if let Some(cell) = self.origin_cell.take() {
+            cell.borrow_mut().take();
+        }
+        if let Some(flag) = self.open_flag.take() {
+            flag.set(false);
+        }
+    }
+
+    fn has_changes(&self) -> bool {
+        !self.inner.delete_set.is_empty() || self.inner.before_state != self.inner.state_vector()
+    }
+
+    /// Fails when this transaction is read-only, which every change to a document checks first.
+    pub(crate) fn ensure_writable(&self) -> PyResult<()> {
+        if self.read_only {
+            Err(ReadOnlyTransactionException::new_err(
+                "Cannot modify a document using a read-only transaction.",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Integrates a decoded `update`, reporting it to the debug logger if one is set.
+    pub(crate) fn apply_decoded(&mut self, update: Update) -> PyResult<()> {
+        self.ensure_writable()?;
+        if let Some(logger) = self.debug_logger.clone() {
+            self.apply_update_logged(update, logger)
+        } else {
+            self.integrate(update)
+        }
+    }
+
+    /// Integrates a decoded `update`. Updates which decode fine may still describe blocks that
+    /// yrs panics on, which is reported as `EncodingException` as well.
+    fn integrate(&mut self, update: Update) -> PyResult<()> {
+        let txn = &mut self.inner;
+        try_decode(AssertUnwindSafe(|| {
+            txn.apply_update(update);
+            Ok(())
+        }))
+        .map_err(EncodingException::new_err)
+    }
+
+    /// Records a root type with a given `name` in its document, attaching `YDoc.observe_deep`
+    /// callbacks to it.
+    pub(crate) fn register_root(&self, name: &str, value: Value) {
+        if let Some(root_types) = &self.root_types {
+            RootTypes::register(root_types, name, value);
+        }
+    }
+
+    /// Marks this transaction as originating from a given `origin`, which is exposed to
+    /// after-transaction observers through a shared `cell` until the transaction is committed.
+    pub(crate) fn set_origin(&mut self, origin: PyObject, cell: Rc<RefCell<Option<PyObject>>>) {
+        *cell.borrow_mut() = Some(origin.clone());
+        self.origin = Some(origin);
+        self.origin_cell = Some(cell);
+    }
+}
+
+impl Drop for YTransaction {
+    fn drop(&mut self) {
+        self.finish()
+    }
+}
+
+impl YTransaction {
+    /// Applies an `update` while reporting ranges of blocks it integrated, deleted or left pending
+    /// to a given `logger`, as described by `YDoc.set_debug_logger`.
+    fn apply_update_logged(&mut self, update: Update, logger: PyObject) -> PyResult<()> {
+        let expected = update.state_vector();
+        let before = self.state_vector();
+        let deleted_before = self.delete_set.clone();
+        self.integrate(update)?;
+        let after = self.state_vector();
+
+        let mut entries: Vec<(&str, u64, u32, u32)> = Vec::new();
+        for (&client, &clock) in after.iter() {
+            let start = before.get(&client);
+            if clock > start {
+                entries.push(("integrate", client, start, clock - start));
+            }
+        }
+        for (&client, ranges) in self.delete_set.iter() {
+            for range in ranges.iter() {
+                // only report ranges which were not deleted before
+                let mut start = None;
+                for clock in range.start..=range.end {
+                    let new =
+                        clock < range.end && !deleted_before.is_deleted(&ID::new(client, clock));
+                    match (new, start) {
+                        (true, None) => start = Some(clock),
+                        (false, Some(s)) => {
+                            entries.push(("delete", client, s, clock - s));
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+            }
         }
+        for (&client, &clock) in expected.iter() {
+            let integrated = after.get(&client);
+            if clock > integrated {
+                entries.push(("pending", client, integrated, clock - integrated));
+            }
+        }
+        entries.sort_by_key(|&(action, client, clock, _)| (client, action, clock));
+
+        Python::with_gil(|py| {
+            for (action, client, clock, length) in entries {
+                let entry = PyDict::new(py);
+                entry.set_item("action", action)?;
+                entry.set_item("client", client)?;
+                entry.set_item("clock", clock)?;
+                entry.set_item("length", length)?;
+                logger.call1(py, (entry,))?;
+            }
+            Ok(())
+        })
     }
 }
 
@@ -86,7 +334,9 @@ impl YTransaction {
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YText` instance.
     pub fn get_text(&mut self, name: &str) -> YText {
-        self.deref_mut().get_text(name).into()
+        let text = self.deref_mut().get_text(name);
+        self.register_root(name, Value::YText(text.clone()));
+        text.into()
     }
 
     /// Returns a `YArray` shared data type, that's accessible for subsequent accesses using given
@@ -97,7 +347,9 @@ impl YTransaction {
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YArray` instance.
     pub fn get_array(&mut self, name: &str) -> YArray {
-        self.deref_mut().get_array(name).into()
+        let array = self.deref_mut().get_array(name);
+        self.register_root(name, Value::YArray(array.clone()));
+        array.into()
     }
 
     /// Returns a `YMap` shared data type, that's accessible for subsequent accesses using given
@@ -108,14 +360,81 @@ impl YTransaction {
     /// If there was an instance with this name, but it was of different type, it will be projected
     /// onto `YMap` instance.
     pub fn get_map(&mut self, name: &str) -> YMap {
-        self.deref_mut().get_map(name).into()
+        let map = self.deref_mut().get_map(name);
+        self.register_root(name, Value::YMap(map.clone()));
+        map.into()
     }
 
     /// Triggers a post-update series of operations without `free`ing the transaction. This includes
     /// compaction and optimization of internal representation of updates, triggering events etc.
     /// Ypy transactions are auto-committed when they are `free`d.
-    pub fn commit(&mut self) {
-        self.deref_mut().commit()
+    ///
+    /// The transaction can still be used afterwards: subsequent operations are made as a part of
+    /// a new transaction with the same origin, committed on the next call to `commit` or when this
+    /// one is released. Committing again without making any changes in between does nothing.
+    pub fn commit(&mut self) -> PyResult<()> {
+        if self.restarted && !self.has_changes() {
+            return Ok(());
+        }
+        let origin_cell = self.origin_cell.clone();
+        let open_flag = self.open_flag.clone();
+        self.finish();
+        if let Some(doc) = &self.doc {
+            self.inner = Python::with_gil(|py| -> PyResult<Transaction> {
+                Ok(doc.try_borrow(py)?.inner.transact())
+            })?;
+            self.restarted = true;
+            self.cached_before_state = None;
+            if let (Some(origin), Some(cell)) = (self.origin.clone(), origin_cell) {
+                self.set_origin(origin, cell);
+            }
+            if let Some(flag) = open_flag {
+                flag.set(true);
+                self.open_flag = Some(flag);
+            }
+        }
+        Python::with_gil(raise_observer_error)
+    }
+
+    /// Origin given to `YDoc.begin_transaction`, `YDoc.transact` or `apply_update` when this
+    /// transaction was started, or `None` otherwise.
+    #[getter]
+    pub fn origin(&self) -> Option<PyObject> {
+        self.origin.clone()
+    }
+
+    /// True if this transaction was started with `YDoc.begin_read_transaction`, which means that it
+    /// can only be used to read the document.
+    #[getter(read_only)]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns a number of changes made within this transaction (or since its last `commit`),
+    /// including changes applied from remote updates. Every inserted or deleted element counts as
+    /// one, with text measured in UTF-16 code units, the same way as in update clocks. It's cheap
+    /// to call, so it can be used to split transactions growing over an application-defined limit.
+    pub fn op_count(&self) -> u32 {
+        let before = &self.inner.before_state;
+        let inserted: u32 = self
+            .state_vector()
+            .iter()
+            .map(|(client, &clock)| clock - before.get(client))
+            .sum();
+        let deleted: u32 = self
+            .delete_set
+            .iter()
+            .flat_map(|(_, ranges)| ranges.iter().map(|range| range.end - range.start))
+            .sum();
+        inserted + deleted
+    }
+
+    /// Returns a size in bytes of a lib0 v1 encoded update with changes made within this transaction
+    /// so far. Blocks created by consecutive edits are merged once the transaction is committed, so
+    /// the update it produces may end up smaller. Unlike `op_count`, it has to encode the update,
+    /// so its cost grows with the number of changes.
+    pub fn update_size(&self) -> usize {
+        self.encode_update_v1().len()
     }
 
     /// Encodes a state vector of a given transaction document into its binary representation using
@@ -177,11 +496,10 @@ impl YTransaction {
     ///     del local_txn
     ///     del remote_txn
     /// ```
-    pub fn diff_v1(&self, vector: Option<Vec<u8>>) -> PyResult<PyObject> {
+    pub fn diff_v1(&self, vector: Option<Binary>) -> PyResult<PyObject> {
         let mut encoder = EncoderV1::new();
         let sv = if let Some(vector) = vector {
-            StateVector::decode_v1(vector.to_vec().as_slice())
-                .map_err(|e| EncodingException::new_err(e.to_string()))?
+            decode_v1(&vector)?
         } else {
             StateVector::default()
         };
@@ -214,13 +532,38 @@ impl YTransaction {
     ///     del local_txn
     ///     del remote_txn
     /// ```
-    pub fn apply_v1(&mut self, diff: Vec<u8>) -> PyResult<()> {
-        let diff: Vec<u8> = diff.to_vec();
-        let mut decoder = DecoderV1::from(diff.as_slice());
-        let update =
-            Update::decode(&mut decoder).map_err(|e| EncodingException::new_err(e.to_string()))?;
-        self.apply_update(update);
-        Ok(())
+    pub fn apply_v1(&mut self, diff: Binary) -> PyResult<()> {
+        let update = decode_v1(&diff)?;
+        self.apply_decoded(update)
+    }
+
+    /// Encodes a state vector of a given transaction document into its binary representation using
+    /// lib0 v2 encoding. Counterpart of `state_vector_v1`.
+    pub fn state_vector_v2(&self) -> PyObject {
+        let payload = self.state_vector().encode_v2();
+        Python::with_gil(|py| PyBytes::new(py, &payload).into())
+    }
+
+    /// Encodes all updates that have happened since a given version `vector` into a compact delta
+    /// representation using lib0 v2 encoding. A `vector` is expected to be produced by
+    /// `state_vector_v2`. Counterpart of `diff_v1`.
+    pub fn diff_v2(&self, vector: Option<Binary>) -> PyResult<PyObject> {
+        let mut encoder = EncoderV2::new();
+        let sv = if let Some(vector) = vector {
+            decode_v2(&vector)?
+        } else {
+            StateVector::default()
+        };
+        self.encode_diff(&sv, &mut encoder);
+        let bytes: PyObject = Python::with_gil(|py| PyBytes::new(py, &encoder.to_vec()).into());
+        Ok(bytes)
+    }
+
+    /// Applies delta update generated by the remote document replica to a current transaction's
+    /// document. This method assumes that a payload maintains lib0 v2 encoding format.
+    pub fn apply_v2(&mut self, diff: Binary) -> PyResult<()> {
+        let update = decode_v2(&diff)?;
+        self.apply_decoded(update)
     }
 
     /// Allows YTransaction to be used with a Python context block.
@@ -242,6 +585,10 @@ impl YTransaction {
     /// Allows YTransaction to be used with a Python context block.
     /// Commits the results when the `with` context closes.
     ///
+    /// Transactions can't be rolled back, so when the block raises an exception, changes made
+    /// before are committed as well (and propagated to observers) before the exception is
+    /// re-raised. Writes that need to be atomic should be prepared and validated before the block.
+    ///
     /// Example
     /// ```python
     /// from y_py import YDoc
@@ -259,8 +606,9 @@ impl YTransaction {
         _exception_value: Option<&'p PyAny>,
         _traceback: Option<&'p PyAny>,
     ) -> PyResult<bool> {
-        self.commit();
+        self.finish();
         drop(self);
+        Python::with_gil(raise_observer_error)?;
         Ok(exception_type.is_none())
     }
 }
@@ -1,8 +1,12 @@
 use crate::shared_types::SubId;
+use pyo3::exceptions::PyIndexError;
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
+use std::collections::HashMap;
 use std::mem::ManuallyDrop;
 use std::ops::Deref;
+use std::rc::Rc;
+use yrs::types::text::YChange;
 use yrs::types::xml::{Attributes, TreeWalker, XmlEvent, XmlTextEvent};
 use yrs::types::{DeepObservable, EntryChange, Path, PathSegment};
 use yrs::SubscriptionId;
@@ -11,8 +15,10 @@ use yrs::Xml;
 use yrs::XmlElement;
 use yrs::XmlText;
 
-use crate::shared_types::{DeepSubscription, ShallowSubscription};
-use crate::type_conversions::{events_into_py, ToPython};
+use crate::shared_types::{report_observer_error, DeepSubscription, Observed, ShallowSubscription};
+use crate::type_conversions::{attrs_into_py, events_into_py, ToPython};
+use crate::y_doc::clock_ranges_into_py;
+use crate::y_text::{extract_chunk, YText};
 use crate::y_transaction::YTransaction;
 
 /// XML element data type. It represents an XML node, which can contain key-value attributes
@@ -44,39 +50,50 @@ impl YXmlElement {
         self.0.len() as usize
     }
 
-    /// Inserts a new instance of `YXmlElement` as a child of this XML node and returns it.
+    /// Inserts a new instance of `YXmlElement` as a child of this XML node and returns it. Raises
+    /// `IndexError` when `index` is past the last child.
     pub fn insert_xml_element(
         &self,
         txn: &mut YTransaction,
         index: u32,
         name: &str,
-    ) -> YXmlElement {
-        YXmlElement(self.0.insert_elem(txn, index, name))
+    ) -> PyResult<YXmlElement> {
+        txn.ensure_writable()?;
+        self.check_range(index, 0)?;
+        Ok(YXmlElement(self.0.insert_elem(txn, index, name)))
     }
 
-    /// Inserts a new instance of `YXmlText` as a child of this XML node and returns it.
-    pub fn insert_xml_text(&self, txn: &mut YTransaction, index: u32) -> YXmlText {
-        YXmlText(self.0.insert_text(txn, index))
+    /// Inserts a new instance of `YXmlText` as a child of this XML node and returns it. Raises
+    /// `IndexError` when `index` is past the last child.
+    pub fn insert_xml_text(&self, txn: &mut YTransaction, index: u32) -> PyResult<YXmlText> {
+        txn.ensure_writable()?;
+        self.check_range(index, 0)?;
+        Ok(YXmlText(self.0.insert_text(txn, index)))
     }
 
     /// Removes a range of children XML nodes from this `YXmlElement` instance,
-    /// starting at given `index`.
-    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) {
-        self.0.remove_range(txn, index, length)
+    /// starting at given `index`. Raises `IndexError` when the range goes past the last child.
+    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) -> PyResult<()> {
+        txn.ensure_writable()?;
+        self.check_range(index, length)?;
+        self.0.remove_range(txn, index, length);
+        Ok(())
     }
 
     /// Appends a new instance of `YXmlElement` as the last child of this XML node and returns it.
-    pub fn push_xml_element(&self, txn: &mut YTransaction, name: &str) -> YXmlElement {
-        YXmlElement(self.0.push_elem_back(txn, name))
+    pub fn push_xml_element(&self, txn: &mut YTransaction, name: &str) -> PyResult<YXmlElement> {
+        txn.ensure_writable()?;
+        Ok(YXmlElement(self.0.push_elem_back(txn, name)))
     }
 
     /// Appends a new instance of `YXmlText` as the last child of this XML node and returns it.
-    pub fn push_xml_text(&self, txn: &mut YTransaction) -> YXmlText {
-        YXmlText(self.0.push_text_back(txn))
+    pub fn push_xml_text(&self, txn: &mut YTransaction) -> PyResult<YXmlText> {
+        txn.ensure_writable()?;
+        Ok(YXmlText(self.0.push_text_back(txn)))
     }
 
     /// Returns a first child of this XML node.
-    /// It can be either `YXmlElement`, `YXmlText` or `undefined` if current node has not children.
+    /// It can be either `YXmlElement`, `YXmlText` or `None` if current node has no children.
     #[getter]
     pub fn first_child(&self) -> PyObject {
         Python::with_gil(|py| {
@@ -87,7 +104,7 @@ impl YXmlElement {
     }
 
     /// Returns a next XML sibling node of this XMl node.
-    /// It can be either `YXmlElement`, `YXmlText` or `undefined` if current node is a last child of
+    /// It can be either `YXmlElement`, `YXmlText` or `None` if current node is a last child of
     /// parent XML node.
     #[getter]
     pub fn next_sibling(&self) -> PyObject {
@@ -99,7 +116,7 @@ impl YXmlElement {
     }
 
     /// Returns a previous XML sibling node of this XMl node.
-    /// It can be either `YXmlElement`, `YXmlText` or `undefined` if current node is a first child
+    /// It can be either `YXmlElement`, `YXmlText` or `None` if current node is a first child
     /// of parent XML node.
     #[getter]
     pub fn prev_sibling(&self) -> PyObject {
@@ -110,7 +127,7 @@ impl YXmlElement {
         })
     }
 
-    /// Returns a parent `YXmlElement` node or `undefined` if current node has no parent assigned.
+    /// Returns a parent `YXmlElement` node or `None` if current node has no parent assigned.
     #[getter]
     pub fn parent(&self) -> Option<YXmlElement> {
         self.0.parent().map(YXmlElement)
@@ -126,24 +143,28 @@ impl YXmlElement {
     }
 
     /// Sets a `name` and `value` as new attribute for this XML node. If an attribute with the same
-    /// `name` already existed on that node, its value with be overridden with a provided one.
-    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) {
-        self.0.insert_attribute(txn, name, value)
+    /// `name` already existed on that node, its value will be overridden with a provided one.
+    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) -> PyResult<()> {
+        txn.ensure_writable()?;
+        self.0.insert_attribute(txn, name, value);
+        Ok(())
     }
 
     /// Returns a value of an attribute given its `name`. If no attribute with such name existed,
-    /// `null` will be returned.
+    /// `None` will be returned.
     pub fn get_attribute(&self, name: &str) -> Option<String> {
         self.0.get_attribute(name)
     }
 
     /// Removes an attribute from this XML node, given its `name`.
-    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) {
+    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) -> PyResult<()> {
+        txn.ensure_writable()?;
         self.0.remove_attribute(txn, &name);
+        Ok(())
     }
 
     /// Returns an iterator that enables to traverse over all attributes of this XML node in
-    /// unspecified order.
+    /// unspecified order, as `(name, value)` pairs.
     pub fn attributes(&self) -> YXmlAttributes {
         unsafe {
             let this: *const XmlElement = &self.0;
@@ -153,6 +174,12 @@ impl YXmlElement {
         }
     }
 
+    /// Returns an iterator over direct children of this XML node, in document order. Unlike
+    /// `tree_walker`, it doesn't descend into nested XML elements.
+    pub fn children(&self, _txn: &YTransaction) -> YXmlChildren {
+        YXmlChildren(self.0.first_child())
+    }
+
     /// Returns an iterator that enables a deep traversal of this XML node - starting from first
     /// child over this XML node successors using depth-first strategy.
     pub fn tree_walker(&self) -> YXmlTreeWalker {
@@ -168,45 +195,62 @@ impl YXmlElement {
     /// batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, can be used to unsubscribe the observer.
     pub fn observe(&mut self, f: PyObject) -> ShallowSubscription {
+        let f = Rc::new(f);
+        let callback = Rc::downgrade(&f);
         let sub_id = self
             .0
             .observe(move |txn, e| {
                 Python::with_gil(|py| {
                     let event = YXmlEvent::new(e, txn);
                     if let Err(err) = f.call1(py, (event,)) {
-                        err.restore(py)
+                        report_observer_error(py, err)
                     }
                 })
             })
             .into();
 
-        ShallowSubscription(sub_id)
+        ShallowSubscription::new(sub_id, Observed::XmlElement(self.0.clone()), callback)
     }
 
     /// Subscribes to all operations happening over this instance of `YXmlElement` and all of its children.
     /// All changes are batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, can be used to unsubscribe the observer.
     pub fn observe_deep(&mut self, f: PyObject) -> DeepSubscription {
+        let f = Rc::new(f);
+        let callback = Rc::downgrade(&f);
         let sub_id = self
             .0
             .observe_deep(move |txn, events| {
                 Python::with_gil(|py| {
                     let events = events_into_py(txn, events);
                     if let Err(err) = f.call1(py, (events,)) {
-                        err.restore(py)
+                        report_observer_error(py, err)
                     }
                 })
             })
             .into();
-        DeepSubscription(sub_id)
+        DeepSubscription::new(sub_id, Observed::XmlElement(self.0.clone()), callback)
     }
 
     /// Cancels the observer callback associated with the `subscripton_id`.
     pub fn unobserve(&mut self, subscription_id: SubId) {
-        match subscription_id {
-            SubId::Shallow(ShallowSubscription(id)) => self.0.unobserve(id),
-            SubId::Deep(DeepSubscription(id)) => self.0.unobserve_deep(id),
+        subscription_id.unobserve()
+    }
+}
+
+impl YXmlElement {
+    /// Ensures that a range of `length` children starting at a given `index` fits within this node.
+    fn check_range(&self, index: u32, length: u32) -> PyResult<()> {
+        let len = self.0.len();
+        let end = index as u64 + length as u64;
+        if end > len as u64 {
+            return Err(PyIndexError::new_err(if length <= 1 {
+                format!("Index {index} is out of range of an element with {len} children.")
+            } else {
+                format!("Range {index}..{end} is out of range of an element with {len} children.")
+            }));
         }
+        Ok(())
     }
 }
 
@@ -238,23 +282,80 @@ impl YXmlText {
     }
 
     /// Inserts a given `chunk` of text into this `YXmlText` instance, starting at a given `index`.
-    pub fn insert(&self, txn: &mut YTransaction, index: i32, chunk: &str) {
-        self.0.insert(txn, index as u32, chunk)
+    /// Optional `attributes` (eg. marks of a ProseMirror text node) are used to format the
+    /// inserted text.
+    pub fn insert(
+        &self,
+        txn: &mut YTransaction,
+        index: u32,
+        chunk: &PyAny,
+        attributes: Option<HashMap<String, PyObject>>,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let chunk = extract_chunk(chunk)?;
+        self.check_range(index, 0)?;
+        match attributes {
+            Some(attributes) => {
+                let attrs = YText::parse_attrs(attributes)?;
+                self.0.insert_with_attributes(txn, index, chunk, attrs)
+            }
+            None => self.0.insert(txn, index, chunk),
+        }
+        Ok(())
     }
 
     /// Appends a given `chunk` of text at the end of `YXmlText` instance.
-    pub fn push(&self, txn: &mut YTransaction, chunk: &str) {
-        self.0.push(txn, chunk)
+    pub fn push(&self, txn: &mut YTransaction, chunk: &PyAny) -> PyResult<()> {
+        txn.ensure_writable()?;
+        let chunk = extract_chunk(chunk)?;
+        self.0.push(txn, chunk);
+        Ok(())
     }
 
     /// Deletes a specified range of of characters, starting at a given `index`.
     /// Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
-    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) {
-        self.0.remove_range(txn, index, length)
+    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) -> PyResult<()> {
+        txn.ensure_writable()?;
+        self.check_range(index, length)?;
+        self.0.remove_range(txn, index, length);
+        Ok(())
+    }
+
+    /// Wraps an existing piece of text within a range described by `index`-`length` parameters
+    /// with formatting blocks containing provided `attributes` metadata.
+    pub fn format(
+        &self,
+        txn: &mut YTransaction,
+        index: u32,
+        length: u32,
+        attributes: HashMap<String, PyObject>,
+    ) -> PyResult<()> {
+        txn.ensure_writable()?;
+        self.check_range(index, length)?;
+        let attrs = YText::parse_attrs(attributes)?;
+        self.0.format(txn, index, length, attrs);
+        Ok(())
+    }
+
+    /// Returns contents of this `YXmlText` as a list of Quill-style delta operations, in the same
+    /// shape as `YText.to_delta`.
+    pub fn to_delta(&self, txn: &mut YTransaction) -> PyObject {
+        Python::with_gil(|py| {
+            let ops = PyList::empty(py);
+            for chunk in self.0.diff(txn, YChange::identity) {
+                let op = PyDict::new(py);
+                op.set_item("insert", chunk.insert.into_py(py)).unwrap();
+                if let Some(attrs) = chunk.attributes {
+                    op.set_item("attributes", attrs_into_py(&attrs)).unwrap();
+                }
+                ops.append(op).unwrap();
+            }
+            ops.into()
+        })
     }
 
     /// Returns a next XML sibling node of this XMl node.
-    /// It can be either `YXmlElement`, `YXmlText` or `undefined` if current node is a last child of
+    /// It can be either `YXmlElement`, `YXmlText` or `None` if current node is a last child of
     /// parent XML node.
     #[getter]
     pub fn next_sibling(&self) -> PyObject {
@@ -266,7 +367,7 @@ impl YXmlText {
     }
 
     /// Returns a previous XML sibling node of this XMl node.
-    /// It can be either `YXmlElement`, `YXmlText` or `undefined` if current node is a first child
+    /// It can be either `YXmlElement`, `YXmlText` or `None` if current node is a first child
     /// of parent XML node.
     #[getter]
     pub fn prev_sibling(&self) -> PyObject {
@@ -277,7 +378,7 @@ impl YXmlText {
         })
     }
 
-    /// Returns a parent `YXmlElement` node or `undefined` if current node has no parent assigned.
+    /// Returns a parent `YXmlElement` node or `None` if current node has no parent assigned.
     #[getter]
     pub fn parent(&self) -> PyObject {
         Python::with_gil(|py| {
@@ -297,24 +398,28 @@ impl YXmlText {
     }
 
     /// Sets a `name` and `value` as new attribute for this XML node. If an attribute with the same
-    /// `name` already existed on that node, its value with be overridden with a provided one.
-    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) {
+    /// `name` already existed on that node, its value will be overridden with a provided one.
+    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) -> PyResult<()> {
+        txn.ensure_writable()?;
         self.0.insert_attribute(txn, name, value);
+        Ok(())
     }
 
     /// Returns a value of an attribute given its `name`. If no attribute with such name existed,
-    /// `null` will be returned.
+    /// `None` will be returned.
     pub fn get_attribute(&self, name: &str) -> Option<String> {
         self.0.get_attribute(name)
     }
 
     /// Removes an attribute from this XML node, given its `name`.
-    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) {
+    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) -> PyResult<()> {
+        txn.ensure_writable()?;
         self.0.remove_attribute(txn, name);
+        Ok(())
     }
 
     /// Returns an iterator that enables to traverse over all attributes of this XML node in
-    /// unspecified order.
+    /// unspecified order, as `(name, value)` pairs.
     pub fn attributes(&self) -> YXmlAttributes {
         unsafe {
             let this: *const XmlText = &self.0;
@@ -328,44 +433,62 @@ impl YXmlText {
     /// batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, which can be used to unsubscribe the callback function.
     pub fn observe(&mut self, f: PyObject) -> ShallowSubscription {
+        let f = Rc::new(f);
+        let callback = Rc::downgrade(&f);
         let sub_id: SubscriptionId = self
             .0
             .observe(move |txn, e| {
                 Python::with_gil(|py| {
                     let e = YXmlTextEvent::new(e, txn);
                     if let Err(err) = f.call1(py, (e,)) {
-                        err.restore(py)
+                        report_observer_error(py, err)
                     }
                 })
             })
             .into();
-        ShallowSubscription(sub_id)
+        ShallowSubscription::new(sub_id, Observed::XmlText(self.0.clone()), callback)
     }
 
     /// Subscribes to all operations happening over this instance of `YXmlText` and its child elements. All changes are
     /// batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, which can be used to unsubscribe the callback function.
     pub fn observe_deep(&mut self, f: PyObject) -> DeepSubscription {
+        let f = Rc::new(f);
+        let callback = Rc::downgrade(&f);
         let sub_id: SubscriptionId = self
             .0
             .observe_deep(move |txn, events| {
                 Python::with_gil(|py| {
                     let e = events_into_py(txn, events);
                     if let Err(err) = f.call1(py, (e,)) {
-                        err.restore(py)
+                        report_observer_error(py, err)
                     }
                 })
             })
             .into();
-        DeepSubscription(sub_id)
+        DeepSubscription::new(sub_id, Observed::XmlText(self.0.clone()), callback)
     }
 
     /// Cancels the observer callback associated with the `subscripton_id`.
     pub fn unobserve(&mut self, subscription_id: SubId) {
-        match subscription_id {
-            SubId::Shallow(ShallowSubscription(id)) => self.0.unobserve(id),
-            SubId::Deep(DeepSubscription(id)) => self.0.unobserve_deep(id),
+        subscription_id.unobserve()
+    }
+}
+
+impl YXmlText {
+    /// Ensures that a range of `length` characters starting at a given `index` fits within this
+    /// text.
+    fn check_range(&self, index: u32, length: u32) -> PyResult<()> {
+        let len = self.0.len();
+        let end = index as u64 + length as u64;
+        if end > len as u64 {
+            return Err(PyIndexError::new_err(if length <= 1 {
+                format!("Index {index} is out of range of a text with length {len}.")
+            } else {
+                format!("Range {index}..{end} is out of range of a text with length {len}.")
+            }));
         }
+        Ok(())
     }
 }
 
@@ -388,6 +511,25 @@ impl YXmlAttributes {
     }
 }
 
+/// Iterator over direct children of a `YXmlElement`, which reads them one by one.
+#[pyclass(unsendable)]
+pub struct YXmlChildren(Option<Xml>);
+
+#[pymethods]
+impl YXmlChildren {
+    pub fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+    pub fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
+        let current = slf.0.take()?;
+        slf.0 = match &current {
+            Xml::Element(el) => el.next_sibling(),
+            Xml::Text(text) => text.next_sibling(),
+        };
+        Some(Python::with_gil(|py| current.into_py(py)))
+    }
+}
+
 #[pyclass(unsendable)]
 pub struct YXmlTreeWalker(ManuallyDrop<TreeWalker<'static>>);
 
@@ -467,12 +609,21 @@ impl YXmlEvent {
         format!("YXmlEvent(target={target}, delta={delta}, keys={keys}, path={path})")
     }
 
-    /// Returns an array of keys and indexes creating a path from root type down to current instance
-    /// of shared type (accessible via `target` getter).
+    /// Returns an array of keys and indexes creating a path from the observed shared type (a root
+    /// type for `YDoc.observe_deep`) down to current instance of shared type (accessible via
+    /// `target` getter).
     pub fn path(&self) -> PyObject {
         Python::with_gil(|py| self.inner().path().into_py(py))
     }
 
+    /// Returns a dictionary of clients, whose clocks have advanced during the transaction which
+    /// produced this event, mapped to a `(before, after)` pair of their clocks, like
+    /// `AfterTransactionEvent.clock_ranges` does.
+    pub fn clock_ranges(&self) -> PyObject {
+        let txn = self.txn();
+        clock_ranges_into_py(&txn.before_state, &txn.after_state)
+    }
+
     /// Returns all changes done upon map component of a current shared data type (which can be
     /// accessed via `target`) within a bounds of corresponding transaction `txn`. These
     /// changes are done in result of operations made on `YMap` data type or attribute changes of
@@ -572,14 +723,24 @@ impl YXmlTextEvent {
         let keys = self.keys();
         let path = self.path();
 
-        format!("YXmlEvent(target={target}, delta={delta}, keys={keys}, path={path})")
+        format!("YXmlTextEvent(target={target}, delta={delta}, keys={keys}, path={path})")
     }
 
-    /// Returns a current shared type instance, that current event changes refer to.
+    /// Returns an array of keys and indexes creating a path from the observed shared type (a root
+    /// type for `YDoc.observe_deep`) down to current instance of shared type (accessible via
+    /// `target` getter).
     pub fn path(&self) -> PyObject {
         Python::with_gil(|py| self.inner().path().into_py(py))
     }
 
+    /// Returns a dictionary of clients, whose clocks have advanced during the transaction which
+    /// produced this event, mapped to a `(before, after)` pair of their clocks, like
+    /// `AfterTransactionEvent.clock_ranges` does.
+    pub fn clock_ranges(&self) -> PyObject {
+        let txn = self.txn();
+        clock_ranges_into_py(&txn.before_state, &txn.after_state)
+    }
+
     /// Returns all changes done upon map component of a current shared data type (which can be
     /// accessed via `target`) within a bounds of corresponding transaction `txn`. These
     /// changes are done in result of operations made on `YMap` data type or attribute changes of
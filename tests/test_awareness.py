@@ -0,0 +1,89 @@
+import pytest
+import y_py as Y
+from y_py import Awareness, apply_awareness_update, encode_awareness_update
+
+
+def test_local_state():
+    doc = Y.YDoc(client_id=1)
+    awareness = Awareness(doc)
+    assert awareness.client_id == 1
+    assert awareness.get_local_state() == {}
+
+    events = []
+    awareness.observe(events.append)
+    awareness.set_local_state({"user": "alice"})
+    assert awareness.get_local_state() == {"user": "alice"}
+    assert awareness.get_states() == {1: {"user": "alice"}}
+
+    awareness.set_local_state(None)
+    assert awareness.get_local_state() is None
+    assert awareness.get_states() == {}
+
+    assert [(e.added, e.updated, e.removed) for e in events] == [
+        ([], [1], []),
+        ([], [], [1]),
+    ]
+
+
+def test_awareness_update():
+    local = Awareness(Y.YDoc(client_id=1))
+    remote = Awareness(Y.YDoc(client_id=2))
+    events = []
+    sub = remote.observe(events.append)
+
+    local.set_local_state({"cursor": 5})
+    assert apply_awareness_update(remote, encode_awareness_update(local)) == [1]
+    assert remote.get_states() == {1: {"cursor": 5}, 2: {}}
+
+    # outdated updates are ignored
+    update = encode_awareness_update(local, [1])
+    local.set_local_state({"cursor": 6})
+    apply_awareness_update(remote, encode_awareness_update(local))
+    assert apply_awareness_update(remote, update) == []
+    assert remote.get_states()[1] == {"cursor": 6}
+
+    local.set_local_state(None)
+    assert apply_awareness_update(remote, encode_awareness_update(local)) == [1]
+    assert remote.get_states() == {2: {}}
+
+    remote.unobserve(sub)
+    local.set_local_state({"cursor": 7})
+    apply_awareness_update(remote, encode_awareness_update(local))
+    assert [(e.added, e.updated, e.removed) for e in events] == [
+        ([1], [], []),
+        ([], [1], []),
+        ([], [], [1]),
+    ]
+
+    with pytest.raises(KeyError):
+        encode_awareness_update(local, [3])
+    with pytest.raises(ValueError):
+        apply_awareness_update(remote, b"\x01\x01")
+    # a huge number of entries is rejected without allocating room for them up front
+    with pytest.raises(ValueError):
+        apply_awareness_update(remote, bytes([0xFF, 0xFF, 0xFF, 0xFF, 0x0F]))
+
+
+def test_awareness_numbers():
+    local = Awareness(Y.YDoc(client_id=1))
+    remote = Awareness(Y.YDoc(client_id=2))
+    local.set_local_state({"cursor": 5, "zoom": 1.0, "ratio": 0.5, "path": [1, 2.0]})
+    apply_awareness_update(remote, bytearray(encode_awareness_update(local)))
+    expected = {"cursor": 5, "zoom": 1, "ratio": 0.5, "path": [1, 2]}
+    for state in [local.get_local_state(), remote.get_states()[1]]:
+        assert state == expected
+        assert [type(state[k]) for k in ["cursor", "zoom", "ratio"]] == [int, int, float]
+
+
+def test_remote_removal_of_local_state():
+    local = Awareness(Y.YDoc(client_id=1))
+    remote = Awareness(Y.YDoc(client_id=2))
+    local.set_local_state({"cursor": 5})
+    apply_awareness_update(remote, encode_awareness_update(local))
+
+    # a peer can't remove the local state of another client (client 1, clock 2, null state)
+    removal = b"\x01\x01\x02\x04null"
+    apply_awareness_update(local, removal)
+    assert local.get_local_state() == {"cursor": 5}
+    apply_awareness_update(remote, encode_awareness_update(local, [1]))
+    assert remote.get_states()[1] == {"cursor": 5}
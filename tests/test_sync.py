@@ -0,0 +1,55 @@
+import pytest
+import y_py as Y
+from y_py import YDoc
+
+
+def test_sync_step_1():
+    doc = YDoc()
+    # message type followed by a length-prefixed empty state vector
+    assert Y.sync_step_1(doc) == b"\x00\x01\x00"
+    with doc.begin_transaction() as txn:
+        doc.get_text("test").extend(txn, "hello")
+    sv = Y.encode_state_vector(doc)
+    assert Y.sync_step_1(doc) == b"\x00" + bytes([len(sv)]) + sv
+
+
+def test_sync():
+    local, remote = YDoc(client_id=1), YDoc(client_id=2)
+    with local.begin_transaction() as txn:
+        local.get_text("test").extend(txn, "hello")
+    with remote.begin_transaction() as txn:
+        remote.get_array("array").append(txn, 1)
+
+    origins = []
+    remote.observe_after_transaction(lambda e: origins.append(e.origin))
+
+    step_2 = Y.read_sync_step_1(remote, Y.sync_step_1(local))
+    Y.read_sync_step_2(local, step_2)
+    reply = Y.read_sync_message(local, Y.sync_step_1(remote))
+    assert Y.read_sync_message(remote, reply, "local") is None
+    assert origins[-1] == "local"
+    assert str(remote.get_text("test")) == "hello"
+    assert list(local.get_array("array")) == [1]
+
+    updates = []
+    local.observe_after_transaction(lambda e: updates.append(e.get_update()))
+    with local.begin_transaction() as txn:
+        local.get_text("test").extend(txn, " world")
+    msg = Y.write_update(updates[-1])
+    assert msg[0] == 2
+    assert Y.read_sync_message(remote, msg) is None
+    assert str(remote.get_text("test")) == "hello world"
+
+
+def test_malformed_messages():
+    doc = YDoc()
+    with pytest.raises(ValueError):
+        Y.read_sync_message(doc, b"")
+    with pytest.raises(ValueError):
+        Y.read_sync_message(doc, b"\x00\x05\x00")
+    with pytest.raises(ValueError, match="Unknown sync message type: 3"):
+        Y.read_sync_message(doc, b"\x03\x00")
+    with pytest.raises(ValueError, match="Expected a sync message of type 1"):
+        Y.read_sync_step_2(doc, Y.sync_step_1(doc))
+    with pytest.raises(ValueError):
+        Y.read_sync_step_1(doc, Y.write_update(b""))
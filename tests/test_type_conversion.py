@@ -1,30 +1,121 @@
-from y_py import YMap, YDoc
-from math import isclose
+from datetime import datetime, timedelta, timezone
+import json
+from y_py import YMap, YDoc, apply_update, encode_state_as_update
+import pytest
 
 
 def test_int_conversion():
     """
-    Tests conversions between Python integers and the Yrs `Any` value.
-    To be consistent with Y.js, all floats and integers are represented as `float64`.
-    Since Python `int`s can continuously grow, numbers larger than this data type limit will turn into JavaScript `BigInts`.
-    Converting back to Python, `int`s will become `float`s unless they were cast as a `BigInt`.
+    Tests conversions between Python numbers and the Yrs `Any` value.
+    To be consistent with Y.js, integers are stored as `float64` numbers, unless they don't fit into
+    its exact range, in which case they are stored as 64 bit `BigInt`s. Numbers without a fractional
+    part are read back as `int`s, even after being synchronized with another document, so `float`s
+    without one are read back as `int`s too.
+    Integers which don't fit into 64 bits are rejected with an `OverflowError`.
     """
 
     JS_MAX_NUMBER = 2 ** 53 - 1
+    values = {
+        "int": 5,
+        "float": 5.0,
+        "fraction": 0.5,
+        "big_int": JS_MAX_NUMBER + 2,
+        "negative_big_int": -(2 ** 63),
+        "edge_number": JS_MAX_NUMBER,
+        "negative_edge_number": -JS_MAX_NUMBER,
+        "big_float": 2.0 ** 60,
+    }
+    types = {"fraction": float, "big_float": float}
     doc = YDoc()
     map = doc.get_map("map")
     with doc.begin_transaction() as txn:
-        map.update(
-            txn,
-            {
-                "big_int": JS_MAX_NUMBER + 1,
-                "small_number": 1,
-                "edge_number": JS_MAX_NUMBER,
-            },
-        )
-
-    assert type(map["big_int"]) == int and map["big_int"] == JS_MAX_NUMBER + 1
-    assert type(map["small_number"]) == float and isclose(1.0, map["small_number"])
-    assert type(map["edge_number"]) == float and isclose(
-        float(JS_MAX_NUMBER), map["edge_number"]
-    )
+        map.update(txn, values)
+
+    remote = YDoc()
+    apply_update(remote, encode_state_as_update(doc))
+    for m in [map, remote.get_map("map")]:
+        assert {k: (type(v), v) for k, v in m.items()} == {
+            k: (types.get(k, int), v) for k, v in values.items()
+        }
+    assert json.loads(map.to_json())["int"] == 5
+
+    with pytest.raises(OverflowError):
+        with doc.begin_transaction() as txn:
+            map.set(txn, "too_big", 2 ** 64)
+    assert "too_big" not in map
+
+
+def test_set_conversion():
+    """
+    Sets have no defined ordering, so they cannot be mapped onto a Y sequence deterministically.
+    Instead of silently storing `null`, integrating a set raises a `TypeError` pointing the user to lists.
+    """
+    doc = YDoc()
+    map = doc.get_map("map")
+    for unordered in [{1, 2, 3}, frozenset(["a", "b"])]:
+        with pytest.raises(TypeError, match="convert it into a list"):
+            with doc.begin_transaction() as txn:
+                map.set(txn, "values", unordered)
+
+    assert len(map) == 0
+
+    # Sorting the set first is the suggested workaround
+    with doc.begin_transaction() as txn:
+        map.set(txn, "values", sorted({3, 1, 2}))
+    assert map["values"] == [1, 2, 3]
+
+
+def test_bytes_conversion():
+    values = {
+        "empty": b"",
+        "blob": b"\x00\xff\x10",
+        "large": bytes(range(256)) * 1024,
+        "nested": [b"a", {"b": bytearray(b"b")}],
+    }
+    doc = YDoc()
+    map = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        map.update(txn, values)
+        map.set(txn, "array", bytearray(b"\x01\x02"))
+
+    remote = YDoc()
+    apply_update(remote, encode_state_as_update(doc))
+    for m in [map, remote.get_map("map")]:
+        assert m["empty"] == b"" and type(m["empty"]) == bytes
+        assert m["blob"] == b"\x00\xff\x10"
+        assert m["large"] == values["large"]
+        assert m["nested"] == [b"a", {"b": b"b"}]
+        assert type(m["array"]) == bytes and m["array"] == b"\x01\x02"
+
+    # JSON has no representation for binary data
+    with pytest.raises(TypeError):
+        map.to_json()
+
+
+def test_datetime_conversion():
+    """
+    Datetimes are stored as `{"__datetime__": "<ISO 8601 string>"}` maps, which are read back as
+    `datetime` objects. Timezone-aware datetimes keep their UTC offset.
+    """
+    values = {
+        "naive": datetime(2022, 10, 1, 12, 30, 15, 250),
+        "utc": datetime(2022, 10, 1, 12, 30, tzinfo=timezone.utc),
+        "offset": datetime(2022, 10, 1, 12, 30, tzinfo=timezone(timedelta(hours=-5, minutes=-30))),
+        "nested": [{"at": datetime(2000, 1, 1)}],
+    }
+    doc = YDoc()
+    map = doc.get_map("map")
+    prelim = YMap(values)
+    with doc.begin_transaction() as txn:
+        map.update(txn, values)
+        map.set(txn, "prelim", prelim)
+
+    remote = YDoc()
+    apply_update(remote, encode_state_as_update(doc))
+    for m in [map, remote.get_map("map")]:
+        assert {k: m[k] for k in values} == values
+        assert m["offset"].utcoffset() == timedelta(hours=-5, minutes=-30)
+        assert m["naive"].tzinfo is None
+        assert dict(m["prelim"]) == values
+
+    assert json.loads(map.to_json())["utc"] == {"__datetime__": "2022-10-01T12:30:00+00:00"}
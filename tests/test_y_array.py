@@ -1,8 +1,10 @@
 from test_helper import exchange_updates
 import pytest
 
+import y_py as Y
 from y_py import YDoc, YArray, YArrayEvent
 from copy import deepcopy
+from datetime import datetime
 import json
 
 def test_inserts():
@@ -68,6 +70,138 @@ def test_inserts_nested():
     assert to_list(x) == expected
 
 
+def test_append_nested_prelims():
+    doc = YDoc()
+    x = doc.get_array("test")
+    text = Y.YText("hello")
+    with doc.begin_transaction() as txn:
+        x.append(txn, text)
+        x.extend(txn, [Y.YMap({"a": Y.YArray([1])})])
+    assert type(x[0]) == Y.YText and not text.prelim
+    assert type(x[1]["a"]) == YArray
+    assert json.loads(x.to_json()) == ["hello", {"a": [1]}]
+
+    # an integrated type can't be inserted again, even when nested in a preliminary one
+    for insert in [
+        lambda txn: x.append(txn, text),
+        lambda txn: x.insert(txn, 0, Y.YMap({"b": text})),
+        lambda txn: x.extend(txn, [1, text]),
+    ]:
+        with pytest.raises(Exception):
+            with doc.begin_transaction() as txn:
+                insert(txn)
+    assert len(x) == 2
+
+
+
+def test_extend_bulk():
+    doc = YDoc()
+    x = doc.get_array("test")
+    blocks = []
+    sub = x.observe(lambda e: blocks.append(len(e.delta)))
+    rows = [{"id": i} for i in range(50000)]
+    with doc.begin_transaction() as txn:
+        x.extend(txn, rows)
+    assert len(x) == 50000 and x[49999] == {"id": 49999}
+    assert blocks == [1]
+
+    with doc.begin_transaction() as txn:
+        x.insert_range(txn, 1, [1, 2, Y.YArray([3]), 4])
+    assert [x[1], x[2], x[4]] == [1, 2, 4] and x[3].to_json() == "[3]"
+
+    # a value which can't be converted leaves the array unchanged
+    with pytest.raises(Exception):
+        with doc.begin_transaction() as txn:
+            x.extend(txn, [1, object()])
+    assert len(x) == 50004
+
+    # values of nested preliminary arrays are checked up front, but converted only once they are
+    # integrated, when errors can only be raised after the transaction is committed
+    class Flaky(datetime):
+        calls = 0
+
+        def isoformat(self, *args):
+            Flaky.calls += 1
+            if Flaky.calls > 1:
+                raise ValueError("flaky")
+            return super().isoformat(*args)
+
+    with pytest.raises(ValueError, match="flaky"):
+        with doc.begin_transaction() as txn:
+            x.extend(txn, [Y.YArray([Flaky(2020, 1, 1)])])
+            assert x[50004].to_json() == "[]"
+    with doc.begin_transaction() as txn:
+        x.append(txn, 5)
+    assert len(x) == 50006 and x[50005] == 5
+
+
+def test_set_and_del_item():
+    x = YArray([1, 2, 3, 4, 5])
+    x[0] = 10
+    x[-1] = 50
+    x[1:3] = ["a", "b", "c"]
+    assert list(x) == [10, "a", "b", "c", 4, 50]
+    x[::2] = [0, 0, 0]
+    assert list(x) == [0, "a", 0, "c", 0, 50]
+    with pytest.raises(ValueError):
+        x[::2] = [1]
+    with pytest.raises(IndexError):
+        x[6] = 1
+
+    del x[-1]
+    del x[::2]
+    assert list(x) == ["a", "c"]
+    del x[:]
+    assert len(x) == 0
+
+    # integrated arrays have to be modified within a transaction
+    doc = YDoc()
+    integrated = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        integrated.extend(txn, [1, 2])
+    with pytest.raises(TypeError):
+        integrated[0] = 3
+    with pytest.raises(TypeError):
+        del integrated[0]
+    assert list(integrated) == [1, 2]
+
+
+def test_clear():
+    doc = YDoc()
+    x = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        x.extend(txn, [1, Y.YMap({"a": 1}), "c"])
+        x.clear(txn)
+        x.clear(txn)
+    assert len(x) == 0
+
+    prelim = YArray([1, 2])
+    with doc.begin_transaction() as txn:
+        prelim.clear(txn)
+    assert list(prelim) == []
+
+
+def test_pop():
+    doc = YDoc()
+    x = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        x.extend(txn, [1, Y.YMap({"a": [2]}), "c", 4])
+        assert x.pop(txn) == 4
+        assert x.pop(txn, -1) == "c"
+        nested = x.pop(txn, 1)
+        assert x.pop(txn, 0) == 1
+        with pytest.raises(IndexError):
+            x.pop(txn)
+    assert nested.prelim and dict(nested.items()) == {"a": [2]}
+    assert len(x) == 0
+
+    prelim = YArray([1, 2, 3])
+    with doc.begin_transaction() as txn:
+        assert prelim.pop(txn, -2) == 2
+        with pytest.raises(IndexError):
+            prelim.pop(txn, 2)
+    assert list(prelim) == [1, 3]
+
 def test_delete():
     d1 = YDoc(1)
     assert d1.client_id == 1
@@ -133,6 +267,68 @@ def test_get():
         assert arr[0:] == expected
         assert arr[4:1:-1] == expected[4:1:-1]
         assert arr[::-1] == expected[::-1]
+        for s in [
+            slice(1, 3),
+            slice(-3, None),
+            slice(None, None, 2),
+            slice(None, None, -2),
+            slice(3, 0, -2),
+            slice(-10, 2),
+            slice(10, None),
+            slice(2, 1),
+        ]:
+            assert arr[s] == expected[s]
+        with pytest.raises(ValueError):
+            arr[::0]
+
+    with d1.begin_transaction() as txn:
+        integrated.append(txn, YArray([{"nested": ["value"]}]))
+    [nested] = integrated[-1:]
+    assert isinstance(nested, YArray)
+    assert list(nested) == [{"nested": ["value"]}]
+
+
+def test_none_values():
+    doc = YDoc()
+    array = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, None, 3])
+        array.append(txn, [None])
+
+    remote = YDoc()
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    for arr in [array, remote.get_array("test"), YArray([1, None, 3, [None]])]:
+        assert len(arr) == 4
+        assert arr[0] == 1 and arr[1] is None and arr[2] == 3 and arr[3] == [None]
+        assert arr[-3] is None and arr[1:3] == [None, 3]
+        assert list(arr) == arr.to_list() == [1, None, 3, [None]]
+        assert json.loads(arr.to_json()) == [1, None, 3, [None]]
+        # a stored None doesn't make out of range indexes valid
+        with pytest.raises(IndexError):
+            arr[4]
+
+
+def test_index_count():
+    d = YDoc()
+    x = d.get_array("test")
+    with d.begin_transaction() as txn:
+        x.extend(txn, [1, "a", 1, YArray([1, 2]), {"b": True}, 1])
+
+    assert x.index(1) == 0
+    assert x.index("a") == 1
+    assert x.index([1, 2]) == 3
+    assert x.index({"b": True}) == 4
+    assert x.count(1) == 3
+    assert x.count([1, 2]) == 1
+    assert x.count("missing") == 0
+    with pytest.raises(ValueError):
+        x.index("missing")
+
+    prelim = YArray(["a", YArray(["b"]), "a"])
+    assert prelim.index(["b"]) == 1
+    assert prelim.count("a") == 2
+    with pytest.raises(ValueError):
+        prelim.index("b")
 
 
 def test_iterator():
@@ -150,6 +346,29 @@ def test_iterator():
     # Test contains
     assert 2.0 in x
 
+    # Iterators are lazy and stop early when the array shrinks
+    it = iter(x)
+    assert next(it) == 1.0
+    with d1.begin_transaction() as txn:
+        x.delete_range(txn, 1, 2)
+    assert list(it) == []
+
+    # Values read ahead are refreshed when the array changes, even within an open transaction
+    with d1.begin_transaction() as txn:
+        x.extend(txn, list(range(2, 1000)))
+    assert list(x) == [1.0] + list(range(2, 1000))
+    it = iter(x)
+    assert [next(it), next(it)] == [1.0, 2]
+    with d1.begin_transaction() as txn:
+        x.delete(txn, 2)
+        assert next(it) == 4
+    x.insert(d1.begin_transaction(), 3, "a")
+    assert next(it) == "a"
+    for arr in [YArray([1, YArray(["a"])]), x]:
+        assert list(iter(arr))[0] == 1
+    with pytest.raises(StopIteration):
+        next(iter(YArray()))
+
 
 def test_borrow_mut_edge_case():
     """
@@ -221,6 +440,71 @@ def test_observer():
     assert delta == None
 
 
+def test_observer_nested_inserts():
+    doc = YDoc()
+    array = doc.get_array("test")
+    children = []
+    changes = []
+    subs = []
+
+    def callback(e: YArrayEvent):
+        for op in e.delta:
+            for child in op.get("insert", []):
+                # inserted shared types are integrated, so they can be observed right away
+                assert not child.prelim
+                subs.append(child.observe(lambda e: changes.append(e.target)))
+                children.append(child)
+
+    sub = array.observe(callback)
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [Y.YMap({"x": 1}), Y.YText("text"), Y.YArray([1])])
+    assert [type(child) for child in children] == [Y.YMap, Y.YText, Y.YArray]
+
+    with doc.begin_transaction() as txn:
+        children[0].set(txn, "y", 2)
+        children[1].extend(txn, "!")
+        children[2].append(txn, 2)
+    assert {type(target) for target in changes} == {Y.YMap, Y.YText, Y.YArray}
+    assert len(changes) == 3
+    assert array.to_list(deep=True) == [{"x": 1, "y": 2}, "text!", [1, 2]]
+
+
+def test_observer_with_snapshot():
+    d1 = YDoc()
+    x = d1.get_array("test")
+    with d1.begin_transaction() as txn:
+        x.extend(txn, [1, 2, 3])
+
+    events = []
+    sub = x.observe(events.append, with_snapshot=True)
+    with d1.begin_transaction() as txn:
+        x.delete_range(txn, 0, 2)
+        x.append(txn, 4)
+    with d1.begin_transaction() as txn:
+        x.append(txn, 5)
+
+    # snapshots stay readable after the callbacks have finished
+    assert [e.before for e in events] == [[1, 2, 3], [3, 4]]
+    assert list(x) == [3, 4, 5]
+
+    # edits made inside nested types are reflected by the next snapshot
+    with d1.begin_transaction() as txn:
+        x.append(txn, Y.YArray([1]))
+    with d1.begin_transaction() as txn:
+        x[3].append(txn, 2)
+    with d1.begin_transaction() as txn:
+        x.delete(txn, 0)
+    assert events[3].before == [3, 4, 5, [1, 2]]
+    x.unobserve(sub)
+
+    # snapshots are opt-in
+    events = []
+    sub = x.observe(events.append)
+    with d1.begin_transaction() as txn:
+        x.append(txn, 6)
+    assert events[0].before is None
+
+
 def test_deep_observe():
     """
     Ensure that changes to elements inside the array trigger a callback.
@@ -293,6 +577,28 @@ def test_move_to():
     with pytest.raises(Exception):
         doc.transact(lambda t: arr.move_to(t, 0, -5))
 
+    # Out of range indices
+    with doc.begin_transaction() as t:
+        with pytest.raises(IndexError):
+            arr.move_to(t, 10, 0)
+        with pytest.raises(IndexError):
+            arr.move_to(t, 0, 11)
+        with pytest.raises(IndexError):
+            arr.move_range_to(t, 0, 10, 0)
+        with pytest.raises(IndexError):
+            arr.move_range_to(t, 0, 1, 11)
+    assert list(arr) == [0,1,2,3,4,5,6,7,8,9]
+
+    # Preliminary arrays follow the same rules
+    prelim = YArray([0,1,2])
+    with doc.begin_transaction() as t:
+        prelim.move_to(t, 0, 3)
+        assert prelim.to_json() == "[1,2,0]"
+        prelim.move_range_to(t, 1, 0, 3)
+        assert prelim.to_json() == "[1,2,0]"
+        with pytest.raises(IndexError):
+            prelim.move_to(t, 3, 0)
+
 def test_move_range_to():
     """
     Ensure that move_range_to works.
@@ -384,3 +690,139 @@ def test_move_range_to():
         arr.extend(t, [0,1,2,3,4,5,6,7,8,9])
     with pytest.raises(Exception):
         doc.transact(lambda t: arr.move_range_to(t, 0, -1, 3))
+
+
+def test_transact():
+    doc = YDoc()
+    arr = doc.get_array("test")
+    arr.transact(doc, lambda txn, a: a.extend(txn, [1, 2, 3]))
+    arr.transact(doc, lambda txn, a: a.delete(txn, 0))
+    assert list(arr) == [2, 3]
+
+
+def test_msgpack():
+    doc = YDoc()
+    arr = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        arr.extend(txn, [1, -2, "a", None, True, 1.5, [300, {"k": "v"}]])
+        payload = arr.to_msgpack(txn)
+    assert payload == (
+        b"\x97\x01\xfe\xa1a\xc0\xc3\xcb\x3f\xf8\x00\x00\x00\x00\x00\x00"
+        b"\x92\xcd\x01\x2c\x81\xa1k\xa1v"
+    )
+
+    prelim = YArray.from_msgpack(payload)
+    assert prelim.prelim
+    other = YDoc()
+    restored = other.get_array("test")
+    with other.begin_transaction() as txn:
+        restored.extend(txn, prelim)
+    assert list(restored) == list(arr)
+
+    # binary values map to the MessagePack bin type
+    blob = b"\x92\xc4\x02\x00\xff\xa3end"
+    prelim = YArray.from_msgpack(blob)
+    assert list(prelim) == [bytearray(b"\x00\xff"), "end"]
+    with doc.begin_transaction() as txn:
+        assert prelim.to_msgpack(txn) == blob
+
+    with pytest.raises(TypeError):
+        YArray.from_msgpack(b"\x01")
+    # malformed payloads are rejected instead of exhausting memory or the stack
+    with pytest.raises(ValueError, match="nested deeper"):
+        YArray.from_msgpack(b"\x91" * 200000 + b"\xc0")
+    with pytest.raises(ValueError, match="Unexpected end"):
+        YArray.from_msgpack(b"\xdd\xff\xff\xff\xff")
+    nested = YArray.from_msgpack(b"\x91" * 128 + b"\xc0")
+    for _ in range(127):
+        nested = nested[0]
+    assert nested == [None]
+
+
+def test_eq():
+    d1 = YDoc()
+    d2 = YDoc()
+    a1 = d1.get_array("test")
+    a2 = d2.get_array("test")
+    with d1.begin_transaction() as txn:
+        a1.extend(txn, [1, "a", Y.YMap({"k": [True]})])
+    with d2.begin_transaction() as txn:
+        a2.extend(txn, [1, "a", Y.YMap({"k": [True]})])
+    assert a1 == a2
+    assert a1 == [1, "a", {"k": [True]}]
+    assert a1 != [1, "a"]
+    assert YArray([1, Y.YText("x")]) == YArray([1, "x"])
+    assert YArray([1, Y.YArray([2])]) == [1, [2]]
+    assert YArray([1]) != YArray([2])
+    assert a1 != "a"
+    with pytest.raises(TypeError):
+        hash(a1)
+
+
+def test_out_of_range():
+    doc = YDoc()
+    array = doc.get_array("test")
+    for test in [array, YArray([])]:
+        with doc.begin_transaction() as txn:
+            test.extend(txn, [1, 2, 3])
+            with pytest.raises(IndexError, match="Index 4 is out of range of an array with length 3"):
+                test.insert(txn, 4, 0)
+            with pytest.raises(IndexError):
+                test.insert_range(txn, 9999, [0])
+            with pytest.raises(IndexError):
+                test.delete(txn, 3)
+            with pytest.raises(IndexError, match="Range 2..4"):
+                test.delete_range(txn, 2, 2)
+            test.insert(txn, 3, 4)
+        assert list(test) == [1, 2, 3, 4]
+
+
+def test_to_list():
+    doc = YDoc()
+    array = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, "two", Y.YMap({"a": 1}), YArray([True])])
+
+    items = array.to_list()
+    assert type(items) == list
+    assert items[:2] == [1.0, "two"]
+    assert isinstance(items[2], Y.YMap) and isinstance(items[3], YArray)
+    assert array.to_list(deep=True) == [1.0, "two", {"a": 1.0}, [True]]
+
+    prelim = YArray([1, YArray([2])])
+    assert prelim.to_list()[0] == 1
+    assert isinstance(prelim.to_list()[1], YArray)
+    assert prelim.to_list(deep=True) == [1, [2]]
+
+
+def test_get_type():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [Y.YText("a"), Y.YArray([]), Y.YMap({}), "b", {"key": 1}])
+    kinds = [array.get_type(i) for i in range(len(array))]
+    assert kinds == ["text", "array", "map", "primitive", "primitive"]
+    assert array.get_type(-3) == "map"
+    with pytest.raises(IndexError):
+        array.get_type(5)
+
+    prelim = YArray([YArray([1]), 2])
+    assert prelim.get_type(0) == "array"
+    assert prelim.get_type(-1) == "primitive"
+    with pytest.raises(IndexError):
+        prelim.get_type(-3)
+
+
+def test_negative_indexes():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, 2, 3])
+        array.insert(txn, -1, "a")
+        array.insert_range(txn, -4, ["b", "c"])
+        array.delete(txn, -1)
+        array.delete_range(txn, -3, 2)
+        with pytest.raises(IndexError, match="Index -4 is out of range of an array with length 3"):
+            array.delete(txn, -4)
+    assert array.to_list() == ["b", "c", "a"]
+    assert array[-1] == "a"
@@ -1,3 +1,5 @@
+import copy
+import pickle
 from y_py import YDoc, AfterTransactionEvent
 
 import y_py as Y
@@ -45,6 +47,73 @@ def test_encoding():
     assert value == contents
 
 
+def test_encoding_v2():
+    doc = YDoc()
+    receiver = YDoc()
+    array = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        array.insert_range(txn, 0, [True, 42, "string"])
+    update = Y.encode_state_as_update_v2(doc, Y.encode_state_vector_v2(receiver))
+    Y.apply_update_v2(receiver, update)
+    assert list(receiver.get_array("test")) == [True, 42, "string"]
+
+    # only missing changes are sent
+    with doc.begin_transaction() as txn:
+        array.append(txn, "new")
+    update = Y.encode_state_as_update_v2(doc, Y.encode_state_vector_v2(receiver))
+    Y.apply_update_v2(receiver, update)
+    assert list(receiver.get_array("test")) == [True, 42, "string", "new"]
+    assert Y.encode_state_vector(receiver) == Y.encode_state_vector(doc)
+
+    for malformed in [b"\xca", b"\x00\x05"]:
+        with pytest.raises(ValueError):
+            Y.apply_update_v2(receiver, malformed)
+        with pytest.raises(ValueError):
+            Y.encode_state_as_update_v2(doc, malformed)
+
+
+def test_malformed_v1():
+    doc = YDoc()
+    # truncated payloads fail to decode, while the last update decodes fine, but refers to
+    # an item that doesn't exist
+    malformed = [b"\xca", b"\x01\x01\x05\x00\x04\x00\x05\x00\x01a\x00"]
+    for update in malformed:
+        with pytest.raises(Y.EncodingException):
+            Y.apply_update(doc, update)
+        with pytest.raises(Y.EncodingException):
+            with doc.begin_transaction() as txn:
+                txn.apply_v1(update)
+    with pytest.raises(Y.EncodingException):
+        Y.encode_state_as_update(doc, b"\xca")
+    with pytest.raises(Y.EncodingException):
+        with doc.begin_transaction() as txn:
+            txn.diff_v1(b"\xca")
+    assert issubclass(Y.EncodingException, ValueError)
+
+
+def test_encoding_buffers():
+    doc = YDoc()
+    array = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        array.insert_range(txn, 0, [True, 42, "string"])
+    update = Y.encode_state_as_update(doc)
+    padded = memoryview(b"".join(bytes([b, 0]) for b in update))[::2]
+    for payload in [bytearray(update), memoryview(update), padded, list(update)]:
+        receiver = YDoc()
+        Y.apply_update(receiver, payload)
+        assert list(receiver.get_array("test")) == [True, 42, "string"]
+
+    state_vec = Y.encode_state_vector(doc)
+    assert Y.encode_state_as_update(doc, memoryview(state_vec)) == Y.encode_state_as_update(
+        doc, state_vec
+    )
+    receiver = YDoc()
+    state_vec = Y.encode_state_vector_v2(receiver)
+    update = Y.encode_state_as_update_v2(doc, bytearray(state_vec))
+    Y.apply_update_v2(receiver, memoryview(update))
+    assert Y.encode_state_vector(receiver) == Y.encode_state_vector(doc)
+
+
 def test_boolean_encoding():
     """
     Makes sure the boolean types are preserved.
@@ -79,6 +148,95 @@ def test_tutorial():
     assert value == "hello world!"
 
 
+def test_snapshot():
+    doc = YDoc(1, skip_gc=True)
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+    snapshot = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 6)
+        text.extend(txn, "!")
+    assert str(text) == "world!"
+
+    restored = doc.restore(snapshot)
+    assert str(restored.get_text("test")) == "hello world"
+    assert restored.client_id != doc.client_id
+
+    receiver = YDoc()
+    Y.apply_update(receiver, Y.encode_state_from_snapshot(doc, snapshot))
+    assert str(receiver.get_text("test")) == "hello world"
+
+    with pytest.raises(ValueError):
+        YDoc().snapshot()
+    with pytest.raises(ValueError):
+        YDoc().restore(snapshot)
+
+
+def test_merge_updates():
+    doc = YDoc()
+    text = doc.get_text("test")
+    updates = []
+    doc.observe_after_transaction(lambda e: updates.append(e.get_update()))
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    sv = Y.encode_state_vector(doc)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " world")
+        text.delete_range(txn, 0, 1)
+
+    merged = Y.merge_updates(updates)
+    receiver = YDoc()
+    Y.apply_update(receiver, merged)
+    assert str(receiver.get_text("test")) == "ello world"
+
+    # a peer which has seen the first update only gets the rest
+    peer = YDoc()
+    Y.apply_update(peer, updates[0])
+    Y.apply_update(peer, Y.diff_updates(merged, sv))
+    assert str(peer.get_text("test")) == "ello world"
+    assert len(Y.diff_updates(merged, sv)) < len(merged)
+
+    with pytest.raises(ValueError):
+        Y.merge_updates([updates[0], b"\xff\x01"])
+    with pytest.raises(ValueError):
+        Y.diff_updates(merged, b"\xff")
+
+
+def test_apply_updates():
+    doc = YDoc()
+    text = doc.get_text("test")
+    log = []
+    doc.observe_update_v1(lambda update, origin: log.append(update))
+    for i in range(20):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, str(i))
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 5)
+
+    receiver = YDoc()
+    received = receiver.get_text("test")
+    events = []
+    origins = []
+    updates = []
+    sub = received.observe(lambda e: events.append(e.delta))
+    receiver.observe_after_transaction(lambda e: origins.append(e.origin))
+    receiver.observe_update_v1(lambda update, origin: updates.append(update))
+    # all updates are integrated by a single transaction
+    Y.apply_updates(receiver, log, origin="log")
+    assert events == [[{"insert": str(text)}]]
+    assert origins == ["log"] and len(updates) == 1
+    assert receiver.equal_state(doc) and str(received) == str(text)
+
+    # nothing is applied when any of the updates is malformed
+    receiver = YDoc()
+    with pytest.raises(ValueError, match="index 1"):
+        Y.apply_updates(receiver, [log[0], b"\xff\x01", log[1]])
+    assert str(receiver.get_text("test")) == ""
+    Y.apply_updates(receiver, [])
+    assert str(receiver.get_text("test")) == ""
+
+
 def test_observe_after_transaction():
     doc = Y.YDoc()
     text = doc.get_text("test")
@@ -126,3 +284,653 @@ def test_get_update():
         m.set(txn, "hi", "there")
 
     assert type(update) == bytes
+
+
+def test_current_clock():
+    """
+    Ensures that the logical clock of each client grows monotonically with every edit.
+    """
+    d1 = Y.YDoc(1)
+    d2 = Y.YDoc(2)
+    text = d1.get_text("test")
+    assert d1.current_clock() == 0
+
+    clocks = []
+    for chunk in ["a", "bc", "def"]:
+        with d1.begin_transaction() as txn:
+            text.extend(txn, chunk)
+        clocks.append(d1.current_clock())
+    assert clocks == sorted(clocks) and len(set(clocks)) == len(clocks)
+    assert d1.current_clock(1) == clocks[-1]
+
+    # Remote clocks become visible once their updates are integrated
+    assert d2.current_clock(1) == 0
+    Y.apply_update(d2, Y.encode_state_as_update(d1))
+    assert d2.current_clock(1) == d1.current_clock()
+    assert d2.current_clock() == 0
+
+    assert d2.state_vector() == {1: d1.current_clock()}
+    with d2.begin_transaction() as txn:
+        d2.get_text("other").extend(txn, "xy")
+    assert d2.state_vector() == {1: d1.current_clock(), 2: 2}
+    assert Y.YDoc().state_vector() == {}
+
+
+def test_clock_ranges():
+    doc = Y.YDoc(1)
+    text = doc.get_text("test")
+    ranges = None
+    states = None
+
+    def callback(event):
+        nonlocal ranges, states
+        ranges = event.clock_ranges()
+        states = (event.before_state_map, event.after_state_map)
+        assert isinstance(event.before_state, bytes)
+
+    doc.observe_after_transaction(callback)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "abc")
+    assert ranges == {1: (0, 3)}
+    assert states == ({}, {1: 3})
+
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "de")
+    assert ranges == {1: (3, 5)}
+    assert states == ({1: 3}, {1: 5})
+
+
+def test_event_clock_ranges():
+    d1 = Y.YDoc(1)
+    text = d1.get_text("text")
+    array = d1.get_array("array")
+    xml = d1.get_xml_element("xml")
+    d2 = Y.YDoc(2)
+    ranges = {}
+    subscriptions = []
+
+    def observe(shared):
+        def callback(event):
+            ranges[type(event).__name__] = event.clock_ranges()
+
+        subscriptions.append(shared.observe(callback))
+
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "abc")
+        array.append(txn, Y.YMap({}))
+        xml.push_xml_text(txn)
+    for shared in [text, array, array[0], xml, xml.first_child]:
+        observe(shared)
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "de")
+        array[0].set(txn, "key", 1)
+        xml.first_child.push(txn, "x")
+    assert ranges == {
+        "YTextEvent": {1: (5, 9)},
+        "YMapEvent": {1: (5, 9)},
+        "YXmlTextEvent": {1: (5, 9)},
+    }
+    ranges.clear()
+    with d1.begin_transaction() as txn:
+        array.append(txn, 1)
+        xml.set_attribute(txn, "a", "b")
+    assert ranges == {"YArrayEvent": {1: (9, 11)}, "YXmlEvent": {1: (9, 11)}}
+
+    # remote changes carry clocks of the client which made them
+    deep = None
+
+    def callback(events):
+        nonlocal deep
+        deep = [event.clock_ranges() for event in events]
+
+    subscription = d2.get_text("text").observe_deep(callback)
+    Y.apply_update(d2, Y.encode_state_as_update(d1))
+    assert deep == [{1: (0, 11)}]
+
+
+def test_delete_set_map():
+    doc = Y.YDoc(1)
+    text = doc.get_text("test")
+    deleted = None
+
+    def callback(event):
+        nonlocal deleted
+        deleted = event.delete_set_map
+
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "abcdef")
+    doc.observe_after_transaction(callback)
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 4, 1)
+        text.delete_range(txn, 0, 2)
+    assert deleted == {1: [(0, 2), (4, 1)]}
+
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+    assert deleted == {}
+
+
+def test_gc_performed():
+    for skip_gc in [False, True]:
+        doc = Y.YDoc(skip_gc=skip_gc)
+        text = doc.get_text("test")
+        gc = []
+        doc.observe_after_transaction(lambda e: gc.append(e.gc_performed))
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "abc")
+        with doc.begin_transaction() as txn:
+            text.delete_range(txn, 0, 2)
+        assert gc == [False, not skip_gc]
+
+
+def test_keep_history():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    assert doc.history() == []
+    doc.keep_history(10)
+
+    states = []
+    for chunk in ["a", "b", "c", "d", "e"]:
+        with doc.begin_transaction() as txn:
+            text.extend(txn, chunk)
+        states.append(str(text))
+    history = doc.history()
+    assert len(history) == 5
+
+    # replaying the history in order reconstructs every intermediate state
+    replica = Y.YDoc()
+    replica_text = replica.get_text("test")
+    for update, expected in zip(history, states):
+        Y.apply_update(replica, update)
+        assert str(replica_text) == expected
+
+    # lowering the limit trims the oldest updates
+    doc.keep_history(3)
+    assert doc.history() == history[2:]
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "f")
+    assert len(doc.history()) == 3
+    assert doc.history()[:2] == history[3:]
+
+    # a limit of 0 stops the recording
+    doc.keep_history(0)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "g")
+    assert doc.history() == []
+
+
+def test_converged_with():
+    d1 = Y.YDoc(1)
+    d2 = Y.YDoc(2)
+    assert d1.converged_with(d2)
+
+    t1 = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        t1.extend(txn, "hello")
+    t2 = d2.get_text("test")
+    with d2.begin_transaction() as txn:
+        t2.extend(txn, "world")
+    assert not d1.converged_with(d2)
+
+    # partially synced: d2 has received everything from d1, but not the other way around
+    Y.apply_update(d2, d1.missing_from(d2))
+    assert not d1.converged_with(d2)
+    assert not d2.converged_with(d1)
+
+    Y.apply_update(d1, d2.missing_from(d1))
+    assert d1.converged_with(d2) and d2.converged_with(d1)
+    assert str(t1) == str(t2)
+
+    # nothing left to exchange
+    d3 = Y.YDoc(3)
+    Y.apply_update(d3, d1.missing_from(d2))
+    assert str(d3.get_text("test")) == ""
+
+
+def test_equal_state():
+    peers = [Y.YDoc(i) for i in range(3)]
+    updates = []
+    for i, doc in enumerate(peers):
+        text = doc.get_text("test")
+        with doc.begin_transaction() as txn:
+            text.extend(txn, f"peer {i} ")
+        with doc.begin_transaction() as txn:
+            text.delete_range(txn, 0, 2)
+        updates.append(Y.encode_state_as_update(doc))
+
+    merged = []
+    for order in [[0, 1, 2], [2, 1, 0], [1, 2, 0]]:
+        doc = Y.YDoc(10 + len(merged))
+        for i in order:
+            Y.apply_update(doc, updates[i])
+        merged.append(doc)
+    assert merged[0].equal_state(merged[1])
+    assert merged[0].equal_state(merged[2])
+    assert not merged[0].equal_state(peers[0])
+
+    # deletions don't advance the state vector, but are still compared
+    text = merged[0].get_text("test")
+    with merged[0].begin_transaction() as txn:
+        text.delete_range(txn, 0, 1)
+    assert merged[0].converged_with(merged[1])
+    assert not merged[0].equal_state(merged[1])
+
+    # documents are compared and hashed by identity, even while a transaction is open
+    with merged[1].begin_transaction():
+        assert merged[1] == merged[1] and merged[1] != merged[2]
+        assert merged[2] in [merged[1], merged[2]]
+    assert len({merged[1], merged[2], merged[1]}) == 2
+
+
+def test_pickle():
+    doc = Y.YDoc(client_id=42, offset_kind="utf16", skip_gc=True)
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello 🌍 world")
+        text.delete_range(txn, 0, 6)
+        doc.get_map("map").set(txn, "nested", Y.YArray([1, 2]))
+
+    for restored in [pickle.loads(pickle.dumps(doc)), copy.deepcopy(doc)]:
+        assert restored is not doc and restored.equal_state(doc)
+        assert restored.client_id == 42
+        restored_text = restored.get_text("text")
+        assert str(restored_text) == "🌍 world"
+        # utf16 offsets count the emoji as two units
+        assert len(restored_text) == 8
+        assert list(restored.get_map("map")["nested"]) == [1, 2]
+        # snapshots require skip_gc, which is preserved as well
+        assert restored.skip_gc
+        restored.snapshot()
+    assert not YDoc().skip_gc
+
+
+def test_fork():
+    doc = Y.YDoc(client_id=42, offset_kind="utf16", skip_gc=True)
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello 🌍")
+        doc.get_map("map").set(txn, "nested", Y.YArray([1, 2]))
+    updates = []
+    doc.observe_update_v1(lambda update, origin: updates.append(update))
+
+    fork = doc.fork()
+    assert fork.equal_state(doc) and fork.client_id != 42
+    assert fork.skip_gc and len(fork.get_text("text")) == 8
+    assert doc.fork(7).client_id == 7
+    assert copy.copy(doc).client_id == 42
+
+    # forks don't share any state
+    with fork.begin_transaction() as txn:
+        fork.get_text("text").extend(txn, "!")
+        fork.get_map("map")["nested"].append(txn, 3)
+    assert str(text) == "hello 🌍"
+    assert list(doc.get_map("map")["nested"]) == [1, 2]
+    assert not updates
+
+    # changes of both documents can be merged afterwards
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 0, ">")
+    Y.apply_update(doc, fork.missing_from(doc))
+    Y.apply_update(fork, doc.missing_from(fork))
+    assert fork.equal_state(doc)
+    assert str(text) == ">hello 🌍!"
+
+
+def test_debug_logger():
+    d1 = Y.YDoc(1)
+    d2 = Y.YDoc(2)
+    t1 = d1.get_text("test")
+    t2 = d2.get_text("test")
+    with d1.begin_transaction() as txn:
+        t1.extend(txn, "abc")
+    first = Y.encode_state_as_update(d1)
+    sv = Y.encode_state_vector(d1)
+    with d1.begin_transaction() as txn:
+        t1.extend(txn, "de")
+    second = Y.encode_state_as_update(d1, sv)
+
+    # concurrent edit of a peer, which has seen only the first update
+    Y.apply_update(d2, first)
+    with d2.begin_transaction() as txn:
+        t2.delete_range(txn, 1, 1)
+        t2.insert(txn, 0, "xyz")
+    concurrent = Y.encode_state_as_update(d2, sv)
+
+    logs = []
+    d3 = Y.YDoc(3)
+    d3.set_debug_logger(logs.append)
+    Y.apply_update(d3, first)
+    Y.apply_update(d3, concurrent)
+    Y.apply_update(d3, second)
+    assert logs == [
+        {"action": "integrate", "client": 1, "clock": 0, "length": 3},
+        {"action": "delete", "client": 1, "clock": 1, "length": 1},
+        {"action": "integrate", "client": 2, "clock": 0, "length": 3},
+        {"action": "integrate", "client": 1, "clock": 3, "length": 2},
+    ]
+    assert str(d3.get_text("test")) == "xyzacde"
+
+    # an update applied out of order has to wait for the changes it depends on
+    pending = []
+    d4 = Y.YDoc(4)
+    d4.set_debug_logger(pending.append)
+    Y.apply_update(d4, second)
+    assert pending == [{"action": "pending", "client": 1, "clock": 0, "length": 5}]
+
+    # removing the logger stops notifications
+    logs.clear()
+    d3.set_debug_logger(None)
+    Y.apply_update(d3, Y.encode_state_as_update(d1))
+    assert logs == []
+
+
+def test_transaction_origin():
+    remote = YDoc()
+    with remote.begin_transaction() as txn:
+        remote.get_text("test").extend(txn, "hello")
+    update = Y.encode_state_as_update(remote)
+
+    doc = YDoc()
+    text = doc.get_text("test")
+    origins = []
+    doc.observe_after_transaction(lambda e: origins.append(e.origin))
+
+    Y.apply_update(doc, update, "remote")
+    with doc.begin_transaction() as txn:
+        assert txn.origin is None
+        text.extend(txn, " world")
+    with doc.begin_transaction(origin=b"x") as txn:
+        assert txn.origin == b"x"
+        text.extend(txn, "!")
+    assert doc.transact(lambda txn: (text.extend(txn, "?"), txn.origin), origin=42) == (None, 42)
+    assert origins == ["remote", None, b"x", 42]
+
+    # origin doesn't leak into subsequent transactions
+    with doc.begin_transaction() as txn:
+        text.extend(txn, ".")
+    assert origins[-1] is None
+
+
+def test_read_transaction():
+    doc = YDoc()
+    text = doc.get_text("text")
+    array = doc.get_array("array")
+    m = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        array.append(txn, 1)
+        m.set(txn, "key", "value")
+    update = Y.encode_state_as_update(doc)
+
+    with doc.begin_read_transaction() as first, doc.begin_read_transaction() as second:
+        assert first.read_only and second.read_only
+        assert text.to_delta(first) == text.to_delta(second) == [{"insert": "hello"}]
+        for mutation in [
+            lambda: text.insert(first, 0, "x"),
+            lambda: text.delete_range(first, 0, 1),
+            lambda: array.append(first, 2),
+            lambda: array.delete(first, 0),
+            lambda: m.set(first, "key", "other"),
+            lambda: m.pop(first, "key"),
+            lambda: first.apply_v1(update),
+        ]:
+            with pytest.raises(Y.ReadOnlyTransactionException):
+                mutation()
+
+    assert str(text) == "hello"
+    assert list(array) == [1]
+    assert dict(m) == {"key": "value"}
+    with doc.begin_transaction() as txn:
+        assert not txn.read_only
+
+
+def test_read_transaction_overlapping_write():
+    doc = YDoc()
+    text = doc.get_text("text")
+    updates = []
+    doc.observe_update_v1(lambda update, origin: updates.append(update))
+    doc.keep_history(10)
+    sizes = []
+    doc.observe_after_transaction(lambda e: sizes.append(len(e.get_update())))
+
+    reader = doc.begin_read_transaction()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    sizes.clear()
+    del reader
+
+    # the read transaction doesn't publish changes committed while it was open again
+    assert len(updates) == 1
+    assert len(doc.history()) == 1
+    assert sizes == [2]
+    assert str(text) == "hello"
+
+
+def test_observe_update():
+    doc = YDoc()
+    text = doc.get_text("test")
+    v1 = []
+    v2 = []
+    sub_v1 = doc.observe_update_v1(lambda update, origin: v1.append((update, origin)))
+    sub_v2 = doc.observe_update_v2(lambda update, origin: v2.append((update, origin)))
+
+    with doc.begin_transaction(origin="local") as txn:
+        text.extend(txn, "hello")
+    # transactions which don't change the document don't produce updates
+    with doc.begin_transaction() as txn:
+        pass
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " world")
+
+    assert [origin for _, origin in v1] == ["local", None]
+    assert [origin for _, origin in v2] == ["local", None]
+
+    remote = YDoc()
+    for update, _ in v1:
+        Y.apply_update(remote, update)
+    assert str(remote.get_text("test")) == "hello world"
+    remote = YDoc()
+    for update, _ in v2:
+        Y.apply_update_v2(remote, update)
+    assert str(remote.get_text("test")) == "hello world"
+
+    doc.unobserve_update_v1(sub_v1)
+    doc.unobserve_update_v2(sub_v2)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+    assert len(v1) == len(v2) == 2
+
+    # unsubscribing from a document which was never observed is a no-op
+    YDoc().unobserve_update_v2(sub_v2)
+
+
+def test_pending_transaction():
+    doc = YDoc()
+    updates = []
+    doc.observe_update_v1(lambda update, origin: updates.append(update))
+    assert doc.pending_transaction() is None
+
+    # all changes made within a transaction produce a single update
+    with doc.begin_transaction() as txn:
+        assert doc.pending_transaction() is txn
+        text = doc.get_text("text")
+        text.extend(txn, "hello")
+        text.insert(txn, 0, ">")
+        assert str(text) == ">hello"
+        doc.get_array("array").append(txn, Y.YMap({"nested": [1, 2]}))
+        doc.get_map("map").set(txn, "key", "value")
+    assert doc.pending_transaction() is None
+    assert len(updates) == 1
+    remote = YDoc()
+    Y.apply_update(remote, updates[0])
+    assert remote.get_array("array").to_list(deep=True) == [{"nested": [1, 2]}]
+
+    assert doc.transact(lambda txn: doc.pending_transaction() is txn)
+    txn = doc.begin_transaction()
+    text.extend(txn, "!")
+    txn.commit()
+    assert len(updates) == 2
+    assert doc.pending_transaction() is txn
+    text.extend(txn, "!")
+    del txn
+    assert doc.pending_transaction() is None
+    assert len(updates) == 3
+
+
+def test_roots():
+    doc = Y.YDoc()
+    text = doc.get_text("text")
+    array = doc.get_array("array")
+    map = doc.get_map("map")
+    xml = doc.get_xml_element("xml")
+    doc.get_text("empty")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        array.append(txn, 1)
+        map.set(txn, "key", "value")
+        xml.push_xml_element(txn, "p")
+        assert doc.roots(txn) == [
+            ("array", "array"),
+            ("empty", "text"),
+            ("map", "map"),
+            ("text", "text"),
+            ("xml", "xml"),
+        ]
+
+    # kinds of remote roots are inferred from their contents
+    remote = Y.YDoc()
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    with remote.begin_transaction() as txn:
+        assert remote.roots(txn) == [
+            ("array", "array"),
+            ("map", "map"),
+            ("text", "text"),
+            ("xml", "xml"),
+        ]
+    restored = pickle.loads(pickle.dumps(doc))
+    with restored.begin_transaction() as txn:
+        assert [name for name, _ in restored.roots(txn)] == ["array", "map", "text", "xml"]
+
+    # contents deleted remotely don't tell the kind, until the root is accessed
+    with doc.begin_transaction() as txn:
+        array.delete(txn, 0)
+    remote = Y.YDoc()
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    with remote.begin_transaction() as txn:
+        assert ("array", None) in remote.roots(txn)
+        txn.get_array("array")
+        assert ("array", "array") in remote.roots(txn)
+
+
+def test_transact_exception():
+    doc = YDoc()
+    text = doc.get_text("test")
+    updates = []
+    doc.observe_after_transaction(lambda e: updates.append(e.get_update()))
+
+    def callback(txn):
+        text.extend(txn, "partial")
+        raise ValueError("failed")
+
+    with pytest.raises(ValueError, match="failed"):
+        doc.transact(callback)
+    # changes made before the exception are committed, and the document is usable again
+    assert str(text) == "partial"
+    assert len(updates) == 1
+    assert doc.transact(lambda txn: text.extend(txn, "!")) is None
+    assert str(text) == "partial!"
+
+
+def test_observer_exception():
+    doc = YDoc()
+    text = doc.get_text("test")
+    calls = []
+
+    def failing(event):
+        calls.append("failing")
+        raise KeyError("boom")
+
+    sub = text.observe(failing)
+    doc.observe_after_transaction(lambda e: calls.append("after"))
+
+    with pytest.raises(KeyError, match="boom"):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "a")
+    # remaining callbacks are still called and the changes are committed
+    assert calls == ["failing", "after"]
+    assert str(text) == "a"
+
+    with pytest.raises(KeyError):
+        doc.transact(lambda txn: text.extend(txn, "b"))
+    txn = doc.begin_transaction()
+    text.extend(txn, "c")
+    with pytest.raises(KeyError):
+        txn.commit()
+    del txn
+
+    remote = YDoc()
+    sub = remote.get_text("test").observe(failing)
+    with pytest.raises(KeyError):
+        Y.apply_update(remote, Y.encode_state_as_update(doc))
+    assert str(remote.get_text("test")) == "abc"
+
+    text.unobserve(sub)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "d")
+    assert str(text) == "abcd"
+
+    # commits made internally by other methods raise exceptions themselves, so they aren't left
+    # behind for later calls
+    def fail_once(event):
+        if "failed" not in calls:
+            calls.append("failed")
+            raise KeyError("boom")
+
+    doc.observe_after_transaction(fail_once)
+    with pytest.raises(KeyError):
+        doc.get_text("other")
+    assert "failed" in calls
+    doc.current_clock()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "e")
+    assert str(text) == "abcde"
+
+
+def test_doc_observe_deep():
+    doc = YDoc()
+    text = doc.get_text("text")
+    events = []
+
+    def callback(e):
+        events.extend((type(event).__name__, event.path()) for event in e)
+
+    sub = doc.observe_deep(callback)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        # roots created after subscribing are observed as well
+        doc.get_map("map").set(txn, "nested", Y.YArray([1]))
+    assert sorted(events) == [("YMapEvent", []), ("YTextEvent", [])]
+
+    events.clear()
+    with doc.begin_transaction() as txn:
+        txn.get_map("map")["nested"].append(txn, 2)
+        txn.get_array("array").append(txn, 3)
+    assert sorted(events) == [("YArrayEvent", []), ("YArrayEvent", ["nested"])]
+
+    # remote roots are observed once they are accessed
+    remote = YDoc()
+    remote_events = []
+    remote.observe_deep(lambda e: remote_events.extend(event.target for event in e))
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    remote_map = remote.get_map("map")
+    with doc.begin_transaction() as txn:
+        doc.get_map("map").set(txn, "key", "value")
+    Y.apply_update(remote, Y.encode_state_as_update(doc, Y.encode_state_vector(remote)))
+    assert len(remote_events) == 1
+    assert remote_events[0] == remote_map
+
+    doc.unobserve_deep(sub)
+    events.clear()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+    assert events == []
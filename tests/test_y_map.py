@@ -1,3 +1,5 @@
+import gc
+from datetime import datetime
 from copy import deepcopy
 import json
 import pytest
@@ -13,7 +15,7 @@ def test_get():
 
     # Put user info into the map.
     with d.begin_transaction() as txn:
-        m.update(txn, {"username": "John", "online": True})
+        m.update(txn, {"username": "John", "online": True, "avatar": None})
 
     # Extract the information from the map.
     assert m.get("username") == "John"
@@ -22,6 +24,8 @@ def test_get():
     assert m.get("secretIdentity", "basic") == "basic"
     # Ensure that nonexistant keys without default values return None.
     assert m.get("SocialSecurityNumber") is None
+    # Stored `None` values are returned instead of the default.
+    assert m.get("avatar", "default.png") is None
     # Ensure that indexing a non_existant key with bracket notation produces `KeyError`
     with pytest.raises(KeyError):
         m["doesn't exist"]
@@ -55,7 +59,81 @@ def test_to_json():
         integrated.update(txn, contents)
     # ensure that it works with python json
     assert json.loads(integrated.to_json()) == contents
+    assert json.loads(prelim.to_json()) == contents
 
+    # strings and keys are escaped, including the ones of preliminary types
+    contents = {'quoted "key"': ['back\\slash', "new\nline"], "text": 'say "hi"'}
+    prelim = Y.YMap({**contents, "text": Y.YText(contents["text"])})
+    assert json.loads(prelim.to_json()) == contents
+    with doc.begin_transaction() as txn:
+        integrated.set(txn, "nested", prelim)
+    assert json.loads(prelim.to_json()) == contents
+
+
+
+def test_to_json_value():
+    doc = Y.YDoc()
+    x = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        x.update(
+            txn,
+            {
+                "list": Y.YArray([1, Y.YText("nested")]),
+                "blob": b"\x00\xff",
+                "when": datetime(2022, 1, 2, 3, 4, 5),
+                "map": Y.YMap({"n": 1.5}),
+            },
+        )
+    expected = {
+        "list": [1, "nested"],
+        "blob": "AP8=",
+        "when": "2022-01-02T03:04:05",
+        "map": {"n": 1.5},
+    }
+    assert x.to_json_value() == expected
+    assert json.loads(json.dumps(x.to_json_value())) == expected
+    assert x["list"].to_json_value() == [1, "nested"]
+    assert Y.YArray([Y.YMap({"a": b""})]).to_json_value() == [{"a": ""}]
+
+
+def test_to_python():
+    doc = Y.YDoc()
+    x = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        x.update(
+            txn,
+            {
+                "list": Y.YArray([1, Y.YMap({"text": Y.YText("nested")})]),
+                "blob": b"\x00\xff",
+                "when": datetime(2022, 1, 2, 3, 4, 5),
+            },
+        )
+    expected = {
+        "list": [1, {"text": "nested"}],
+        "blob": b"\x00\xff",
+        "when": datetime(2022, 1, 2, 3, 4, 5),
+    }
+    assert x.to_python() == expected
+    assert x["list"].to_python() == [1, {"text": "nested"}]
+    assert Y.YArray([Y.YMap({"a": Y.YArray([b"x"])})]).to_python() == [{"a": [b"x"]}]
+
+
+def test_to_dict():
+    doc = Y.YDoc()
+    x = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        x.update(txn, {"a": 1, "text": Y.YText("hi"), "map": YMap({"b": True})})
+
+    entries = x.to_dict()
+    assert type(entries) == dict
+    assert sorted(entries) == ["a", "map", "text"]
+    assert entries["a"] == 1
+    assert isinstance(entries["text"], Y.YText) and isinstance(entries["map"], YMap)
+    assert x.to_dict(deep=True) == {"a": 1, "text": "hi", "map": {"b": True}}
+
+    prelim = YMap({"a": 1, "map": YMap({"b": True})})
+    assert isinstance(prelim.to_dict()["map"], YMap)
+    assert prelim.to_dict(deep=True) == {"a": 1, "map": {"b": True}}
 
 def test_update():
     doc = Y.YDoc()
@@ -84,6 +162,52 @@ def test_update():
         with doc.begin_transaction() as txn:
             ymap.update(txn, [1])
 
+    # Existing keys are overridden, nested types are integrated
+    with doc.begin_transaction() as txn:
+        ymap.update(txn, {"username": "John", "tags": Y.YArray(["a"]), "bio": Y.YText("hi")})
+    assert ymap["username"] == "John"
+    assert ymap["user_id"] == 1
+    assert list(ymap["tags"]) == ["a"]
+    assert str(ymap["bio"]) == "hi"
+
+    # Any mapping works, including another YMap
+    other = doc.get_map("other")
+    with doc.begin_transaction() as txn:
+        other.update(txn, doc.get_map("dict"))
+    assert dict(other) == dict_vals
+
+    # Invalid entries leave the map unchanged
+    with pytest.raises(TypeError):
+        with doc.begin_transaction() as txn:
+            other.update(txn, [("user_id", 2), (3, "x")])
+    with pytest.raises(TypeError):
+        with doc.begin_transaction() as txn:
+            other.update(txn, {"user_id": 2, 3: "x"})
+    assert dict(other) == dict_vals
+
+
+def test_pop_nested():
+    doc = Y.YDoc()
+    x = doc.get_map("test")
+    with doc.begin_transaction() as txn:
+        x.set(txn, "map", Y.YMap({"a": 1}))
+        x.set(txn, "array", Y.YArray([1, Y.YArray(["b"])]))
+        x.set(txn, "text", Y.YText("hello"))
+
+    # popped shared types keep their contents and can be inserted again
+    with doc.begin_transaction() as txn:
+        popped_map = x.pop(txn, "map")
+        popped_array = x.pop(txn, "array")
+        popped_text = x.pop(txn, "text")
+    assert len(x) == 0
+    assert popped_map.prelim and dict(popped_map) == {"a": 1}
+    assert popped_array.prelim and list(popped_array) == [1, ["b"]]
+    assert popped_text.prelim and str(popped_text) == "hello"
+
+    with doc.begin_transaction() as txn:
+        x.set(txn, "moved", popped_map)
+    assert dict(x["moved"]) == {"a": 1}
+
 
 def test_set_nested():
     d1 = Y.YDoc()
@@ -97,6 +221,58 @@ def test_set_nested():
     assert {k : dict(v) for k, v in x.items()} == {"key": {"a": "A", "b": "B"}}
 
 
+def test_set_nested_prelims():
+    doc = Y.YDoc()
+    x = doc.get_map("test")
+    body = Y.YText("hello")
+    with doc.begin_transaction() as txn:
+        x.set(txn, "body", body)
+        x.set(txn, "tree", Y.YArray([1, Y.YMap({"leaf": Y.YText("deep")})]))
+
+    # the inserted instance gets integrated and is the same as the one read back
+    assert not body.prelim and not x["body"].prelim
+    with doc.begin_transaction() as txn:
+        body.extend(txn, " world")
+        x["tree"][1]["leaf"].extend(txn, "er")
+    assert json.loads(x.to_json()) == {"body": "hello world", "tree": [1, {"leaf": "deeper"}]}
+
+    # integrated types can't be inserted again, and shared types can't be nested in plain values
+    for value in [body, Y.YArray([x["tree"]]), [Y.YText("plain")]]:
+        with pytest.raises(Exception):
+            with doc.begin_transaction() as txn:
+                x.set(txn, "invalid", value)
+    assert "invalid" not in x
+
+
+def test_nested_prelim_tree():
+    # a whole tree of preliminary types can be built before inserting it in one go
+    tree = Y.YMap(
+        {
+            "items": Y.YArray([Y.YMap({"x": 1}), {"plain": [1, {"a": 2}]}]),
+            "title": Y.YText("todo"),
+        }
+    )
+    expected = {"items": [{"x": 1}, {"plain": [1, {"a": 2}]}], "title": "todo"}
+    assert json.loads(tree.to_json()) == expected
+
+    doc = Y.YDoc()
+    root = doc.get_map("root")
+    updates = []
+    doc.observe_update_v1(lambda update, origin: updates.append(update))
+    with doc.begin_transaction() as txn:
+        root.set(txn, "tree", tree)
+    assert len(updates) == 1
+    assert root.to_dict(deep=True) == {"tree": expected}
+
+    items = root["tree"]["items"]
+    assert isinstance(items, Y.YArray) and isinstance(items[0], Y.YMap)
+    with doc.begin_transaction() as txn:
+        items[0].set(txn, "y", 2)
+    remote = Y.YDoc()
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    assert remote.get_map("root").to_dict(deep=True)["tree"]["items"][0] == {"x": 1, "y": 2}
+
+
 def test_pop():
     d1 = Y.YDoc()
     x = d1.get_map("test")
@@ -106,11 +282,12 @@ def test_pop():
     value = x["key"]
     assert length == 1
     assert value == "value1"
-    d1.transact(lambda txn: x.pop(txn, "key"))
+    assert d1.transact(lambda txn: x.pop(txn, "key")) == "value1"
 
     with pytest.raises(KeyError):
         with d1.begin_transaction() as txn:
             x.pop(txn, "does not exist")
+    with d1.begin_transaction() as txn:
         assert x.pop(txn, "does not exist", "fallback") == "fallback"
     length = len(x)
     value = x.get("key")
@@ -124,6 +301,30 @@ def test_pop():
     assert value == "value2"
 
 
+
+def test_contains_del_and_clear():
+    prelim = YMap({"a": 1, "b": 2})
+    assert "a" in prelim and "c" not in prelim and 1 not in prelim
+    del prelim["a"]
+    with pytest.raises(KeyError):
+        del prelim["a"]
+    assert dict(prelim.items()) == {"b": 2}
+
+    d1 = Y.YDoc()
+    x = d1.get_map("test")
+    with d1.begin_transaction() as txn:
+        x.update(txn, {"a": 1, "b": YMap({"c": 3})})
+    assert "a" in x and "b" in x and "c" not in x
+    with pytest.raises(TypeError):
+        del x["a"]
+
+    d1.transact(x.clear)
+    assert len(x) == 0 and "a" not in x
+
+    with d1.begin_transaction() as txn:
+        prelim.clear(txn)
+    assert len(prelim) == 0
+
 def test_items_view():
     d = Y.YDoc()
     m = d.get_map("test")
@@ -186,6 +387,16 @@ def test_keys_values():
     assert len(keys) == 4
     assert len(values) == 4
 
+    # Views and iterators keep working after the map they came from is gone
+    items = d.get_map("test").items()
+    key_iter = iter(d.get_map("test"))
+    values = Y.YMap({"x": 1}).values()
+    gc.collect()
+    assert dict(items) == {"a": 1, "b": 2, "c": 3, "d": 4}
+    assert sorted(key_iter) == ["a", "b", "c", "d"]
+    assert list(values) == [1]
+    assert 1 in values
+
 
 def test_observer():
     d1 = Y.YDoc()
@@ -237,6 +448,36 @@ def test_observer():
     assert entries == None
 
 
+def test_observer_with_snapshot():
+    d1 = Y.YDoc()
+    x = d1.get_map("test")
+    with d1.begin_transaction() as txn:
+        x.set(txn, "a", 1)
+        x.set(txn, "b", [1, 2])
+
+    events = []
+    sub = x.observe(events.append, with_snapshot=True)
+    with d1.begin_transaction() as txn:
+        x.set(txn, "a", 2)
+        x.pop(txn, "b")
+    with d1.begin_transaction() as txn:
+        x.set(txn, "c", 3)
+
+    # snapshots stay readable after the callbacks have finished
+    assert events[0].before == {"a": 1, "b": [1, 2]}
+    assert events[1].before == {"a": 2}
+    assert dict(x) == {"a": 2, "c": 3}
+
+    # edits made inside nested types are reflected by the next snapshot
+    with d1.begin_transaction() as txn:
+        x.set(txn, "child", Y.YMap({"x": 1}))
+    with d1.begin_transaction() as txn:
+        x["child"].set(txn, "x", 2)
+    with d1.begin_transaction() as txn:
+        x.pop(txn, "c")
+    assert events[3].before == {"a": 2, "c": 3, "child": {"x": 2}}
+
+
 def test_deep_observe():
     """
     Ensure that changes to elements inside the array trigger a callback.
@@ -267,6 +508,79 @@ def test_deep_observe():
     assert events is None
 
 
+def test_deep_observe_nested_paths():
+    doc = Y.YDoc()
+    root = doc.get_map("root")
+    with doc.begin_transaction() as txn:
+        root.set(
+            txn,
+            "a",
+            YMap({"b": YMap({"c": YMap({"x": 1})}), "list": Y.YArray([YMap({})])}),
+        )
+
+    mirror = json.loads(root.to_json())
+    changes = []
+
+    def callback(events):
+        changes.append([(e.path(), e.keys) for e in events])
+
+    sub = root.observe_deep(callback)
+    for i in range(5):
+        with doc.begin_transaction() as txn:
+            root.set(txn, "top", i)
+            root["a"]["list"][0].set(txn, "y", i)
+            root["a"]["b"]["c"].set(txn, "x", i)
+            root["a"].set(txn, "z", i)
+        # events are passed in document order, with full paths from the observed map
+        assert [path for path, _ in changes[-1]] == [
+            [],
+            ["a"],
+            ["a", "b", "c"],
+            ["a", "list", 0],
+        ]
+    path, keys = changes[0][2]
+    assert keys == {"x": {"action": "update", "oldValue": 1, "newValue": 0}}
+
+    # a mirror can be kept up to date by following the paths
+    for events in changes:
+        for path, keys in events:
+            node = mirror
+            for segment in path:
+                node = node[segment]
+            for key, change in keys.items():
+                node[key] = change["newValue"]
+    assert mirror == json.loads(root.to_json())
+
+
+def test_observe_deep_flag():
+    doc = Y.YDoc()
+    container = doc.get_map("container")
+    with doc.begin_transaction() as txn:
+        container.set(txn, "inner", Y.YArray([1]))
+
+    paths = []
+
+    def callback(events):
+        paths.append(sorted(e.path() for e in events))
+
+    sub = container.observe(callback, deep=True)
+    with doc.begin_transaction() as txn:
+        container["inner"].append(txn, 2)
+        container.set(txn, "other", 1)
+
+    assert paths == [[[], ["inner"]]]
+
+    container.unobserve(sub)
+    with doc.begin_transaction() as txn:
+        container["inner"].append(txn, 3)
+    assert len(paths) == 1
+
+    with pytest.raises(ValueError):
+        container.observe(callback, with_snapshot=True, deep=True)
+    with pytest.raises(Exception):
+        Y.YMap({}).observe(callback, deep=True)
+
+
 def test_borrow_issue():
     doc = Y.YDoc()
     wrapper = doc.get_array("wrapper")
@@ -274,3 +588,135 @@ def test_borrow_issue():
 
     with doc.begin_transaction() as txn:
         wrapper.append(txn, inner)
+
+
+def test_transact():
+    doc = Y.YDoc()
+    m = doc.get_map("test")
+
+    def edit(txn, target):
+        target.set(txn, "a", 1)
+        target.update(txn, {"b": 2, "c": 3})
+        target.pop(txn, "c")
+        return len(target)
+
+    assert m.transact(doc, edit) == 2
+    assert dict(m) == {"a": 1, "b": 2}
+
+    # changes are committed even if the callback fails
+    def failing_edit(txn, target):
+        target.set(txn, "d", 4)
+        raise RuntimeError("boom")
+
+    with pytest.raises(RuntimeError):
+        m.transact(doc, failing_edit)
+    other = Y.YDoc()
+    Y.apply_update(other, Y.encode_state_as_update(doc))
+    assert dict(other.get_map("test")) == {"a": 1, "b": 2, "d": 4}
+
+    with pytest.raises(Exception):
+        YMap({}).transact(doc, edit)
+
+
+def test_msgpack():
+    doc = Y.YDoc()
+    m = doc.get_map("test")
+    with doc.begin_transaction() as txn:
+        m.update(txn, {"a": 1, "nested": {"list": [1, "two", None]}, "f": 0.25})
+        payload = m.to_msgpack(txn)
+
+    prelim = YMap.from_msgpack(payload)
+    assert prelim.prelim
+    other = Y.YDoc()
+    restored = other.get_map("test")
+    with other.begin_transaction() as txn:
+        restored.update(txn, dict(prelim.items()))
+    assert json.loads(restored.to_json()) == json.loads(m.to_json())
+
+    # binary values map to the MessagePack bin type
+    blob = b"\x81\xa4blob\xc4\x03abc"
+    prelim = YMap.from_msgpack(blob)
+    assert prelim["blob"] == bytearray(b"abc")
+    with doc.begin_transaction() as txn:
+        assert prelim.to_msgpack(txn) == blob
+
+    with pytest.raises(ValueError, match="Unexpected end"):
+        YMap.from_msgpack(b"\xdf\xff\xff\xff\xff")
+    with pytest.raises(ValueError, match="nested deeper"):
+        YMap.from_msgpack(b"\x81\xa1a" * 200 + b"\xc0")
+
+
+def test_eq():
+    d1 = Y.YDoc()
+    d2 = Y.YDoc()
+    m1 = d1.get_map("test")
+    m2 = d2.get_map("test")
+    with d1.begin_transaction() as txn:
+        m1.update(txn, {"a": 1, "b": Y.YArray(["x"])})
+    with d2.begin_transaction() as txn:
+        m2.update(txn, {"b": Y.YArray(["x"]), "a": 1})
+    assert m1 == m2
+    assert m1 == {"a": 1, "b": ["x"]}
+    assert m1 != {"a": 1}
+    assert YMap({"t": Y.YText("hi")}) == YMap({"t": "hi"})
+    assert YMap({"a": 1}) != YMap({"a": 2})
+    assert m1 != [1]
+    with pytest.raises(TypeError):
+        hash(m1)
+
+
+def test_setdefault():
+    doc = Y.YDoc()
+    m = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        m.set(txn, "existing", 1)
+        assert m.setdefault(txn, "existing", 2) == 1
+        assert m.setdefault(txn, "new", 2) == 2
+        assert m.setdefault(txn, "none") is None
+        assert "none" in m
+        nested = m.setdefault(txn, "nested", YMap({}))
+        assert isinstance(nested, YMap) and not nested.prelim
+        nested.set(txn, "key", "value")
+        assert m.setdefault(txn, "nested", YMap({})).to_python() == {"key": "value"}
+    assert m.to_python() == {"existing": 1, "new": 2, "none": None, "nested": {"key": "value"}}
+
+    prelim = YMap({"a": 1})
+    with doc.begin_transaction() as txn:
+        assert prelim.setdefault(txn, "a", 2) == 1
+        assert prelim.setdefault(txn, "b", [1]) == [1]
+    assert dict(prelim.items()) == {"a": 1, "b": [1]}
+
+
+def test_get_type():
+    doc = Y.YDoc()
+    map = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        map.update(
+            txn,
+            {
+                "text": Y.YText("hello"),
+                "array": Y.YArray([1]),
+                "map": YMap({}),
+                "number": 1,
+                "list": [1, 2],
+                "none": None,
+            },
+        )
+    kinds = {key: map.get_type(key) for key in map}
+    assert kinds == {
+        "text": "text",
+        "array": "array",
+        "map": "map",
+        "number": "primitive",
+        "list": "primitive",
+        "none": "primitive",
+    }
+    with pytest.raises(KeyError):
+        map.get_type("missing")
+
+    prelim = YMap({"text": Y.YText("a"), "list": [1], "nested": YMap({})})
+    assert prelim.get_type("text") == "text"
+    assert prelim.get_type("list") == "primitive"
+    assert prelim.get_type("nested") == "map"
+    with pytest.raises(KeyError):
+        prelim.get_type("missing")
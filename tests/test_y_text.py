@@ -1,4 +1,5 @@
 from test_helper import exchange_updates
+import pytest
 import y_py as Y
 from y_py import YText, YTextEvent
 
@@ -17,6 +18,287 @@ def test_to_string():
         assert test.__repr__() == f"YText({expected})"
 
 
+def test_getitem():
+    content = "hello world"
+    d = Y.YDoc(offset_kind="utf32")
+    text = d.get_text("test")
+    with d.begin_transaction() as txn:
+        text.extend(txn, content)
+    for test in [text, YText(content)]:
+        assert test[4] == "o"
+        assert test[-1] == "d"
+        assert test[2:5] == "llo"
+        assert test[-5:] == "world"
+        assert test[::-2] == content[::-2]
+        assert test[5:100] == " world"
+        assert test[100:] == ""
+        with pytest.raises(IndexError):
+            test[11]
+        with pytest.raises(IndexError):
+            test[-12]
+
+    # indexes follow the offset kind of a document
+    for offset_kind, expected in [
+        ("utf8", ["a", "é", "é", "ö", "ö", "b"]),
+        ("utf16", ["a", "é", "ö", "b"]),
+        ("utf32", ["a", "é", "ö", "b"]),
+    ]:
+        d = Y.YDoc(offset_kind=offset_kind)
+        text = d.get_text("test")
+        with d.begin_transaction() as txn:
+            text.extend(txn, "aéöb")
+        assert [text[i] for i in range(len(text))] == expected
+        assert text[:] == "aéöb"
+        assert text[1:len(text) - 1] == "éö"
+
+    d = Y.YDoc(offset_kind="utf16")
+    text = d.get_text("test")
+    with d.begin_transaction() as txn:
+        text.extend(txn, "a😀b")
+    assert text[1:3] == "😀"
+    assert text[2:] == "b"
+
+    # embeds take a single unit
+    for offset_kind in ["utf8", "utf16", "utf32"]:
+        d = Y.YDoc(offset_kind=offset_kind)
+        text = d.get_text("test")
+        with d.begin_transaction() as txn:
+            text.extend(txn, "ab")
+            text.insert_embed(txn, 1, {"image": "cat.png"})
+        assert len(text) == 3
+        assert [text[i] for i in range(3)] == ["a", {"image": "cat.png"}, "b"]
+        assert text[:] == "ab"
+
+
+def test_find():
+    content = "hello world"
+    d = Y.YDoc()
+    text = d.get_text("test")
+    with d.begin_transaction() as txn:
+        text.extend(txn, content)
+    for test in [text, YText(content)]:
+        assert "world" in test
+        assert "xyz" not in test
+        for sub, start in [("o", None), ("o", 5), ("o", -3), ("", 11), ("", 12), ("xyz", None)]:
+            expected = content.find(sub) if start is None else content.find(sub, start)
+            assert test.find(sub, start) == expected
+        assert test.index("world") == 6
+        with pytest.raises(ValueError):
+            test.index("o", 8)
+
+    # positions are reported in units of the document's offset kind
+    d = Y.YDoc(offset_kind="utf16")
+    text = d.get_text("test")
+    with d.begin_transaction() as txn:
+        text.extend(txn, "😀 héllo héllo")
+    assert text.find("héllo") == 3
+    assert text.find("héllo", 4) == 9
+    with d.begin_transaction() as txn:
+        text.delete_range(txn, text.find("héllo"), 6)
+    assert str(text) == "😀 héllo"
+
+    # embeds count into positions, which can be passed back to editing methods
+    with d.begin_transaction() as txn:
+        text.insert_embed(txn, 2, {"image": "cat.png"})
+    assert text.find("h") == 4
+    assert "😀 " not in text and " h" in text
+    with d.begin_transaction() as txn:
+        text.delete(txn, text.index("h"))
+    assert str(text) == "😀 éllo"
+
+
+def test_to_delta():
+    d = Y.YDoc()
+    text = d.get_text("test")
+    with d.begin_transaction() as txn:
+        assert text.to_delta(txn) == []
+        text.extend(txn, "hello world")
+        text.format(txn, 0, 5, {"bold": True})
+        text.insert_embed(txn, 5, {"image": "cat.png"}, {"width": "100px"})
+        text.format(txn, 7, 3, {"italic": True})
+        delta = text.to_delta(txn)
+    assert delta == [
+        {"insert": "hello", "attributes": {"bold": True}},
+        {"insert": {"image": "cat.png"}, "attributes": {"width": "100px"}},
+        {"insert": " "},
+        {"insert": "wor", "attributes": {"italic": True}},
+        {"insert": "ld"},
+    ]
+
+    with d.begin_transaction() as txn:
+        assert YText("prelim").to_delta(txn) == [{"insert": "prelim"}]
+
+
+def test_apply_delta():
+    d = Y.YDoc()
+    text = d.get_text("test")
+    with d.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        text.format(txn, 6, 5, {"italic": True})
+        text.apply_delta(
+            txn,
+            [
+                {"retain": 5, "attributes": {"bold": True}},
+                {"insert": {"image": "cat.png"}, "attributes": {"width": "100px"}},
+                {"delete": 1},
+                {"retain": 5},
+                {"insert": "!"},
+            ],
+        )
+        delta = text.to_delta(txn)
+    assert delta == [
+        {"insert": "hello", "attributes": {"bold": True}},
+        {"insert": {"image": "cat.png"}, "attributes": {"width": "100px"}},
+        {"insert": "world", "attributes": {"italic": True}},
+        {"insert": "!"},
+    ]
+
+    # a delta produced by `to_delta` recreates the same content
+    d2 = Y.YDoc()
+    copy = d2.get_text("test")
+    d2.transact(lambda txn: copy.apply_delta(txn, delta))
+    with d2.begin_transaction() as txn:
+        assert copy.to_delta(txn) == delta
+
+    malformed = [
+        ["insert"],
+        [{"insert": "a", "delete": 1}],
+        [{"delete": 1, "attributes": {"bold": True}}],
+        [{"retain": -1}],
+        [{"insert": "a", "attributes": "bold"}],
+        [{}],
+    ]
+    for ops in malformed:
+        with d.begin_transaction() as txn:
+            with pytest.raises(TypeError):
+                text.apply_delta(txn, [{"insert": "unchanged"}] + ops)
+    assert str(text) == "helloworld!"
+
+    with d.begin_transaction() as txn:
+        with pytest.raises(IndexError):
+            text.apply_delta(txn, [{"retain": 100}])
+        # ranges are checked against the length the text has once preceding ops are applied
+        for ops in [
+            [{"insert": "xyz"}, {"delete": 3}, {"retain": 10}],
+            [{"delete": 5}, {"retain": 8}],
+            [{"retain": 1}, {"retain": 2 ** 32 - 1}],
+        ]:
+            with pytest.raises(IndexError):
+                text.apply_delta(txn, ops)
+        assert str(text) == "helloworld!"
+        text.apply_delta(txn, [{"insert": "xyz"}, {"delete": 3}, {"retain": 9}])
+        assert str(text) == "xyzloworld!"
+        with pytest.raises(Exception):
+            YText("prelim").apply_delta(txn, [{"insert": "a"}])
+
+
+def test_sticky_index():
+    d1 = Y.YDoc(1)
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        after = text.sticky_index(txn, 5)
+        before = text.sticky_index(txn, 5, -1)
+        end = text.sticky_index(txn, 11)
+        start = text.sticky_index(txn, 0, -1)
+        with pytest.raises(IndexError):
+            text.sticky_index(txn, 12)
+
+    # positions are sent to other peers as bytes
+    encoded = Y.encode_relative_position(after)
+    assert Y.decode_relative_position(encoded) == after
+    with pytest.raises(ValueError):
+        Y.decode_relative_position(b"\x05")
+    with pytest.raises(TypeError):
+        hash(after)
+
+    d2 = Y.YDoc(2)
+    remote = d2.get_text("test")
+    exchange_updates([d1, d2])
+    with d2.begin_transaction() as txn:
+        remote.insert(txn, 0, "oh, ")
+        remote.insert(txn, 9, "!")
+    exchange_updates([d1, d2])
+
+    assert str(text) == "oh, hello! world"
+    with d1.begin_transaction() as txn:
+        # inserts made exactly at the position follow its association
+        assert text.resolve_index(txn, after) == 10
+        assert text.resolve_index(txn, before) == 9
+        assert text.resolve_index(txn, end) == 16
+        assert text.resolve_index(txn, start) == 0
+    with d2.begin_transaction() as txn:
+        assert remote.resolve_index(txn, Y.decode_relative_position(encoded)) == 10
+
+    # deleted anchors resolve to where they used to be until they get garbage collected
+    d3 = Y.YDoc(skip_gc=True)
+    text = d3.get_text("test")
+    with d3.begin_transaction() as txn:
+        text.extend(txn, "hello 😀 world")
+        emoji = text.sticky_index(txn, 6)
+        text.delete_range(txn, 4, 6)
+    with d3.begin_transaction() as txn:
+        assert str(text) == "hell world"
+        assert text.resolve_index(txn, emoji) == 4
+        with pytest.raises(Exception):
+            YText("prelim").sticky_index(txn, 0)
+
+
+def test_nested_sticky_index():
+    d1 = Y.YDoc(1)
+    root = d1.get_text("root")
+    with d1.begin_transaction() as txn:
+        root.extend(txn, "root")
+        d1.get_map("map").update(
+            txn, {"a": YText(), "b": YText("other"), "empty": YText()}
+        )
+        a = d1.get_map("map")["a"]
+        a.extend(txn, "x")
+        a.insert(txn, 0, "nested ")
+        end = a.sticky_index(txn, len(a))
+        root_end = root.sticky_index(txn, len(root))
+        with pytest.raises(ValueError):
+            d1.get_map("map")["empty"].sticky_index(txn, 0)
+    # positions at the end of a nested text are anchored to the item holding it
+    assert repr(end).startswith("StickyIndex(type=")
+    assert repr(root_end) == 'StickyIndex(name="root", assoc=0)'
+
+    d2 = Y.YDoc(2)
+    exchange_updates([d1, d2])
+    remote = d2.get_map("map")["a"]
+    with d2.begin_transaction() as txn:
+        remote.extend(txn, "!?")
+    exchange_updates([d1, d2])
+    with d2.begin_transaction() as txn:
+        position = Y.decode_relative_position(Y.encode_relative_position(end))
+        assert remote.resolve_index(txn, position) == len("nested x!?")
+        # positions don't resolve within other texts
+        assert d2.get_map("map")["b"].resolve_index(txn, position) is None
+        assert d2.get_text("root").resolve_index(txn, position) is None
+        assert remote.resolve_index(txn, root_end) is None
+        assert d2.get_text("root").resolve_index(txn, root_end) == 4
+
+
+def test_concurrent_inserts_at_cursor():
+    docs = [Y.YDoc(1), Y.YDoc(2)]
+    texts = [doc.get_text("test") for doc in docs]
+    with docs[0].begin_transaction() as txn:
+        texts[0].extend(txn, "ab")
+    exchange_updates(docs)
+
+    with docs[0].begin_transaction() as txn:
+        cursors = [texts[0].sticky_index(txn, 1, assoc) for assoc in [-1, 0]]
+    for doc, text, chunk in zip(docs, texts, ["11", "22"]):
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 1, chunk)
+    exchange_updates(docs)
+
+    # concurrent inserts are ordered the same way by every peer, while cursors keep their side
+    assert str(texts[0]) == str(texts[1]) == "a1122b"
+    with docs[0].begin_transaction() as txn:
+        assert [texts[0].resolve_index(txn, cursor) for cursor in cursors] == [1, 5]
+
+
 def test_inserts():
     d1 = Y.YDoc()
     x = d1.get_text("test")
@@ -34,6 +316,17 @@ def test_inserts():
 
     assert value == expected
 
+    with d2.begin_transaction() as txn:
+        for insert in [
+            lambda: x.insert(txn, 0, 1),
+            lambda: x.extend(txn, None),
+            lambda: x.insert_many(txn, 0, ["a", b"b"]),
+        ]:
+            with pytest.raises(TypeError, match="chunk must be str, got"):
+                insert()
+        x.extend(txn, "\x00")
+    assert str(x) == expected + "\x00"
+
 
 def test_deletes():
     d1 = Y.YDoc()
@@ -128,7 +421,7 @@ def test_drop_sub_id():
     x = d.get_text("test")
 
     def register_callback(x, callback):
-        # The subscription_id `i` is dropped here
+        # The subscription `i` is dropped here, which cancels the callback
         i = x.observe(callback)
 
     register_callback(x, callback)
@@ -136,8 +429,40 @@ def test_drop_sub_id():
     with d.begin_transaction() as txn:
         x.insert(txn, 0, "abcd")
 
-    assert str(target) == str(x)
-    assert delta == [{"insert": "abcd"}]
+    assert target is None
+    assert delta is None
+
+    # a handle that was already unobserved, or outlives its document, is dropped safely
+    sub = x.observe(callback)
+    x.unobserve(sub)
+    del sub
+    sub = x.observe(callback)
+    del d, x
+    del sub
+
+
+def test_subscription_handle():
+    d = Y.YDoc()
+    x = d.get_text("test")
+    deltas = []
+    events = []
+
+    with x.observe(lambda e: deltas.append(e.delta)) as sub:
+        assert isinstance(sub.id, int)
+        with d.begin_transaction() as txn:
+            x.insert(txn, 0, "abc")
+
+    deep = x.observe_deep(events.append)
+    with d.begin_transaction() as txn:
+        x.insert(txn, 3, "def")
+    deep.drop()
+    deep.drop()
+    with d.begin_transaction() as txn:
+        x.insert(txn, 6, "ghi")
+
+    assert deltas == [[{"insert": "abc"}]]
+    assert len(events) == 1
+    assert str(x) == "abcdefghi"
 
 
 def test_delta_embed_attributes():
@@ -173,12 +498,15 @@ def test_formatting():
 
     delta = None
     target = None
+    attributes_changed = None
 
     def callback(e):
         nonlocal delta
         nonlocal target
+        nonlocal attributes_changed
         delta = e.delta
         target = e.target
+        attributes_changed = e.attributes_changed
 
     sub = text.observe(callback)
 
@@ -190,11 +518,23 @@ def test_formatting():
         {"insert": "styl", "attributes": {"bold": True}},
         {"insert": "ish"},
     ]
+    assert attributes_changed == []
 
     with d1.begin_transaction() as txn:
         text.format(txn, 4, 7, {"bold": True})
 
     assert delta == [{"retain": 4}, {"retain": 3, "attributes": {"bold": True}}]
+    assert attributes_changed == [{"retain": 3, "attributes": {"bold": True}}]
+
+    with d1.begin_transaction() as txn:
+        text.format(txn, 0, 2, {"bold": None})
+        text.delete_range(txn, 2, 1)
+
+    assert delta == [
+        {"retain": 2, "attributes": {"bold": None}},
+        {"delete": 1},
+    ]
+    assert attributes_changed == [{"retain": 2, "attributes": {"bold": None}}]
 
     text.unobserve(sub)
 
@@ -226,3 +566,554 @@ def test_deep_observe():
         text.extend(txn, " should not trigger")
 
     assert events is None
+
+
+def test_comments():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        text.add_comment(txn, 6, 11, "c1", {"author": "bob"})
+        text.add_comment(txn, 0, 2, "c2", "typo?")
+
+    with d1.begin_transaction() as txn:
+        assert text.comments(txn) == [
+            {"id": "c2", "start": 0, "end": 2, "data": "typo?", "orphaned": False},
+            {"id": "c1", "start": 6, "end": 11, "data": {"author": "bob"}, "orphaned": False},
+        ]
+    assert str(text) == "hello world"
+    # comments are not a part of the text formatting
+    with d1.begin_transaction() as txn:
+        assert text.to_delta(txn) == [{"insert": "hello world"}]
+
+    # edits before and inside of the range move and grow it respectively
+    with d1.begin_transaction() as txn:
+        text.insert(txn, 5, ",")
+        text.insert(txn, 9, "--")
+    assert str(text) == "hello, wo--rld"
+    with d1.begin_transaction() as txn:
+        ranges = {c["id"]: (c["start"], c["end"]) for c in text.comments(txn)}
+    assert ranges == {"c1": (7, 14), "c2": (0, 2)}
+
+    # comments are synchronized with other peers
+    d2 = Y.YDoc()
+    remote = d2.get_text("test")
+    exchange_updates([d1, d2])
+    with d2.begin_transaction() as txn:
+        ranges = {c["id"]: (c["start"], c["end"]) for c in remote.comments(txn)}
+    assert ranges == {"c1": (7, 14), "c2": (0, 2)}
+
+    # deleting all commented text orphans the comment instead of dropping it
+    with d1.begin_transaction() as txn:
+        text.delete_range(txn, 0, 3)
+    with d1.begin_transaction() as txn:
+        comments = text.comments(txn)
+    assert comments[-1] == {
+        "id": "c2",
+        "start": None,
+        "end": None,
+        "data": "typo?",
+        "orphaned": True,
+    }
+
+    with d1.begin_transaction() as txn:
+        text.remove_comment(txn, "c2")
+        text.remove_comment(txn, "c1")
+        assert text.comments(txn) == []
+        with pytest.raises(KeyError):
+            text.remove_comment(txn, "c1")
+        with pytest.raises(IndexError):
+            text.add_comment(txn, 3, 100, "c3", None)
+
+    # removing a comment also drops its anchors
+    with d1.begin_transaction() as txn:
+        text.add_comment(txn, 0, 1, "c1", None)
+        assert [(c["start"], c["end"]) for c in text.comments(txn)] == [(0, 1)]
+
+    # negative indexes count from the end, and ranges can't split characters
+    doc = Y.YDoc(offset_kind="utf16")
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "a😀bc")
+        text.add_comment(txn, -2, -1, "c1", None)
+        assert [(c["start"], c["end"]) for c in text.comments(txn)] == [(3, 4)]
+        with pytest.raises(ValueError):
+            text.add_comment(txn, 0, 2, "c2", None)
+        with pytest.raises(IndexError):
+            text.add_comment(txn, 2, 2, "c2", None)
+
+
+def test_indent_lines():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "def f():\nreturn 1\n\nx = f()")
+
+    # selection starting and ending mid-line covers both whole lines
+    with d1.begin_transaction() as txn:
+        text.indent_lines(txn, 10, 14)
+    assert str(text) == "def f():\n    return 1\n\nx = f()"
+
+    # a selection ending right at the start of a line doesn't include that line
+    with d1.begin_transaction() as txn:
+        text.indent_lines(txn, 0, 9, "\t")
+    assert str(text) == "\tdef f():\n    return 1\n\nx = f()"
+
+    # multi-line selection
+    with d1.begin_transaction() as txn:
+        text.indent_lines(txn, 0, len(text), "  ")
+    assert str(text) == "  \tdef f():\n      return 1\n  \n  x = f()"
+
+
+def test_dedent_lines():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "    a\n  b\n\tc\nd")
+    with d1.begin_transaction() as txn:
+        text.dedent_lines(txn, 2, len(text))
+    assert str(text) == "a\nb\nc\nd"
+
+    with d1.begin_transaction() as txn:
+        text.indent_lines(txn, 0, len(text))
+        text.dedent_lines(txn, 0, 1)
+    assert str(text) == "a\n    b\n    c\n    d"
+
+    with d1.begin_transaction() as txn:
+        with pytest.raises(IndexError):
+            text.dedent_lines(txn, 0, 100)
+
+    # line boundaries are computed in the document's offset units
+    d2 = Y.YDoc(offset_kind="utf16")
+    text = d2.get_text("test")
+    with d2.begin_transaction() as txn:
+        text.extend(txn, "é\nb")
+        text.indent_lines(txn, 2, 3)
+    assert str(text) == "é\n    b"
+
+
+def test_lines():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        assert text.lines(txn) == [("", 0)]
+        text.extend(txn, "first\nsecond\n")
+        text.insert_embed(txn, 7, {"image": "a.png"})
+        assert text.lines(txn) == [("first", 0), ("second", 6), ("", 14)]
+
+    # indexes are measured in the document's offset units
+    for offset_kind, start in [("utf8", 7), ("utf16", 4), ("utf32", 3)]:
+        doc = Y.YDoc(offset_kind=offset_kind)
+        text = doc.get_text("test")
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "é😀\nend")
+            lines = text.lines(txn)
+            assert lines == [("é😀", 0), ("end", start)]
+            text.delete_range(txn, lines[1][1], 3)
+        assert str(text) == "é😀\n"
+
+    prelim = Y.YText("ä\nb")
+    with doc.begin_transaction() as txn:
+        assert prelim.lines(txn) == [("ä", 0), ("b", 3)]
+
+
+def test_set_content():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "hello brave world")
+
+    deltas = []
+    sub = text.observe(lambda e: deltas.append(e.delta))
+
+    # only the differing middle gets replaced
+    with d1.begin_transaction() as txn:
+        text.set_content(txn, "hello new world")
+    assert str(text) == "hello new world"
+    assert deltas[-1] == [{"retain": 6}, {"delete": 5}, {"insert": "new"}]
+
+    # pure insertion and deletion
+    with d1.begin_transaction() as txn:
+        text.set_content(txn, "hello new world!")
+    assert deltas[-1] == [{"retain": 15}, {"insert": "!"}]
+    with d1.begin_transaction() as txn:
+        text.set_content(txn, "new world!")
+    assert deltas[-1] == [{"delete": 6}]
+
+    # no changes produce no events
+    count = len(deltas)
+    with d1.begin_transaction() as txn:
+        text.set_content(txn, "new world!")
+    assert len(deltas) == count
+
+    # offsets respect multi-byte characters
+    with d1.begin_transaction() as txn:
+        text.set_content(txn, "new wörld!")
+    assert str(text) == "new wörld!"
+    assert deltas[-1] == [{"retain": 5}, {"delete": 1}, {"insert": "ö"}]
+
+
+def test_replace():
+    d1 = Y.YDoc(offset_kind="utf16")
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "🐟 one fish, 🐟 two fish, 🐟 red fish")
+
+    with d1.begin_transaction() as txn:
+        assert text.replace(txn, "fish", "cat", 2) == 2
+    assert str(text) == "🐟 one cat, 🐟 two cat, 🐟 red fish"
+
+    with d1.begin_transaction() as txn:
+        assert text.replace(txn, "🐟 ", "") == 3
+        assert text.replace(txn, "blue", "green") == 0
+    assert str(text) == "one cat, two cat, red fish"
+
+    with d1.begin_transaction() as txn:
+        with pytest.raises(ValueError):
+            text.replace(txn, "", "x")
+
+    d2 = Y.YDoc()
+    exchange_updates([d1, d2])
+    assert str(d2.get_text("test")) == "one cat, two cat, red fish"
+
+    prelim = YText("aaa")
+    with d1.begin_transaction() as txn:
+        assert prelim.replace(txn, "a", "b", 2) == 2
+    assert str(prelim) == "bba"
+
+
+def test_char_len():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "café 👍")
+    assert (len(text), text.char_len(), text.code_unit_len()) == (10, 6, 7)
+
+    with d1.begin_transaction() as txn:
+        text.insert_embed(txn, 0, {"image": "cat.png"})
+    assert (len(text), text.char_len(), text.code_unit_len()) == (11, 7, 8)
+
+    d2 = Y.YDoc(offset_kind="utf16")
+    exchange_updates([d1, d2])
+    remote = d2.get_text("test")
+    assert (len(remote), remote.char_len(), remote.code_unit_len()) == (8, 7, 8)
+
+    assert YText("👍").char_len() == 1
+
+    # lengths don't depend on the position of embeds
+    d3 = Y.YDoc(offset_kind="utf16")
+    text = d3.get_text("test")
+    with d3.begin_transaction() as txn:
+        text.extend(txn, "é👍")
+        for index in [0, 2, 5]:
+            text.insert_embed(txn, index, {"image": "cat.png"})
+    assert (len(text), text.char_len(), text.code_unit_len()) == (6, 5, 6)
+
+
+def test_attributes_at():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "plain ")
+        text.insert(txn, 6, "bold 👍", {"bold": True})
+        text.format(txn, 11, 4, {"color": "red"})
+        text.insert_embed(txn, 15, {"image": "cat.png"}, {"width": "100px"})
+
+        assert text.attributes_at(txn, 0) == {}
+        assert text.attributes_at(txn, 6) == {"bold": True}
+        assert text.attributes_at(txn, 13) == {"bold": True, "color": "red"}
+        assert text.attributes_at(txn, 15) == {"width": "100px"}
+        assert text.attributes_at(txn, 16) == {}
+        with pytest.raises(IndexError):
+            text.attributes_at(txn, 17)
+        assert YText("abc").attributes_at(txn, 1) == {}
+
+
+def test_remove_format():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.insert(txn, 0, "hello world", {"bold": True, "italic": True})
+        text.add_comment(txn, 0, 5, "note", None)
+        text.remove_format(txn, 0, 5, ["italic"])
+        text.remove_format(txn, 6, 5)
+        delta = text.to_delta(txn)
+    assert [(op["insert"], op.get("attributes", {})) for op in delta] == [
+        ("hello", {"bold": True}),
+        (" ", {"bold": True, "italic": True}),
+        ("world", {}),
+    ]
+
+    # clearing all formatting keeps comments in place
+    with d1.begin_transaction() as txn:
+        text.remove_format(txn, 0, len(text))
+        assert [c["id"] for c in text.comments(txn)] == ["note"]
+        assert text.attributes_at(txn, 6) == {}
+        with pytest.raises(IndexError):
+            text.remove_format(txn, 5, 7)
+    with pytest.raises(Exception):
+        with d1.begin_transaction() as txn:
+            YText("abc").remove_format(txn, 0, 1)
+
+
+def test_clear():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "héllo")
+        text.insert_embed(txn, 3, {"image": "cat.png"})
+        text.clear(txn)
+        text.clear(txn)
+    assert str(text) == "" and len(text) == 0
+
+    prelim = YText("abc")
+    with d1.begin_transaction() as txn:
+        prelim.clear(txn)
+    assert str(prelim) == ""
+
+
+def test_eq():
+    d1 = Y.YDoc()
+    d2 = Y.YDoc()
+    t1 = d1.get_text("test")
+    t2 = d2.get_text("test")
+    with d1.begin_transaction() as txn:
+        t1.extend(txn, "hello")
+    with d2.begin_transaction() as txn:
+        t2.extend(txn, "hello")
+    assert t1 == t2
+    assert t1 == "hello" and "hello" == t1
+    assert t1 != "world"
+    assert t1 == YText("hello")
+    assert YText("abc") == YText("abc")
+    assert YText("abc") != YText("abd")
+    assert t1 != 5
+    with pytest.raises(TypeError):
+        hash(t1)
+
+
+def test_prelim_embeds():
+    prelim = YText("hello world")
+    doc = Y.YDoc()
+    with doc.begin_transaction() as txn:
+        prelim.insert_embed(txn, 5, {"image": "cat.png"}, {"width": 100})
+        prelim.insert_embed(txn, 0, {"icon": "star"})
+        prelim.insert_embed(txn, 2, {"icon": "star"})
+        with pytest.raises(IndexError):
+            prelim.insert_embed(txn, 15, {"icon": "star"})
+        prelim.extend(txn, "!")
+    assert str(prelim) == "hello world!"
+    assert len(prelim) == 15
+    expected = [
+        {"insert": {"icon": "star"}},
+        {"insert": "h"},
+        {"insert": {"icon": "star"}},
+        {"insert": "ello"},
+        {"insert": {"image": "cat.png"}, "attributes": {"width": 100}},
+        {"insert": " world!"},
+    ]
+    with doc.begin_transaction() as txn:
+        assert prelim.to_delta(txn) == expected
+
+    array = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        array.append(txn, prelim)
+    assert not prelim.prelim
+    with doc.begin_transaction() as txn:
+        assert prelim.to_delta(txn) == expected
+
+
+def test_insert_many():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "<>")
+        text.insert_many(txn, 1, (str(i) for i in range(5)))
+        text.insert_many(txn, 0, [], {"bold": True})
+        text.insert_many(txn, 0, ["a", "b"], {"bold": True})
+        with pytest.raises(TypeError):
+            text.insert_many(txn, 0, ["a", 1])
+    assert str(text) == "ab<01234>"
+    with doc.begin_transaction() as txn:
+        assert text.to_delta(txn)[0] == {"insert": "ab", "attributes": {"bold": True}}
+
+    prelim = YText("ac")
+    with doc.begin_transaction() as txn:
+        prelim.insert_many(txn, 1, iter(["b"]))
+    assert str(prelim) == "abc"
+
+
+def test_diff():
+    doc = Y.YDoc(skip_gc=True)
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+    v1 = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 6)
+        text.insert(txn, 5, "!", {"bold": True})
+    v2 = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 0, "big ")
+
+    with doc.begin_transaction() as txn:
+        assert text.diff(txn, v1, v2) == [
+            {"delete": 6},
+            {"retain": 5},
+            {"insert": "!", "attributes": {"bold": True}},
+        ]
+        assert text.diff(txn, v2) == [{"insert": "big "}]
+        assert text.diff(txn, v1, v1) == []
+        with pytest.raises(ValueError):
+            text.diff(txn, b"\xff")
+
+    with doc.begin_transaction() as txn:
+        with pytest.raises(Exception):
+            YText("abc").diff(txn, v1)
+
+
+def test_diff_single_remote_change():
+    remote = Y.YDoc(1, skip_gc=True)
+    remote_text = remote.get_text("test")
+    with remote.begin_transaction() as txn:
+        remote_text.extend(txn, "z")
+    doc = Y.YDoc(2, skip_gc=True)
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    Y.apply_update(doc, Y.encode_state_as_update(remote))
+    old = doc.snapshot()
+
+    # yrs can't split blocks of a client with a single one element long change
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+        with pytest.raises(RuntimeError):
+            text.diff(txn, old)
+    assert str(text) == "zhello!"
+
+    # nor with two of them, which don't get merged
+    with remote.begin_transaction() as txn:
+        remote_text.insert(txn, 0, "?")
+    Y.apply_update(doc, Y.encode_state_as_update(remote, Y.encode_state_vector(doc)))
+    with doc.begin_transaction() as txn:
+        with pytest.raises(RuntimeError):
+            text.diff(txn, old)
+
+    with remote.begin_transaction() as txn:
+        remote_text.extend(txn, "?")
+    Y.apply_update(doc, Y.encode_state_as_update(remote, Y.encode_state_vector(doc)))
+    assert str(text) == "?z?hello!"
+    with doc.begin_transaction() as txn:
+        assert text.diff(txn, old) == [
+            {"insert": "?"},
+            {"retain": 1},
+            {"insert": "?"},
+            {"retain": 5},
+            {"insert": "!"},
+        ]
+
+
+def test_out_of_range():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        with pytest.raises(IndexError, match="Index 6 is out of range of a text with length 5"):
+            text.insert(txn, 6, "x")
+        with pytest.raises(IndexError):
+            text.insert(txn, 9999, "x", {"bold": True})
+        with pytest.raises(IndexError):
+            text.insert_embed(txn, 6, {"image": "a.png"})
+        with pytest.raises(IndexError):
+            text.delete(txn, 5)
+        with pytest.raises(IndexError, match="Range 3..8"):
+            text.delete_range(txn, 3, 5)
+        text.insert(txn, 5, "!")
+    assert str(text) == "hello!"
+
+    prelim = YText("zażółć")
+    with doc.begin_transaction() as txn:
+        with pytest.raises(IndexError):
+            prelim.insert(txn, 100, "x")
+        with pytest.raises(ValueError):
+            prelim.insert(txn, 3, "x")
+        with pytest.raises(ValueError):
+            prelim.delete_range(txn, 0, 3)
+    assert str(prelim) == "zażółć"
+
+
+def test_character_boundaries():
+    doc = Y.YDoc(offset_kind="utf16")
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "a😀")
+        text.insert_embed(txn, 3, {"image": "a.png"})
+        text.extend(txn, "b")
+        with pytest.raises(ValueError, match="not on a character boundary"):
+            text.insert(txn, 2, "x")
+        with pytest.raises(ValueError, match="not on a character boundary"):
+            text.delete_range(txn, 0, 2)
+        with pytest.raises(ValueError, match="not on a character boundary"):
+            text.insert_embed(txn, 2, {"image": "b.png"})
+        # indexes around emoji and embeds are fine
+        text.insert(txn, 3, "[")
+        text.insert(txn, 5, "]")
+        text.delete_range(txn, 1, 2)
+    assert str(text) == "a[]b"
+
+    doc = Y.YDoc(offset_kind="utf8")
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "é")
+        with pytest.raises(ValueError, match="not on a character boundary"):
+            text.insert(txn, 1, "x")
+        text.insert(txn, 2, "x")
+        # characters after embeds and the ones inserted into text which used to be pure ASCII
+        text.insert_embed(txn, 3, {"image": "a.png"})
+        text.extend(txn, "ö")
+        with pytest.raises(ValueError, match="not on a character boundary"):
+            text.insert(txn, 5, "x")
+        text.delete_range(txn, 0, 2)
+        text.delete_range(txn, 2, 2)
+        text.insert(txn, 1, "ß")
+        with pytest.raises(ValueError, match="not on a character boundary"):
+            text.insert(txn, 2, "x")
+    assert str(text) == "xß"
+
+
+def test_negative_indexes():
+    doc = Y.YDoc(offset_kind="utf16")
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello 😀")
+        # offsets count from the end in the document's offset units
+        text.insert(txn, -2, "!")
+        text.delete_range(txn, -9, 1)
+        text.format(txn, -3, 1, {"bold": True})
+        assert text.attributes_at(txn, -3) == {"bold": True}
+        text.delete(txn, -3)
+        with pytest.raises(IndexError, match="Index -8 is out of range of a text with length 7"):
+            text.insert(txn, -8, "x")
+    assert str(text) == "ello 😀"
+
+
+def test_format_range_validation():
+    doc = Y.YDoc(offset_kind="utf16")
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hi 😀")
+        with pytest.raises(ValueError, match="empty range"):
+            text.format(txn, 1, 0, {"bold": True})
+        with pytest.raises(ValueError, match="empty range"):
+            text.format(txn, 5, 3, {"bold": True})
+        with pytest.raises(IndexError, match="Index 6 is out of range"):
+            text.format(txn, 6, 1, {"bold": True})
+        # the emoji takes two UTF-16 code units
+        with pytest.raises(ValueError, match="character boundary"):
+            text.format(txn, 3, 1, {"bold": True})
+        # ranges extending past the end are clamped
+        text.format(txn, 3, 10, {"bold": True})
+        assert text.to_delta(txn) == [
+            {"insert": "hi "},
+            {"insert": "😀", "attributes": {"bold": True}},
+        ]
@@ -1,4 +1,5 @@
 import y_py as Y
+import pytest
 
 def test_before_state():
     doc = Y.YDoc()
@@ -9,4 +10,123 @@ def test_before_state():
     with doc.begin_transaction() as txn:
         text.extend(txn, " World")
         assert len(txn.before_state) == 1
-    
\ No newline at end of file
+    
+
+def test_commit():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    origins = []
+    doc.observe_after_transaction(lambda e: origins.append(e.origin))
+    updates = []
+    sub = text.observe(lambda e: updates.append(e.delta))
+    with doc.begin_transaction("local") as txn:
+        text.extend(txn, "Hello")
+        txn.commit()
+        assert updates == [[{"insert": "Hello"}]]
+        assert origins == ["local"]
+        # double commit does nothing
+        txn.commit()
+        assert origins == ["local"]
+        text.extend(txn, " World")
+        assert len(txn.before_state) == 1
+    assert updates[1] == [{"retain": 5}, {"insert": " World"}]
+    assert origins == ["local", "local"]
+    assert str(text) == "Hello World"
+
+    # nothing left to commit once the block exits
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+        txn.commit()
+    assert origins == ["local", "local", None]
+
+    def callback(txn):
+        text.extend(txn, "?")
+        txn.commit()
+        text.extend(txn, "?")
+
+    doc.transact(callback)
+    assert str(text) == "Hello World!??"
+    assert len(updates) == 5
+
+
+def test_nested_transaction():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    txn = doc.begin_transaction()
+    with pytest.raises(RuntimeError, match="Transaction already open"):
+        doc.begin_transaction()
+    with pytest.raises(RuntimeError, match="Transaction already open"):
+        doc.transact(lambda t: None)
+    # any other transaction is refused as well, while root types are read with the open one
+    with pytest.raises(RuntimeError, match="Transaction already open"):
+        doc.begin_read_transaction()
+    with pytest.raises(RuntimeError, match="Transaction already open"):
+        Y.encode_state_vector(doc)
+    assert doc.get_text("test") == text
+    text.extend(txn, "a")
+    txn.commit()
+    with pytest.raises(RuntimeError):
+        doc.begin_transaction()
+    del txn
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "b")
+    doc.transact(lambda t: text.extend(t, "c"))
+    assert str(text) == "abc"
+
+
+def test_exception_in_block():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    updates = []
+    doc.observe_after_transaction(lambda e: updates.append(e.get_update()))
+
+    with pytest.raises(ValueError, match="failed"):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "partial")
+            raise ValueError("failed")
+    # changes can't be rolled back, so they are committed before the exception propagates
+    assert str(text) == "partial"
+    assert len(updates) == 1
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+    assert str(text) == "partial!"
+
+
+def test_len_in_open_transaction():
+    doc = Y.YDoc()
+    text = doc.get_text("text")
+    array = doc.get_array("array")
+    updates = []
+    doc.observe_update_v1(lambda update, origin: updates.append(update))
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        array.extend(txn, [1, 2, 3])
+        # lengths are read without opening another transaction and include uncommitted changes
+        assert len(text) == 5 and len(array) == 3
+        text.delete_range(txn, 0, len(text) - 1)
+        array.delete(txn, len(array) - 1)
+    assert str(text) == "o" and list(array) == [1, 2]
+    assert len(updates) == 1
+
+
+def test_op_count():
+    doc = Y.YDoc()
+    text = doc.get_text("text")
+    array = doc.get_array("array")
+    updates = []
+    doc.observe_update_v1(lambda update, origin: updates.append(update))
+    with doc.begin_transaction() as txn:
+        assert txn.op_count() == 0
+        # text is counted in UTF-16 code units
+        text.extend(txn, "ab😀")
+        assert txn.op_count() == 4
+        array.extend(txn, [1, 2, 3])
+        text.delete_range(txn, 0, 2)
+        assert txn.op_count() == 9
+        size = txn.update_size()
+        txn.commit()
+        assert 0 < len(updates[-1]) <= size
+        # counting starts over after a commit
+        assert txn.op_count() == 0
+        array.delete(txn, 0)
+        assert txn.op_count() == 1
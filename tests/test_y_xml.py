@@ -1,5 +1,6 @@
 from test_helper import exchange_updates
 import unittest
+import pytest
 import y_py as Y
 
 
@@ -17,6 +18,12 @@ def test_insert():
     s = str(root)
     assert s == "<UNDEFINED><p>hello</p>world</UNDEFINED>"
 
+    with d1.begin_transaction() as txn:
+        with pytest.raises(TypeError, match="chunk must be str, got int"):
+            b.push(txn, 1)
+        with pytest.raises(TypeError, match="chunk must be str, got bytes"):
+            b.insert(txn, 0, b"x")
+
 
 def test_attributes():
     d1 = Y.YDoc()
@@ -38,6 +45,13 @@ def test_attributes():
         }
 
     assert actual == {"key1": None, "key2": "value2"}
+    assert dict(root.attributes()) == {"key2": "value2"}
+
+    with d1.begin_transaction() as txn:
+        text = root.push_xml_text(txn)
+        text.set_attribute(txn, "bold", "true")
+    assert text.get_attribute("bold") == "true"
+    assert dict(text.attributes()) == {"bold": "true"}
 
 
 def test_siblings():
@@ -63,6 +77,40 @@ def test_siblings():
     assert actual == expected
 
 
+
+def test_children():
+    d1 = Y.YDoc()
+    root = d1.get_xml_element("test")
+    with d1.begin_transaction() as txn:
+        assert list(root.children(txn)) == [] and root.first_child is None
+        text = root.push_xml_text(txn)
+        text.push(txn, "world")
+        p = root.insert_xml_element(txn, 0, "p")
+        p.push_xml_text(txn).push(txn, "hello")
+        root.push_xml_element(txn, "br")
+        children = list(root.children(txn))
+    assert [str(c) for c in children] == ["<p>hello</p>", "world", "<br></br>"]
+    assert type(children[0]) == Y.YXmlElement and type(children[1]) == Y.YXmlText
+    assert str(children[0].parent) == str(root)
+
+    with d1.begin_transaction() as txn:
+        root.delete(txn, 0, 2)
+        assert [str(c) for c in root.children(txn)] == ["<br></br>"]
+
+    # out of range indexes raise instead of crashing
+    empty = d1.get_xml_element("empty")
+    with d1.begin_transaction() as txn:
+        with pytest.raises(IndexError, match="Index 5 is out of range"):
+            empty.insert_xml_element(txn, 5, "p")
+        with pytest.raises(IndexError):
+            empty.insert_xml_text(txn, 1)
+        empty.insert_xml_text(txn, 0)
+        with pytest.raises(IndexError, match="Range 3..5"):
+            empty.delete(txn, 3, 2)
+        with pytest.raises(IndexError):
+            empty.delete(txn, 0, 2)
+    assert len(empty) == 1
+
 def test_tree_walker():
     d1 = Y.YDoc()
     root = d1.get_xml_element("test")
@@ -85,14 +133,17 @@ def test_xml_text_observer():
     target = None
     attributes = None
     delta = None
+    representation = None
 
     def callback(e):
         nonlocal target
         nonlocal attributes
         nonlocal delta
+        nonlocal representation
         target = e.target
         attributes = e.keys
         delta = e.delta
+        representation = repr(e)
 
     subscription_id = x.observe(callback)
 
@@ -100,6 +151,7 @@ def test_xml_text_observer():
     with d1.begin_transaction() as txn:
         x.set_attribute(txn, "attr1", "value1")
         x.set_attribute(txn, "attr2", "value2")
+    assert representation.startswith("YXmlTextEvent(")
 
     assert str(target) == str(x)
     assert delta == []
@@ -273,3 +325,26 @@ def test_deep_observe():
         container.first_child.push(txn, "nested")
 
     assert events != None
+
+
+def test_xml_text_formatting():
+    doc = Y.YDoc()
+    text = doc.get_xml_text("test")
+    with doc.begin_transaction() as txn:
+        text.push(txn, "hello world")
+        text.format(txn, 0, 5, {"strong": {}})
+        text.insert(txn, 11, "!", {"em": {}})
+        text.delete(txn, 5, 6)
+        assert text.to_delta(txn) == [
+            {"insert": "hello", "attributes": {"strong": {}}},
+            {"insert": "!", "attributes": {"em": {}}},
+        ]
+        with pytest.raises(IndexError):
+            text.insert(txn, 7, "x")
+        with pytest.raises(IndexError):
+            text.delete(txn, 0, 7)
+        with pytest.raises(IndexError):
+            text.format(txn, 7, 1, {"em": {}})
+        with pytest.raises(IndexError):
+            text.format(txn, 2, 5, {"em": {}})
+    assert len(text) == 6
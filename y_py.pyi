@@ -16,9 +16,34 @@ class SubscriptionId:
     """
     Tracks an observer callback. Pass this to the `unobserve` method to cancel
     its associated callback.
+
+    Subscriptions returned by `observe` and `observe_deep` of shared types can also cancel
+    their callbacks on their own, either with `drop` or by being used as a context manager.
+    Their callbacks are also cancelled once the subscription is garbage collected, so it has to be
+    kept for as long as the callback should be called.
+
+    Example::
+
+        with text.observe(callback) as sub:
+            with doc.begin_transaction() as txn:
+                text.extend(txn, "hello")  # callback is called here
+        with doc.begin_transaction() as txn:
+            text.extend(txn, " world")  # but not anymore here
     """
 
-Event = Union[YTextEvent, YArrayEvent, YMapEvent, YXmlTextEvent, YXmlElementEvent]
+    id: int
+    """Integer identifier of the subscription."""
+    def drop(self):
+        """
+        Cancels the observer callback. Calling it more than once has no effect.
+        """
+    def __enter__(self) -> SubscriptionId: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool:
+        """
+        Cancels the observer callback.
+        """
+
+Event = Union[YTextEvent, YArrayEvent, YMapEvent, YXmlTextEvent, YXmlEvent]
 
 class YDoc:
     """
@@ -30,6 +55,20 @@ class YDoc:
     Document manages so called root types, which are top-level shared types definitions (as opposed
     to recursively nested types).
 
+    Python `int`s and `float`s are stored as 64 bit floating point numbers, which Yjs peers read as
+    JavaScript `number`s. Integers beyond the range `number`s represent exactly (±2**53-1) are stored
+    as 64 bit integers instead, read by Yjs as `BigInt`s, while the ones outside of the 64 bit range
+    raise `OverflowError`. Numbers without a fractional part are read back as `int`s, so `5.0` is read
+    back as `5`.
+
+    Observer callbacks are called while a transaction is committed. If a callback raises an exception,
+    the remaining callbacks are still called, and the exception is raised once the commit is done by the
+    call which committed the transaction: leaving a `with` block, `YTransaction.commit`, `transact`,
+    `apply_update`, `apply_update_v2`, or a method committing a transaction of its own (e.g. `get_text`
+    or `current_clock`). If more callbacks fail, only the first exception is raised, while the other
+    ones are printed with their tracebacks. Transactions released by deleting them can't raise, so
+    their exceptions are raised by the next commit instead.
+
     Example::
 
         from y_py import YDoc
@@ -43,6 +82,12 @@ class YDoc:
     """
 
     client_id: int
+    skip_gc: bool
+    """
+    True if this document was created with `skip_gc=True`, which keeps contents of deleted blocks around, as
+    required by snapshots. The option can't be changed afterwards, since collecting garbage would invalidate
+    snapshots which were already taken.
+    """
     def __init__(
         self,
         client_id: Optional[int] = None,
@@ -54,8 +99,133 @@ class YDoc:
         document globally unique identifier (it's up to caller to ensure that requirement).
         Otherwise it will be assigned a randomly generated number.
         """
-    def begin_transaction(self) -> YTransaction:
+    def current_clock(self, client_id: Optional[int] = None) -> int:
+        """
+        Every change made by a client advances its logical clock by the length of that change, so
+        comparing clocks reveals which peer's edit was integrated later.
+
+        Args:
+            client_id: Identifier of a client to check. Defaults to this document's own `client_id`.
+        Returns:
+            The logical clock of a given client as currently known by this document,
+            or 0 if that client never made any change visible to this document.
+        """
+    def state_vector(self) -> Dict[int, int]:
+        """
+        Returns:
+            A state vector of this document, mapping IDs of all clients whose changes it has seen to
+            their clocks. It's a decoded form of `encode_state_vector`.
+        """
+    def converged_with(self, other: YDoc) -> bool:
+        """
+        Compares state vectors of both documents, which is cheaper than comparing their contents.
+
+        Args:
+            other: Document to compare with.
+        Returns:
+            `True` if both documents have seen exactly the same changes from the same clients.
+        """
+    def __getstate__(self) -> Tuple[int, str, bool, bytes]:
+        """
+        Makes documents picklable. The state consists of the client ID, offset kind, `skip_gc`
+        setting and all contents of the document encoded as a lib0 v1 update, so all of them are
+        preserved by `pickle` and `copy.deepcopy`.
+
+        Example::
+
+            import pickle
+            from y_py import YDoc
+
+            doc = YDoc(client_id=1)
+            restored = pickle.loads(pickle.dumps(doc))
+            assert restored.equal_state(doc) and restored.client_id == 1
+        """
+    def __setstate__(self, state: Tuple[int, str, bool, bytes]):
+        """
+        Restores a document from a state returned by `__getstate__`. Meant to be called by `pickle`
+        only, since shared types obtained from this document before are not valid afterwards.
+        """
+    def equal_state(self, other: YDoc) -> bool:
+        """
+        Checks if this document and the `other` one represent the same CRDT state, meaning that both
+        of them have seen the same changes and deleted the same contents, regardless of the order in
+        which these were applied. Unlike `converged_with`, it also compares deletions.
+
+        Documents compared with `==` are equal only when they are the same object.
+        """
+    def missing_from(self, other: YDoc) -> bytes:
         """
+        Args:
+            other: Document to compute the update for.
+        Returns:
+            An update containing all changes known to this document that `other` has not seen yet.
+            It can be applied to `other` with `apply_update`.
+        """
+    def snapshot(self) -> bytes:
+        """
+        Returns:
+            A snapshot of this document, describing which changes were visible at this point in
+            time. It can be later passed to `restore` or `encode_state_from_snapshot`.
+
+        Raises:
+            ValueError: If the document was created without `skip_gc=True`. Garbage collection
+                discards deleted contents a snapshot may still refer to.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc(skip_gc=True)
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello')
+            snapshot = doc.snapshot()
+            with doc.begin_transaction() as txn:
+                text.extend(txn, ' world')
+
+            assert str(doc.restore(snapshot).get_text('name')) == 'hello'
+        """
+    def restore(self, snapshot: bytes) -> YDoc:
+        """
+        Args:
+            snapshot: A snapshot obtained from `YDoc.snapshot`.
+        Returns:
+            A new, independent document with contents of this document as they were at the time
+            when the `snapshot` was taken. It uses the same offset kind, but has a random client ID.
+
+        Raises:
+            ValueError: If the document was created without `skip_gc=True`.
+        """
+    def fork(self, client_id: Optional[int] = None) -> YDoc:
+        """
+        Returns a new document with the same contents and options as this one, which doesn't share
+        any state with it, so that it can be edited independently (eg. to preview changes). Observers
+        and update history are not copied. `copy.copy` and `copy.deepcopy` fork a document as well,
+        keeping its client ID.
+
+        Args:
+            client_id: Client ID of the fork, random by default. Reusing the client ID of this
+                document is only safe as long as at most one of them is edited afterwards, otherwise
+                their updates can't be merged anymore.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            fork = doc.fork()
+            with fork.begin_transaction() as txn:
+                fork.get_text('name').extend(txn, 'preview')
+
+            assert str(doc.get_text('name')) == ''
+        """
+    def __copy__(self) -> YDoc: ...
+    def __deepcopy__(self, memo: Dict[int, Any]) -> YDoc: ...
+    def begin_transaction(self, origin: Optional[Any] = None) -> YTransaction:
+        """
+        Args:
+            origin: Optional object describing where the changes come from (eg. a remote peer). It's
+                exposed as `AfterTransactionEvent.origin` to `observe_after_transaction` callbacks.
 
         Returns:
             A new transaction for this document. Ypy shared data types execute their
@@ -63,7 +233,14 @@ class YDoc:
             transaction at the time - subsequent attempts will cause exception to be thrown.
 
         Transactions started with `doc.begin_transaction` can be released by deleting the transaction object
-        method.
+        method. Starting another transaction before that raises `RuntimeError`, and so do document
+        methods which need a transaction of their own, like `encode_state_vector` or
+        `begin_read_transaction`. Root types can still be accessed with `get_text`, `get_map` etc.,
+        which use the open transaction.
+
+        All changes made within a transaction are committed together, producing a single update passed
+        to `observe_update_v1`/`observe_update_v2` callbacks, and a single call of
+        `observe_after_transaction` callbacks. Only an explicit `YTransaction.commit` splits them.
 
         Example::
 
@@ -74,7 +251,45 @@ class YDoc:
                 text.insert(txn, 0, 'hello world')
 
         """
-    def transact(self, callback: Callable[[YTransaction]]): ...
+    def pending_transaction(self) -> Optional[YTransaction]:
+        """
+        Returns:
+            The transaction started with `begin_transaction` (or `transact`), which hasn't been
+            committed yet, or `None` if there is no such transaction. Changes made with it are going
+            to be committed together, once it's done.
+        """
+    def begin_read_transaction(self) -> YTransaction:
+        """
+        Returns:
+            A new read-only transaction for this document. It can be passed to methods which require
+            a transaction only to read the document (like `YText.to_delta`), while any attempt to
+            modify the document with it raises `ReadOnlyTransactionException`. Read-only
+            transactions don't exclude one another, so many of them can be used at the same time,
+            but none can be started while a transaction from `begin_transaction` is open.
+
+        Example::
+
+            from y_py import YDoc
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_read_transaction() as txn:
+                delta = text.to_delta(txn)
+        """
+    def transact(
+        self, callback: Callable[[YTransaction], Any], origin: Optional[Any] = None
+    ) -> Any:
+        """
+        Calls `callback` with a new transaction, returning its result. The transaction is committed as soon as
+        `callback` returns. If `callback` raises an exception, changes it made before are committed as well
+        (transactions can't be rolled back) and the exception is propagated afterwards.
+
+        Args:
+            callback: A function receiving the transaction.
+            origin: An optional value identifying the source of changes, available as `YTransaction.origin`
+                and `AfterTransactionEvent.origin`.
+        Returns:
+            The result of `callback`.
+        """
     def get_map(self, name: str) -> YMap:
         """
         Returns:
@@ -127,6 +342,53 @@ class YDoc:
         If there was an instance with this name, but it was of different type, it will be projected
         onto `YText` instance.
         """
+    def roots(self, txn: YTransaction) -> List[Tuple[str, Optional[str]]]:
+        """
+        Args:
+            txn: A transaction used to read the document.
+        Returns:
+            `(name, kind)` pairs describing root-level shared types of this document, sorted by name. Kind is one of
+            `"text"`, `"array"`, `"map"` or `"xml"`.
+
+        Roots received from other peers have no type until they are accessed with one of the `get_*` methods, so
+        their kind is inferred from their contents instead. It's `None` when the contents don't tell (eg. when all of
+        them were deleted). Text and XML text roots can't be told apart this way, so both are reported as `"text"`.
+        """
+    def keep_history(self, max_updates: int):
+        """
+        Starts recording incremental updates of this document (both local and remote ones) in a ring buffer,
+        which keeps only the `max_updates` most recent ones. Calling it again changes the limit, while 0 stops
+        recording and clears the history.
+
+        Recorded updates stay in memory until trimmed, so the cost grows with the limit and the size of transactions.
+        This is not a CRDT snapshot: the history can only be replayed in order on top of a document that has already
+        seen all changes made before the oldest recorded update.
+
+        Args:
+            max_updates: Maximum number of updates to retain.
+        """
+    def history(self) -> List[bytes]:
+        """
+        Returns:
+            Updates recorded since `keep_history` was called, from the oldest to the most recent one.
+            Each can be applied with `apply_update`.
+        """
+    def set_debug_logger(self, callback: Optional[Callable[[DebugLogEntry], None]]):
+        """
+        Sets a callback receiving diagnostic notifications about how updates applied to this document got integrated,
+        or removes it when `None` is passed. Meant for reproducing convergence issues only: notifications are verbose
+        and make applying updates slower. With no logger set there is no additional cost.
+
+        For every applied update, the callback receives one entry per affected range, ordered by client identifier:
+
+        - `integrate`: a range of blocks that got integrated into the document.
+        - `delete`: a range of blocks that got deleted.
+        - `pending`: blocks that couldn't be integrated yet, because they depend on changes this document has not seen.
+          The range starts at the first clock of a client missing in this document.
+
+        Args:
+            callback: A function receiving log entries, or `None` to disable logging.
+        """
     def observe_after_transaction(
         self, callback: Callable[[AfterTransactionEvent]]
     ) -> SubscriptionId:
@@ -140,10 +402,110 @@ class YDoc:
         Returns:
             A subscription identifier that can be used to cancel the callback.
         """
+    def observe_deep(self, callback: Callable[[List[Event]], None]) -> SubscriptionId:
+        """
+        Subscribes a callback to changes of all root types of this document, including their
+        nested shared types and root types created after subscribing. Like with `observe_deep`
+        of individual types, the callback receives a list of events, each with a `target` and
+        a `path` leading to it from its root type.
+
+        Events of different root types changed by the same transaction are passed by separate
+        calls, all made before `observe_after_transaction` callbacks of that transaction. Root
+        types received from other peers are observed once they are accessed with one of the
+        `get_*` methods, since their type is not known until then.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            doc.observe_deep(lambda events: [print(e.path(), e.target) for e in events])
+            with doc.begin_transaction() as txn:
+                txn.get_map("settings").set(txn, "theme", "dark")
+
+        Args:
+            callback: A function receiving a list of events of a single root type.
+
+        Returns:
+            A subscription identifier that can be used to cancel the callback.
+        """
+    def unobserve_deep(self, subscription_id: SubscriptionId):
+        """
+        Cancels a callback subscribed with `observe_deep`.
+
+        Args:
+            subscription_id: A subscription identifier returned by `observe_deep`.
+        """
+    def observe_update_v1(
+        self, callback: Callable[[YDocUpdate, Optional[Any]], None]
+    ) -> SubscriptionId:
+        """
+        Subscribes a callback to incremental updates produced by transactions committed on this
+        document, encoded using lib0 v1 encoding. Transactions which didn't change the document
+        don't produce updates.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            doc.observe_update_v1(lambda update, origin: websocket.send(update))
+
+        Args:
+            callback: A function receiving the update and the origin of the transaction which produced it.
+
+        Returns:
+            A subscription identifier that can be used to cancel the callback with `unobserve_update_v1`.
+        """
+    def unobserve_update_v1(self, subscription_id: SubscriptionId):
+        """
+        Cancels a callback subscribed with `observe_update_v1`.
+        """
+    def observe_update_v2(
+        self, callback: Callable[[YDocUpdate, Optional[Any]], None]
+    ) -> SubscriptionId:
+        """
+        Subscribes a callback to incremental updates produced by transactions committed on this
+        document, encoded using lib0 v2 encoding. Works the same way as `observe_update_v1`.
+
+        Args:
+            callback: A function receiving the update and the origin of the transaction which produced it.
+
+        Returns:
+            A subscription identifier that can be used to cancel the callback with `unobserve_update_v2`.
+        """
+    def unobserve_update_v2(self, subscription_id: SubscriptionId):
+        """
+        Cancels a callback subscribed with `observe_update_v2`.
+        """
+
+class ReadOnlyTransactionException(Exception):
+    """
+    Occurs when a transaction started with `YDoc.begin_read_transaction` is used to modify a
+    document.
+    """
+
+class EncodingException(ValueError):
+    """
+    Occurs due to issues in the encoding/decoding process of y_py updates.
+    """
 
 EncodedStateVector = bytes
 EncodedDeleteSet = bytes
 YDocUpdate = bytes
+Binary = Union[bytes, bytearray, memoryview, List[int]]
+"""
+Binary payload accepted by decoding functions. Objects supporting the buffer protocol are read
+without being copied, while lists of integers have to be copied first.
+"""
+
+class DebugLogEntry(TypedDict):
+    """A diagnostic notification passed to a `YDoc.set_debug_logger` callback."""
+
+    action: Literal["integrate", "delete", "pending"]
+    client: int
+    clock: int
+    length: int
 
 class AfterTransactionEvent:
     """
@@ -158,11 +520,40 @@ class AfterTransactionEvent:
     """
     Encoded state of the YDoc after the transaction.
     """
+    before_state_map: Dict[int, int]
+    """
+    Decoded `before_state`, mapping client IDs to their clocks.
+    """
+    after_state_map: Dict[int, int]
+    """
+    Decoded `after_state`, mapping client IDs to their clocks.
+    """
     delete_set: EncodedDeleteSet
     """
-    Elements deleted by the associated transaction.
+    Encoded elements deleted by the associated transaction. Kept for compatibility, `delete_set_map` is easier to use.
+    """
+    delete_set_map: Dict[int, List[Tuple[int, int]]]
+    """
+    Elements deleted by the associated transaction, mapping client IDs to sorted lists of `(clock, length)` pairs
+    describing ranges of deleted blocks.
+    """
+    origin: Optional[Any]
+    """
+    Origin given to the transaction when it was started, or `None` if it was not provided.
+    """
+    gc_performed: bool
+    """
+    True if contents deleted by the transaction were garbage collected, which happens on every commit
+    deleting anything, unless the document was created with `skip_gc`. Collected blocks only keep their
+    IDs and lengths, while their contents are discarded.
     """
 
+    def clock_ranges(self) -> Dict[int, Tuple[int, int]]:
+        """
+        Returns:
+            Clients whose clocks have advanced during the transaction, mapped to a `(before, after)`
+            pair of their clocks. Changes made by a client occupy clock values in `range(before, after)`.
+        """
     def get_update(self) -> YDocUpdate:
         """
         Returns:
@@ -192,9 +583,7 @@ def encode_state_vector(doc: YDoc) -> EncodedStateVector:
 
     """
 
-def encode_state_as_update(
-    doc: YDoc, vector: Optional[Union[EncodedStateVector, List[int]]] = None
-) -> YDocUpdate:
+def encode_state_as_update(doc: YDoc, vector: Optional[Binary] = None) -> YDocUpdate:
     """
     Encodes all updates that have happened since a given version `vector` into a compact delta
     representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
@@ -216,10 +605,11 @@ def encode_state_as_update(
         apply_update(local_doc, remote_delta)
     """
 
-def apply_update(doc: YDoc, diff: Union[YDocUpdate, List[int]]):
+def apply_update(doc: YDoc, diff: Binary, origin: Optional[Any] = None):
     """
     Applies delta update generated by the remote document replica to a current document. This
-    method assumes that a payload maintains lib0 v1 encoding format.
+    method assumes that a payload maintains lib0 v1 encoding format. An optional `origin` is passed
+    as `AfterTransactionEvent.origin` to the document's `observe_after_transaction` callbacks.
 
     Example::
 
@@ -236,6 +626,282 @@ def apply_update(doc: YDoc, diff: Union[YDocUpdate, List[int]]):
         apply_update(local_doc, remote_delta)
     """
 
+def apply_updates(doc: YDoc, updates: List[Binary], origin: Optional[Any] = None):
+    """
+    Applies a list of lib0 v1 encoded `updates` (eg. read from an append-only update log) in order,
+    within a single transaction. Observers are called only once, after all of them were applied, and
+    `observe_update_v1` callbacks receive them as a single update. An optional `origin` is passed as
+    `AfterTransactionEvent.origin` to the document's `observe_after_transaction` callbacks.
+
+    Raises:
+        EncodingException: If any of the `updates` is malformed. All of them are decoded before
+            applying any, so nothing is applied in that case.
+
+    Example::
+
+        from y_py import YDoc, apply_updates
+
+        doc = YDoc()
+        log = []
+        doc.observe_update_v1(lambda update, origin: log.append(update))
+        for word in ['hello', ' ', 'world']:
+            with doc.begin_transaction() as txn:
+                doc.get_text('name').extend(txn, word)
+
+        restored = YDoc()
+        apply_updates(restored, log)
+    """
+
+def encode_state_vector_v2(doc: YDoc) -> EncodedStateVector:
+    """
+    Encodes a state vector of a given Ypy document into its binary representation using lib0 v2
+    encoding. It's meant to be used together with `encode_state_as_update_v2`.
+    """
+
+def encode_state_as_update_v2(doc: YDoc, vector: Optional[Binary] = None) -> YDocUpdate:
+    """
+    Encodes all updates that have happened since a given version `vector` into a compact delta
+    representation using lib0 v2 encoding. A `vector` is expected to be produced by
+    `encode_state_vector_v2`. If it has not been provided, generated delta payload will contain
+    all changes of a current Ypy document.
+
+    Raises:
+        EncodingException: If `vector` is malformed. It's a subclass of `ValueError`.
+
+    Example::
+
+        from y_py import YDoc, encode_state_vector_v2, encode_state_as_update_v2, apply_update_v2
+
+        local_doc = YDoc()
+        local_sv = encode_state_vector_v2(local_doc)
+
+        remote_doc = YDoc()
+        remote_delta = encode_state_as_update_v2(remote_doc, local_sv)
+
+        apply_update_v2(local_doc, remote_delta)
+    """
+
+def apply_update_v2(doc: YDoc, diff: Binary, origin: Optional[Any] = None):
+    """
+    Applies delta update generated by the remote document replica to a current document. This
+    method assumes that a payload maintains lib0 v2 encoding format. An optional `origin` is passed
+    as `AfterTransactionEvent.origin` to the document's `observe_after_transaction` callbacks.
+
+    Raises:
+        EncodingException: If `diff` is malformed. It's a subclass of `ValueError`.
+    """
+
+def encode_state_from_snapshot(doc: YDoc, snapshot: bytes) -> YDocUpdate:
+    """
+    Encodes an update, which reproduces contents of a given `doc` at the time when a `snapshot`
+    (obtained via `YDoc.snapshot`) was taken.
+
+    Raises:
+        ValueError: If the `doc` was created without `skip_gc=True`.
+    """
+
+def merge_updates(updates: List[YDocUpdate]) -> YDocUpdate:
+    """
+    Merges a list of lib0 v1 encoded `updates` into a single update, which is equivalent to
+    applying all of them one after another. It doesn't require any `YDoc` instance, which makes it
+    useful for compacting persisted update logs.
+
+    Raises:
+        EncodingException: If any of the `updates` is malformed. It's a subclass of `ValueError`.
+
+    Example::
+
+        from y_py import YDoc, merge_updates
+
+        doc = YDoc()
+        text = doc.get_text('name')
+        updates = []
+        doc.observe_after_transaction(lambda e: updates.append(e.get_update()))
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 0, 'hello')
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 5, ' world')
+
+        compacted = merge_updates(updates)
+    """
+
+def diff_updates(update: YDocUpdate, state_vector: EncodedStateVector) -> YDocUpdate:
+    """
+    Trims a lib0 v1 encoded `update` down to changes missing from a peer with a given lib0 v1
+    encoded `state_vector`. Like `merge_updates`, it doesn't require any `YDoc` instance.
+
+    Raises:
+        EncodingException: If `update` or `state_vector` is malformed. It's a subclass of
+            `ValueError`.
+    """
+
+def encode_relative_position(position: StickyIndex) -> bytes:
+    """
+    Encodes a `position` into a binary payload, which can be persisted or sent to other peers. The
+    format is compatible with relative positions encoded by Yjs.
+    """
+
+def decode_relative_position(data: bytes) -> StickyIndex:
+    """
+    Decodes a position from a binary payload created with `encode_relative_position`.
+
+    Raises:
+        EncodingException: If `data` is malformed. It's a subclass of `ValueError`.
+    """
+
+class Awareness:
+    """
+    Keeps track of presence information (like cursor positions or user names) of all peers
+    collaborating on a document, following the Yjs awareness protocol. Every client owns its own
+    state, which is not persisted as a part of the document.
+
+    Example::
+
+        from y_py import YDoc, Awareness, encode_awareness_update, apply_awareness_update
+
+        local = Awareness(YDoc())
+        local.set_local_state({"user": "alice", "cursor": 5})
+
+        remote = Awareness(YDoc())
+        apply_awareness_update(remote, encode_awareness_update(local))
+        remote.get_states()[local.client_id]  # {"user": "alice", "cursor": 5.0}
+    """
+
+    client_id: int
+    """Client ID of the document this awareness belongs to."""
+    def __init__(self, doc: YDoc):
+        """
+        Creates an awareness instance for a given document. The local client starts with an empty state.
+        """
+    def get_local_state(self) -> Optional[Dict[str, Any]]:
+        """
+        Returns:
+            The state of the local client, or `None` if it has been removed.
+        """
+    def set_local_state(self, state: Optional[Dict[str, Any]]):
+        """
+        Replaces the state of the local client. Setting it to `None` marks the client as offline.
+
+        States are exchanged as JSON, which doesn't tell integers and floats apart, so numbers
+        without a fractional part are read back as `int`s, both locally and by other peers.
+        """
+    def get_states(self) -> Dict[int, Dict[str, Any]]:
+        """
+        Returns:
+            States of all clients known to be online, keyed by their client IDs.
+        """
+    def observe(self, callback: Callable[[AwarenessEvent]]) -> int:
+        """
+        Subscribes a callback, which is called every time the awareness states get updated, either
+        locally or by applying an update.
+
+        Args:
+            callback: A function receiving an `AwarenessEvent`.
+        Returns:
+            An identifier that can be used to cancel the callback with `unobserve`.
+        """
+    def unobserve(self, subscription_id: int):
+        """
+        Cancels the callback associated with the `subscription_id`.
+        """
+
+class AwarenessEvent:
+    """
+    Describes clients affected by a change of awareness states.
+    """
+
+    added: List[int]
+    """Clients which had no state before."""
+    updated: List[int]
+    """Clients which had their states replaced, even if with an identical state."""
+    removed: List[int]
+    """Clients which had their states removed."""
+
+def encode_awareness_update(
+    awareness: Awareness, clients: Optional[List[int]] = None
+) -> bytes:
+    """
+    Encodes states of given clients into an awareness update, which can be sent to other peers.
+    The format is compatible with awareness updates of Yjs.
+
+    Args:
+        awareness: Awareness to encode the states of.
+        clients: IDs of clients to include. All known clients are included by default.
+    Raises:
+        KeyError: If `awareness` doesn't know one of the `clients`.
+    """
+
+def apply_awareness_update(awareness: Awareness, update: Binary) -> List[int]:
+    """
+    Applies an awareness update created by another peer with `encode_awareness_update`.
+
+    Returns:
+        IDs of clients whose states were added, updated or removed.
+    Raises:
+        EncodingException: If `update` is malformed. It's a subclass of `ValueError`.
+    """
+
+def sync_step_1(doc: YDoc) -> bytes:
+    """
+    Creates a sync step 1 message, which opens the synchronization with a remote peer by sending it the
+    state vector of `doc`. The peer answers with a sync step 2 message, created with `read_sync_step_1`
+    (or `read_sync_message`).
+
+    Messages follow the format of the Yjs `y-protocols/sync` module. Providers like `y-websocket` wrap
+    them into envelopes prefixed with their own message type (`0` for sync messages), which has to be
+    added and stripped by the caller.
+
+    Example::
+
+        from y_py import YDoc, sync_step_1, read_sync_message
+
+        local_doc = YDoc()
+        remote_doc = YDoc()
+
+        step_2 = read_sync_message(remote_doc, sync_step_1(local_doc))
+        read_sync_message(local_doc, step_2)
+    """
+
+def read_sync_step_1(doc: YDoc, msg: Binary) -> bytes:
+    """
+    Reads a sync step 1 message sent by a remote peer.
+
+    Returns:
+        A sync step 2 message with all changes of `doc` the peer has not seen yet.
+    Raises:
+        EncodingException: If `msg` is malformed or is not a sync step 1 message.
+    """
+
+def read_sync_step_2(doc: YDoc, msg: Binary, origin: Optional[Any] = None):
+    """
+    Reads a sync step 2 message sent by a remote peer and applies the update it carries to `doc`. An
+    optional `origin` is passed as `AfterTransactionEvent.origin` to the document's
+    `observe_after_transaction` callbacks.
+
+    Raises:
+        EncodingException: If `msg` is malformed or is not a sync step 2 message.
+    """
+
+def write_update(update: bytes) -> bytes:
+    """
+    Wraps an update (e.g. one received by `observe_after_transaction` callbacks) into a message, which can
+    be broadcasted to synchronized peers.
+    """
+
+def read_sync_message(
+    doc: YDoc, msg: Binary, origin: Optional[Any] = None
+) -> Optional[bytes]:
+    """
+    Reads any sync protocol message sent by a remote peer. Updates carried by sync step 2 and update
+    messages are applied to `doc` with an optional `origin`.
+
+    Returns:
+        A sync step 2 message answering a sync step 1 message, which should be sent back to the peer, or
+        `None` for other messages.
+    Raises:
+        EncodingException: If `msg` is malformed or of an unknown type.
+    """
+
 class YTransaction:
     """
     A transaction that serves as a proxy to document block store. Ypy shared data types execute
@@ -255,6 +921,15 @@ class YTransaction:
     """
 
     before_state: Dict[int, int]
+    origin: Optional[Any]
+    """
+    Origin given when this transaction was started, or `None` if it was not provided.
+    """
+    read_only: bool
+    """
+    True if this transaction was started with `YDoc.begin_read_transaction`, which means that it can
+    only be used to read the document.
+    """
 
     def get_text(self, name: str) -> YText:
         """
@@ -291,6 +966,39 @@ class YTransaction:
         Triggers a post-update series of operations without `free`ing the transaction. This includes
         compaction and optimization of internal representation of updates, triggering events etc.
         Ypy transactions are auto-committed when they are `free`d.
+
+        The transaction can still be used afterwards: subsequent operations are made as a part of a new transaction
+        with the same origin, committed on the next call to `commit` or when this one is released. Committing again
+        without making any changes in between does nothing.
+
+        Raises:
+            Exception: The first exception raised by an observer callback called during the commit.
+        """
+    def op_count(self) -> int:
+        """
+        Returns a number of changes made within this transaction (or since its last `commit`),
+        including changes applied from remote updates. Every inserted or deleted element counts as
+        one, with text measured in UTF-16 code units. It's cheap to call, so it can be used to split
+        transactions growing over an application-defined limit.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            array = doc.get_array("items")
+            with doc.begin_transaction() as txn:
+                for item in items:
+                    array.append(txn, item)
+                    if txn.op_count() >= 10_000:
+                        txn.commit()
+        """
+    def update_size(self) -> int:
+        """
+        Returns a size in bytes of a lib0 v1 encoded update with changes made within this
+        transaction so far. Blocks created by consecutive edits are merged once the transaction is
+        committed, so the update it produces may end up smaller. Unlike `op_count`, it has to
+        encode the update, so its cost grows with the number of changes.
         """
     def state_vector_v1(self) -> EncodedStateVector:
         """
@@ -320,7 +1028,7 @@ class YTransaction:
                 del remote_txn
 
         """
-    def diff_v1(self, vector: Optional[EncodedStateVector] = None) -> YDocUpdate:
+    def diff_v1(self, vector: Optional[Binary] = None) -> YDocUpdate:
         """
         Encodes all updates that have happened since a given version `vector` into a compact delta
         representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
@@ -346,8 +1054,11 @@ class YTransaction:
             finally:
                 del local_txn
                 del remote_txn
+
+        Raises:
+            EncodingException: If `vector` is malformed.
         """
-    def apply_v1(self, diff: YDocUpdate):
+    def apply_v1(self, diff: Binary):
         """
         Applies delta update generated by the remote document replica to a current transaction's
         document. This method assumes that a payload maintains lib0 v1 encoding format.
@@ -371,9 +1082,40 @@ class YTransaction:
             finally:
                 del local_txn
                 del remote_txn
+
+        Raises:
+            EncodingException: If `diff` is malformed, or describes changes that can't be integrated.
+        """
+    def state_vector_v2(self) -> EncodedStateVector:
+        """
+        Encodes a state vector of a given transaction document into its binary representation using
+        lib0 v2 encoding. Counterpart of `state_vector_v1`.
+        """
+    def diff_v2(self, vector: Optional[Binary] = None) -> YDocUpdate:
+        """
+        Encodes all updates that have happened since a given version `vector` into a compact delta
+        representation using lib0 v2 encoding. A `vector` is expected to be produced by
+        `state_vector_v2`. Counterpart of `diff_v1`.
+
+        Raises:
+            EncodingException: If `vector` is malformed.
+        """
+    def apply_v2(self, diff: Binary):
+        """
+        Applies delta update generated by the remote document replica to a current transaction's
+        document. This method assumes that a payload maintains lib0 v2 encoding format.
+
+        Raises:
+            EncodingException: If `diff` is malformed, or describes changes that can't be integrated.
         """
     def __enter__() -> YTransaction: ...
-    def __exit__() -> bool: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool:
+        """
+        Commits the transaction when the `with` block closes. Transactions can't be rolled back, so when
+        the block raises an exception, changes made before are committed as well (and propagated to
+        observers) before the exception is re-raised. Writes that need to be atomic should be prepared
+        and validated before the block.
+        """
 
 class YText:
     """
@@ -386,6 +1128,8 @@ class YText:
     `YText` structure internally uses UTF-8 encoding and its length is described in a number of
     bytes rather than individual characters (a single UTF-8 code point can consist of many bytes).
 
+    Editing methods accept negative indexes, which count from the end of the text, like in Python.
+
     Like all Yrs shared data types, `YText` is resistant to the problem of interleaving (situation
     when characters inserted one after another may interleave with other peers concurrent inserts
     after merging all updates together). In case of Yrs conflict resolution is solved by using
@@ -414,59 +1158,506 @@ class YText:
         Returns:
             The string representation wrapped in 'YText()'
         """
+    def __eq__(self, other: object) -> bool:
+        """
+        Compares the string contents of this `YText` with another `YText` or a plain `str`. Embeds and formatting
+        are not taken into account. Since equality is based on contents, `YText` instances are not hashable.
+        """
+    __hash__: None  # type: ignore[assignment]
     def __len__(self) -> int:
         """
         Returns:
-            The length of an underlying string stored in this `YText` instance, understood as a number of UTF-8 encoded bytes.
+            The length of an underlying string stored in this `YText` instance, measured in units of the document's
+            offset kind (UTF-8 bytes by default and for preliminary instances). Embeds count as a single unit. Use
+            `char_len` to count characters.
+
+        It doesn't open a transaction, so it can be used while another one is open, in which case it includes
+        changes made with that transaction so far.
+        """
+    def char_len(self) -> int:
+        """
+        Returns:
+            The number of Unicode code points of this text, independent of the document's offset kind. Embeds count
+            as a single character.
+        """
+    def code_unit_len(self) -> int:
+        """
+        Returns:
+            The number of UTF-16 code units of this text, which is how JavaScript clients measure string lengths.
+            Embeds count as a single unit.
+        """
+    def __getitem__(self, index: Union[int, slice]) -> Any:
+        """
+        Returns a character at a given index or a substring selected by a given slice. Indexes are
+        measured in units of the document's offset kind (UTF-8 bytes for preliminary instances),
+        so they match the ones used by `insert` or `delete_range`. Characters spanning multiple
+        units are selected by the index of their first unit, while slices are clamped the same way
+        as Python strings do. Embeds take a single unit: an integer index returns the embedded
+        value, while slices skip them, the same way as the string representation of a text does.
+
+        Raises:
+            IndexError: If an integer `index` is out of range.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc(offset_kind="utf32")
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello world')
+
+            assert text[4] == 'o'
+            assert text[-5:] == 'world'
+        """
+    def __contains__(self, substring: str) -> bool:
+        """
+        Returns:
+            True if a given `substring` occurs anywhere in this text. Matches can't span embeds.
+        """
+    def find(self, substring: str, start: Optional[int] = None) -> int:
+        """
+        Args:
+            substring: Text to search for.
+            start: Index to start the search at. Negative values count from the end of the text.
+        Returns:
+            The index of the first occurrence of a `substring` or -1 if it wasn't found. Like
+            `__getitem__`, indexes are measured in units of the document's offset kind, so they can
+            be passed directly to `insert` or `delete_range`.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello world')
+
+            assert text.find('o') == 4
+            assert text.find('o', 5) == 7
+            assert text.find('xyz') == -1
+        """
+    def index(self, substring: str, start: Optional[int] = None) -> int:
+        """
+        Works like `find`, but raises a `ValueError` when the `substring` wasn't found.
+        """
+    def to_delta(self, txn: YTransaction) -> List[YTextChangeInsert]:
+        """
+        Returns:
+            The whole content of this text as a list of Quill-style delta operations. Each operation
+            has an `insert` key holding either a run of text sharing the same formatting or a single
+            embed, and an optional `attributes` key with that formatting.
+
+        Unlike `YTextEvent.delta`, which describes changes made by a single transaction, this
+        describes the current state of the text and can be used to initialize an editor.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello world')
+                text.format(txn, 0, 5, {'bold': True})
+                delta = text.to_delta(txn)
+
+            assert delta == [
+                {'insert': 'hello', 'attributes': {'bold': True}},
+                {'insert': ' world'},
+            ]
+        """
+    def diff(
+        self, txn: YTransaction, old: bytes, new: Optional[bytes] = None
+    ) -> List[YTextDelta]:
+        """
+        Compares two versions of this text captured by `YDoc.snapshot`.
+
+        Args:
+            txn: A transaction used to read the document.
+            old: A snapshot of the earlier version.
+            new: A snapshot of the later version. Defaults to the current state of the text.
+
+        Returns:
+            A list of Quill-style delta operations turning the `old` version into the `new` one,
+            in the same shape as accepted by `apply_delta`: unchanged text is skipped with
+            `retain`, removed text is deleted with `delete`, while added text is inserted with
+            `insert` together with its formatting `attributes`. Changes of formatting alone and
+            removed embeds are not reported.
+
+        Snapshots can only be taken in documents created with `skip_gc=True`, which keeps deleted
+        contents around.
+
+        Raises:
+            RuntimeError: If a peer made just one or two changes of a single element each (like
+                typing one character) to the document, which yrs can't compare snapshots of.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc(skip_gc=True)
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello world')
+            old = doc.snapshot()
+            with doc.begin_transaction() as txn:
+                text.delete_range(txn, 0, 6)
+                text.extend(txn, '!')
+                delta = text.diff(txn, old)
+
+            assert delta == [{'delete': 6}, {'retain': 5}, {'insert': '!'}]
+        """
+    def apply_delta(self, txn: YTransaction, ops: List[YTextDelta]):
+        """
+        Applies a list of Quill-style delta operations to this text, moving through it from the
+        beginning. Each operation is a dictionary with exactly one of the following keys:
+
+        - `retain`: skips over a given number of characters, formatting them with `attributes` if
+          these were provided.
+        - `insert`: inserts a string or an embed object, formatted with `attributes`. Unlike
+          `YText.insert`, formatting of preceding text is not inherited when `attributes` are absent.
+        - `delete`: removes a given number of characters.
+
+        All operations are validated before any change is made, and malformed ones raise a
+        `TypeError`. This method only works for `YText` instances that already have been integrated
+        into document store.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello world')
+                text.apply_delta(txn, [
+                    {'retain': 5, 'attributes': {'bold': True}},
+                    {'delete': 6},
+                    {'insert': '!'},
+                ])
+                delta = text.to_delta(txn)
+
+            assert delta == [{'insert': 'hello', 'attributes': {'bold': True}}, {'insert': '!'}]
+        """
+    def insert(
+        self,
+        txn: YTransaction,
+        index: int,
+        chunk: str,
+        attributes: Dict[str, Any] = {},
+    ):
+        """
+        Inserts a string of text into the `YText` instance starting at a given `index`.
+        Attributes are optional style modifiers (`{"bold": True}`) that can be attached to the inserted string.
+        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+
+        Inserted text is anchored to the characters around `index`, so there's nothing to associate it with: chunks
+        inserted concurrently by different peers at the same index are ordered by their client IDs, the same way on
+        every peer. Which side of such inserts a cursor ends up on is decided by the `assoc` of its `sticky_index`
+        instead.
+
+        Raises:
+            TypeError: If `chunk` is not a string.
+            IndexError: If the `index` is past the end of the text.
+            ValueError: If the `index` splits a character spanning more than one offset unit (eg. an emoji in a
+                document measuring offsets in UTF-16 code units).
+        """
+    def insert_many(
+        self,
+        txn: YTransaction,
+        index: int,
+        chunks: Iterable[str],
+        attributes: Dict[str, Any] = {},
+    ):
+        """
+        Inserts text produced by an iterable of string `chunks` (eg. a generator reading a file), starting at a
+        given `index`. Chunks are joined first and inserted as a single operation, which is much faster than
+        inserting them one by one.
+        """
+    def insert_embed(
+        self,
+        txn: YTransaction,
+        index: int,
+        embed: Any,
+        attributes: Dict[str, Any] = {},
+    ):
+        """
+        Inserts embedded content into the YText at the provided index. Attributes are user-defined metadata associated with the embedded content.
+
+        Embeds inserted into a preliminary instance are kept aside from its string and inserted in the same order once it
+        gets integrated, after the string. Their indexes are not shifted by later edits of the string, and those which
+        no longer fit are placed at the end.
+        """
+    def attributes_at(self, txn: YTransaction, index: int) -> Dict[str, Any]:
+        """
+        Returns formatting attributes of a character (or an embed) found at a given `index`. Unformatted
+        characters, as well as the end of the text, have no attributes.
+
+        Example:
+
+        ```python
+        from y_py import YDoc
+
+        doc = YDoc()
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 0, 'hello world', {'bold': True})
+            text.format(txn, 6, 5, {'color': 'red'})
+            assert text.attributes_at(txn, 0) == {'bold': True}
+            assert text.attributes_at(txn, 6) == {'bold': True, 'color': 'red'}
+        ```
+
+        Args:
+            txn: The transaction used to read the formatting.
+            index: Position of the character, measured in units of the document's offset kind.
+        Raises:
+            IndexError: If the `index` is larger than the length of the text.
+        """
+    def format(
+        self, txn: YTransaction, index: int, length: int, attributes: Dict[str, Any]
+    ):
+        """
+        Wraps an existing piece of text within a range described by `index`-`length` parameters with
+        formatting blocks containing provided `attributes` metadata. This method only works for
+        `YText` instances that already have been integrated into document store.
+
+        The range is measured in the document's offset units. A range extending past the end of
+        the text is clamped to it. A `ValueError` is raised when the range is empty (eg. `length`
+        is 0) or splits a character, and an `IndexError` when `index` is past the end of the text.
+        """
+    def remove_format(
+        self,
+        txn: YTransaction,
+        index: int,
+        length: int,
+        keys: Optional[List[str]] = None,
+    ):
+        """
+        Removes formatting attributes with given `keys` from a range described by `index`-`length` parameters.
+        If `keys` are not provided, all formatting found within that range is removed, except for comments, which
+        can be removed with `remove_comment`. This method only works for `YText` instances that already have been
+        integrated into document store.
+
+        Example:
+
+        ```python
+        from y_py import YDoc
+
+        doc = YDoc()
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 0, 'hello world', {'bold': True, 'italic': True})
+            text.remove_format(txn, 0, 5, ['italic'])
+            text.remove_format(txn, 6, 5)
+        assert text.to_delta(doc.begin_transaction()) == [
+            {'insert': 'hello', 'attributes': {'bold': True}},
+            {'insert': ' ', 'attributes': {'bold': True, 'italic': True}},
+            {'insert': 'world'},
+        ]
+        ```
+
+        Raises:
+            IndexError: If the range exceeds the length of the text.
+        """
+    def extend(self, txn: YTransaction, chunk: str):
+        """
+        Appends a given `chunk` of text at the end of current `YText` instance.
+
+        Raises:
+            TypeError: If `chunk` is not a string.
+        """
+    def delete(self, txn: YTransaction, index: int):
+        """
+        Deletes the character at the specified `index`.
+
+        Raises:
+            IndexError: If the `index` is out of range.
+        """
+    def delete_range(self, txn: YTransaction, index: int, length: int):
+        """
+        Deletes a specified range of of characters, starting at a given `index`.
+        Both `index` and `length` are measured in offset units of the document (see `offset_kind` option of `YDoc`).
+
+        Raises:
+            IndexError: If the range doesn't fit within the text.
+            ValueError: If the range splits a character spanning more than one offset unit.
+        """
+    def clear(self, txn: YTransaction):
+        """
+        Removes all contents of this `YText`, including embeds.
+        """
+    def set_content(self, txn: YTransaction, content: str):
+        """
+        Replaces the contents of this `YText` with `content`, editing only the part between their common prefix
+        and suffix. Unchanged text keeps its identity, which is cheaper and friendlier to concurrent edits than
+        deleting everything and inserting `content`. If this `YText` contains embeds, its whole contents are replaced.
+
+        Args:
+            txn: The transaction used to apply the edit.
+            content: New contents of the text.
+        """
+    def replace(
+        self, txn: YTransaction, old: str, new: str, count: Optional[int] = None
+    ) -> int:
+        """
+        Replaces non-overlapping occurrences of `old` with `new`, searching from the beginning of the text. Indexes
+        are measured using the offset kind of the document, so multibyte characters are handled correctly. Texts
+        containing embeds can't be searched, and raise a `ValueError`.
+
+        Example:
+
+        ```python
+        from y_py import YDoc
+
+        doc = YDoc()
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.extend(txn, 'one fish, two fish')
+            assert text.replace(txn, 'fish', 'cat') == 2
+        assert str(text) == 'one cat, two cat'
+        ```
+
+        Args:
+            txn: The transaction used to apply the edits.
+            old: Substring to search for. It can't be empty.
+            new: Replacement of every found occurrence.
+            count: Maximal number of occurrences to replace. All of them are replaced by default.
+        Returns:
+            The number of replaced occurrences.
         """
-    def to_json(self) -> str:
+    def lines(self, txn: YTransaction) -> List[Tuple[str, int]]:
         """
+        Splits this text on `\\n` and returns its lines (without the line breaks) together with their start
+        indexes. Indexes are measured using the offset kind of the document, so that they can be passed to
+        `insert` or `delete_range` directly. Embeds are not a part of the lines' text, but they still occupy
+        a single index.
+
+        Example:
+
+        ```python
+        from y_py import YDoc
+
+        doc = YDoc()
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.extend(txn, 'first\\nsecond')
+            assert text.lines(txn) == [('first', 0), ('second', 6)]
+        ```
+
+        Args:
+            txn: The transaction used to read the text.
         Returns:
-            The underlying shared string stored in this data type.
+            A list of `(line, start_index)` tuples. An empty text consists of a single empty line.
         """
-    def insert(
+    def indent_lines(
         self,
         txn: YTransaction,
-        index: int,
-        chunk: str,
-        attributes: Dict[str, Any] = {},
+        start_index: int,
+        end_index: int,
+        indent_str: str = "    ",
     ):
         """
-        Inserts a string of text into the `YText` instance starting at a given `index`.
-        Attributes are optional style modifiers (`{"bold": True}`) that can be attached to the inserted string.
-        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+        Inserts `indent_str` at the beginning of every line overlapping with the given range, including
+        partially selected lines. Line boundaries are computed in the document's offset units.
+
+        Args:
+            txn: The transaction used to apply all edits.
+            start_index: Start of the selection.
+            end_index: End of the selection. A selection ending right at the start of a line doesn't include it.
+            indent_str: Indentation to insert.
         """
-    def insert_embed(
+    def dedent_lines(
         self,
         txn: YTransaction,
-        index: int,
-        embed: Any,
-        attributes: Dict[str, Any] = {},
+        start_index: int,
+        end_index: int,
+        indent_str: str = "    ",
     ):
         """
-        Inserts embedded content into the YText at the provided index. Attributes are user-defined metadata associated with the embedded content.
-        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+        Removes a single level of indentation from every line overlapping with the given range, including
+        partially selected lines. Lines starting with `indent_str` lose that prefix, while the remaining ones lose
+        at most as many leading spaces or tabs as there are characters in `indent_str`.
+
+        Args:
+            txn: The transaction used to apply all edits.
+            start_index: Start of the selection.
+            end_index: End of the selection. A selection ending right at the start of a line doesn't include it.
+            indent_str: Indentation to remove.
         """
-    def format(
-        self, txn: YTransaction, index: int, length: int, attributes: Dict[str, Any]
-    ):
+    def sticky_index(
+        self, txn: YTransaction, index: int, assoc: int = 0
+    ) -> StickyIndex:
         """
-        Wraps an existing piece of text within a range described by `index`-`length` parameters with
-        formatting blocks containing provided `attributes` metadata. This method only works for
-        `YText` instances that already have been integrated into document store
+        Returns a position pointing at a given `index` of this text, which stays in place when the
+        text before it gets edited. It can be used to track cursors and selections of remote peers.
+
+        Args:
+            txn: The transaction used to read the text.
+            index: The index to point at.
+            assoc: If negative, the position is associated with the character before `index`
+                instead of the one after it, so text inserted exactly at the position ends up after
+                it rather than before it.
+        Raises:
+            IndexError: If `index` is greater than the length of this text.
+            ValueError: If this is a nested text without any content and there is no character
+                next to `index` in direction of `assoc`.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'hello world')
+                cursor = text.sticky_index(txn, 6)
+                text.insert(txn, 0, 'well, ')
+                assert text.resolve_index(txn, cursor) == 12
         """
-    def extend(self, txn: YTransaction, chunk: str):
+    def resolve_index(
+        self, txn: YTransaction, position: StickyIndex
+    ) -> Optional[int]:
         """
-        Appends a given `chunk` of text at the end of current `YText` instance.
+        Returns:
+            A current index of a `position` created with `sticky_index`. If the character the
+            position was anchored to has been deleted, it resolves to where that character used to
+            be, as long as it wasn't garbage collected yet (see `YDoc(skip_gc=True)`). Otherwise
+            `None` is returned, as it is for positions created by a different text.
         """
-    def delete(self, txn: YTransaction, index: int):
+    def add_comment(
+        self,
+        txn: YTransaction,
+        start_index: int,
+        end_index: int,
+        comment_id: str,
+        data: Any,
+    ):
         """
-        Deletes the character at the specified `index`.
+        Anchors a comment to a range of text. The range is tracked as the text gets edited: insertions made
+        inside of it extend it, while deletions shrink it. Comments are synchronized with other peers together
+        with the text. Adding a comment with an already used `comment_id` replaces its range and data.
+
+        Args:
+            txn: The transaction used to add the comment.
+            start_index: Start of the commented range (inclusive).
+            end_index: End of the commented range (exclusive).
+            comment_id: Unique identifier of the comment.
+            data: Arbitrary metadata attached to the comment.
         """
-    def delete_range(self, txn: YTransaction, index: int, length: int):
+    def comments(self, txn: YTransaction) -> List[YTextComment]:
         """
-        Deletes a specified range of of characters, starting at a given `index`.
-        Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
+        Returns:
+            Comments added to this text, ordered by their current position. Comments whose entire range has been
+            deleted are reported at the end as `orphaned` instead of being dropped.
+        """
+    def remove_comment(self, txn: YTransaction, comment_id: str):
+        """
+        Removes a comment together with its range anchors.
+
+        Raises:
+            KeyError: If there's no comment with given `comment_id`.
         """
     def observe(self, f: Callable[[YTextEvent]]) -> SubscriptionId:
         """
@@ -496,6 +1687,32 @@ class YText:
             subscription_id: reference to a subscription provided by the `observe` method.
         """
 
+class YTextComment(TypedDict):
+    """A comment anchored to a range of `YText`."""
+
+    id: str
+    start: Optional[int]
+    end: Optional[int]
+    data: Any
+    orphaned: bool
+
+class StickyIndex:
+    """
+    A position within a `YText`, which - unlike a plain index - stays in place when the text around
+    it gets edited, including concurrent edits made by other peers. It's created with
+    `YText.sticky_index` and can be turned back into an index with `YText.resolve_index`.
+
+    Positions are anchored to characters, so `assoc` decides which one of the two characters
+    adjacent to the index is used: the one after it (when `assoc` is `0` or more) or the one before
+    it (when `assoc` is negative). Embeds can't be used as anchors, so next to them the closest
+    character in direction of `assoc` is used instead.
+
+    Positions are compared by what they are anchored to, which is why they are not hashable.
+    """
+
+    assoc: int
+    __hash__: None  # type: ignore[assignment]
+
 class YTextEvent:
     """
     Communicates updates that occurred during a transaction for an instance of `YText`.
@@ -505,10 +1722,23 @@ class YTextEvent:
 
     target: YText
     delta: List[YTextDelta]
+    attributes_changed: List[YTextChangeRetain]
+    """
+    Only the `retain` operations of `delta` which carry formatting `attributes`, ie. changes of
+    formatting of a text which was already there. An event with no other kinds of operations (no
+    `insert` or `delete`) only restyles the text, without changing its contents.
+    """
     def path(self) -> List[Union[int, str]]:
         """
         Returns:
-            Array of keys and indexes creating a path from root type down to current instance of shared type (accessible via `target` getter).
+            Array of keys and indexes creating a path from the observed shared type (a root type for `YDoc.observe_deep`)
+            down to current instance of shared type (accessible via `target` getter).
+        """
+    def clock_ranges(self) -> Dict[int, Tuple[int, int]]:
+        """
+        Returns:
+            Clients whose clocks have advanced during the transaction which produced this event,
+            mapped to a `(before, after)` pair of their clocks, like `AfterTransactionEvent.clock_ranges`.
         """
 
 YTextDelta = Union[YTextChangeInsert, YTextChangeDelete, YTextChangeRetain]
@@ -525,6 +1755,11 @@ class YTextChangeRetain(TypedDict):
     attributes: Optional[Any]
 
 class YArray:
+    """
+    A collection used to store data in an indexed sequence structure. Both reading and editing
+    methods accept negative indexes, which count from the end of the array, like in Python.
+    """
+
     prelim: bool
     """True if this element has not been integrated into a YDoc."""
 
@@ -541,6 +1776,9 @@ class YArray:
         """
         Returns:
             Number of elements in the `YArray`
+
+        It doesn't open a transaction, so it can be used while another one is open, in which case it
+        includes changes made with that transaction so far.
         """
     def __str__(self) -> str:
         """
@@ -552,25 +1790,82 @@ class YArray:
         Returns:
             The string representation of YArray wrapped in `YArray()`
         """
+    def __eq__(self, other: object) -> bool:
+        """
+        Compares the elements of this `YArray` with another `YArray` or a plain `list`. Nested shared types are
+        compared using their contents. Since equality is based on contents, `YArray` instances are not hashable.
+        """
+    __hash__: None  # type: ignore[assignment]
     def to_json(self) -> str:
         """
         Converts an underlying contents of this `YArray` instance into their JSON representation.
         """
+    def to_json_value(self) -> List[Any]:
+        """
+        Returns contents of this `YArray` as plain Python lists and dicts, which can be serialized with
+        `json.dumps`. Nested shared types are converted recursively, with texts read as strings (embeds are
+        omitted). Binary values are represented as base64 encoded strings and datetimes as ISO 8601 strings.
+        """
+    def to_python(self) -> List[Any]:
+        """
+        Returns contents of this `YArray` as plain Python lists and dicts, converting nested shared types
+        recursively in a single pass, with texts read as strings (embeds are omitted). Unlike `to_json_value`,
+        binary values and datetimes are kept as `bytes` and `datetime` objects.
+        """
+    def to_list(self, deep: bool = False) -> List[Any]:
+        """
+        Returns:
+            Elements of this `YArray` as a Python list. Nested shared types are kept as `YArray`, `YMap`
+            or `YText` instances, unless `deep` is set, in which case they are converted the same way as
+            with `to_python`.
+        """
+    def to_msgpack(self, txn: YTransaction) -> bytes:
+        """
+        Serializes current contents of this `YArray` using MessagePack format. Binary values are written using
+        the MessagePack `bin` type. Only the materialized value is serialized, not the CRDT metadata.
+
+        Args:
+            txn: A transaction in which the contents are read.
+        Returns:
+            MessagePack-encoded list.
+        """
+    @staticmethod
+    def from_msgpack(data: bytes) -> YArray:
+        """
+        Creates a preliminary `YArray` from a MessagePack-encoded list, as produced by `YArray.to_msgpack`.
+        Binary values are read as `bytes`.
+
+        Raises:
+            EncodingException: If `data` is not a valid MessagePack payload, or if it's nested
+                deeper than 128 levels.
+            TypeError: If the encoded value is not a list.
+        """
     def insert(self, txn: YTransaction, index: int, item: Any):
         """
         Inserts an item at the provided index in the `YArray`.
+
+        Raises:
+            IndexError: If the `index` is past the end of the array.
         """
     def insert_range(self, txn: YTransaction, index: int, items: Iterable):
         """
         Inserts a given range of `items` into this `YArray` instance, starting at given `index`.
+        Consecutive plain values are inserted as a single block, which is much cheaper than
+        inserting them one by one. If any of the `items` can't be inserted, the array is left
+        unchanged.
+
+        Raises:
+            IndexError: If the `index` is past the end of the array.
         """
     def append(self, txn: YTransaction, item: Any):
         """
-        Adds a single item to the end of the `YArray`
+        Adds a single item to the end of the `YArray`. Like with `YMap.set`, preliminary shared
+        types are integrated into the document together with their contents.
         """
     def extend(self, txn: YTransaction, items: Iterable):
         """
-        Appends a sequence of `items` at the end of this `YArray` instance.
+        Appends a sequence of `items` at the end of this `YArray` instance. Prefer it over calling
+        `append` in a loop, as consecutive plain values are inserted as a single block.
         """
     def delete(self, txn: YTransaction, index: int):
         """
@@ -579,20 +1874,46 @@ class YArray:
         Args:
             txn: The transaction where the array is being modified.
             index: The index of the element to be deleted.
+
+        Raises:
+            IndexError: If the `index` is out of range.
         """
     def delete_range(self, txn: YTransaction, index: int, length: int):
         """
         Deletes a range of items of given `length` from current `YArray` instance,
         starting from given `index`.
+
+        Raises:
+            IndexError: If the range doesn't fit within the array.
+        """
+    def pop(self, txn: YTransaction, index: int = -1) -> Any:
+        """
+        Removes an element at a given `index` (the last one by default) and returns it. Negative indexes are
+        counted from the end of the array. Like with `YMap.pop`, removed shared types are returned as preliminary
+        copies of their contents, which can be inserted into a document again.
+
+        Raises:
+            IndexError: If the array is empty or the `index` is out of range.
+        """
+    def clear(self, txn: YTransaction):
+        """
+        Removes all elements from this `YArray`.
         """
     def move_to(self, txn: YTransaction, source: int, target: int):
         """
-        Moves a single item found at `source` index into `target` index position.
+        Moves a single item found at `source` index into `target` index position, so that it ends
+        up right before the element that was at `target` before the move. Use the length of the
+        array as `target` to move an element to its end.
+
+        Unlike deleting an element and inserting it again, a moved element keeps its identity, so
+        concurrent moves of the same element made by different peers don't produce duplicates.
 
         Args:
             txn: The transaction where the array is being modified.
             source: The index of the element to be moved.
             target: The new position of the element.
+        Raises:
+            IndexError: If either index is out of range.
         """
     def move_range_to(self, txn: YTransaction, start: int, end: int, target: int):
         """
@@ -606,6 +1927,8 @@ class YArray:
             start: The index of the first element of the range (inclusive).
             end: The index of the last element of the range (inclusive).
             target: The new position of the element.
+        Raises:
+            IndexError: If any of the indexes is out of range.
         
         Example:
         ```
@@ -625,11 +1948,54 @@ class YArray:
         """
         Returns:
             The element stored under given `index` or a new list of elements from the slice range.
+            Slices follow Python semantics, including negative indexes and steps.
+
+        Raises:
+            IndexError: If an integer `index` is out of range.
+            ValueError: If a slice step is zero.
+        """
+    def get_type(
+        self, index: int
+    ) -> Literal["text", "array", "map", "xml", "primitive"]:
+        """
+        Returns a kind of an element at a given `index`, without converting it into a Python
+        object. Nested shared types are reported as `"text"`, `"array"`, `"map"` or `"xml"`, while any
+        other value is `"primitive"`. Negative indexes count from the end of the array.
+
+        Raises:
+            IndexError: If the `index` is out of range.
+        """
+    def __setitem__(self, index: Union[int, slice], value: Any):
+        """
+        Replaces an element at a given `index`, or elements selected by a slice with items of an
+        iterable `value`, following Python list semantics. Extended slices (with a step other than
+        one) can only be assigned the same number of items.
+
+        Only preliminary arrays can be modified this way. An integrated `YArray` doesn't keep a
+        reference to its `YDoc`, so it can't open an implicit transaction: its changes have to be
+        made within an explicit transaction, using `insert` and `delete` methods.
+
+        Raises:
+            IndexError: If an integer `index` is out of range.
+            ValueError: If an extended slice is assigned a different number of items.
+            TypeError: If this `YArray` is integrated into a document.
+        """
+    def __delitem__(self, index: Union[int, slice]):
+        """
+        Removes an element at a given `index`, or all elements selected by a slice. Like
+        `__setitem__`, it only works with preliminary arrays.
+
+        Raises:
+            IndexError: If an integer `index` is out of range.
+            TypeError: If this `YArray` is integrated into a document.
         """
     def __iter__(self) -> Iterator:
         """
         Returns:
             An iterator that can be used to traverse over the values stored withing this instance of `YArray`.
+            Values are read lazily in chunks, so the array is never copied as a whole. Chunks are read
+            again whenever the array changes, and if it shrinks during the iteration, the iterator
+            stops early.
 
         Example::
 
@@ -642,18 +2008,62 @@ class YArray:
             for item in array:
                 print(item)
         """
-    def observe(self, f: Callable[[YArrayEvent]]) -> SubscriptionId:
+    def index(self, value: Any) -> int:
+        """
+        Nested shared types are compared using their JSON-like contents, so a nested `YArray` is
+        equal to a list with the same elements.
+
+        Args:
+            value: The element to look for.
+        Returns:
+            The position of the first element of this `YArray` equal to `value`.
+        Raises:
+            ValueError: If there is no such element.
+        """
+    def count(self, value: Any) -> int:
+        """
+        Args:
+            value: The element to look for.
+        Returns:
+            A number of elements of this `YArray` equal to `value`. Nested shared types are
+            compared the same way as in `YArray.index`.
+        """
+    def transact(
+        self, doc: YDoc, callback: Callable[[YTransaction, YArray], Any]
+    ) -> Any:
+        """
+        Opens a new transaction on `doc` and calls `callback` with that transaction and this array.
+        The transaction is committed once `callback` returns.
+
+        Args:
+            doc: The document this array belongs to.
+            callback: A function receiving the transaction and this array.
+        Returns:
+            The result of `callback`.
+        """
+    def observe(
+        self,
+        f: Union[Callable[[YArrayEvent]], Callable[[List[Event]]]],
+        with_snapshot: bool = False,
+        deep: bool = False,
+    ) -> SubscriptionId:
         """
         Assigns a callback function to listen to YArray updates.
 
         Args:
             f: Callback function that runs when the array object receives an update.
+            with_snapshot: If `True`, each event carries a `before` list with the array contents prior to the transaction.
+                Disabled by default, since the whole array has to be copied on every change.
+            deep: If `True`, the callback is triggered by changes to the array and all of its nested children
+                and receives a list of events, each carrying its `path`, the same way as with `observe_deep`.
+                Cannot be combined with `with_snapshot`.
         Returns:
             An identifier associated with the callback subscription.
         """
     def observe_deep(self, f: Callable[[List[Event]]]) -> SubscriptionId:
         """
         Assigns a callback function to listen to the aggregated updates of the YArray and its child elements.
+        Events are passed in the same order as with `YMap.observe_deep`.
 
         Args:
             f: Callback function that runs when the array object or components receive an update.
@@ -679,10 +2089,19 @@ class YArrayEvent:
 
     target: YArray
     delta: List[ArrayDelta]
+    before: Optional[List[Any]]
+    """Contents of the array prior to the transaction, if observed with `with_snapshot=True`."""
     def path(self) -> List[Union[int, str]]:
         """
         Returns:
-            Array of keys and indexes creating a path from root type down to current instance of shared type (accessible via `target` getter).
+            Array of keys and indexes creating a path from the observed shared type (a root type for `YDoc.observe_deep`)
+            down to current instance of shared type (accessible via `target` getter).
+        """
+    def clock_ranges(self) -> Dict[int, Tuple[int, int]]:
+        """
+        Returns:
+            Clients whose clocks have advanced during the transaction which produced this event,
+            mapped to a `(before, after)` pair of their clocks, like `AfterTransactionEvent.clock_ranges`.
         """
 
 ArrayDelta = Union[ArrayChangeInsert, ArrayChangeDelete, ArrayChangeRetain]
@@ -693,12 +2112,12 @@ class ArrayChangeInsert(TypedDict):
 
     insert: List[Any]
 
-class ArrayChangeDelete:
+class ArrayChangeDelete(TypedDict):
     """Update message that elements were deleted in a YArray."""
 
     delete: int
 
-class ArrayChangeRetain:
+class ArrayChangeRetain(TypedDict):
     """Update message that elements were left unmodified in a YArray."""
 
     retain: int
@@ -737,30 +2156,111 @@ class YMap:
         Returns:
             The string representation of the `YMap` wrapped in 'YMap()'
         """
+    def __eq__(self, other: object) -> bool:
+        """
+        Compares the entries of this `YMap` with another `YMap` or a plain `dict`. Nested shared types are
+        compared using their contents. Since equality is based on contents, `YMap` instances are not hashable.
+        """
+    __hash__: None  # type: ignore[assignment]
     def to_json(self) -> str:
         """
         Converts contents of this `YMap` instance into a JSON representation.
         """
+    def to_json_value(self) -> Dict[str, Any]:
+        """
+        Returns contents of this `YMap` as plain Python lists and dicts, which can be serialized with
+        `json.dumps`. Nested shared types are converted recursively, with texts read as strings (embeds are
+        omitted). Binary values are represented as base64 encoded strings and datetimes as ISO 8601 strings.
+        """
+    def to_python(self) -> Dict[str, Any]:
+        """
+        Returns contents of this `YMap` as plain Python lists and dicts, converting nested shared types
+        recursively in a single pass, with texts read as strings (embeds are omitted). Unlike `to_json_value`,
+        binary values and datetimes are kept as `bytes` and `datetime` objects.
+        """
+    def to_dict(self, deep: bool = False) -> Dict[str, Any]:
+        """
+        Returns:
+            Entries of this `YMap` as a Python dict, in no particular order. Nested shared types are kept as
+            `YArray`, `YMap` or `YText` instances, unless `deep` is set, in which case they are converted the
+            same way as with `to_python`.
+        """
+    def to_msgpack(self, txn: YTransaction) -> bytes:
+        """
+        Serializes current entries of this `YMap` using MessagePack format. Binary values are written using
+        the MessagePack `bin` type. Only the materialized value is serialized, not the CRDT metadata.
+
+        Args:
+            txn: A transaction in which the entries are read.
+        Returns:
+            MessagePack-encoded map.
+        """
+    @staticmethod
+    def from_msgpack(data: bytes) -> YMap:
+        """
+        Creates a preliminary `YMap` from a MessagePack-encoded map, as produced by `YMap.to_msgpack`.
+        Binary values are read as `bytes`.
+
+        Raises:
+            EncodingException: If `data` is not a valid MessagePack payload, or if it's nested
+                deeper than 128 levels.
+            TypeError: If the encoded value is not a map.
+        """
     def set(self, txn: YTransaction, key: str, value: Any):
         """
         Sets a given `key`-`value` entry within this instance of `YMap`. If another entry was
         already stored under given `key`, it will be overridden with new `value`.
+
+        Python `set` and `frozenset` values are rejected with a `TypeError`, since they have no
+        defined ordering. Convert them into a list (e.g. `sorted(value)`) before inserting.
+        Both `bytes` and `bytearray` values are stored as binary data and read back as `bytes`.
+        `datetime` values are stored as `{"__datetime__": <ISO 8601 string>}` maps, which other
+        peers can parse as well, and read back as `datetime` objects with the same UTC offset.
+
+        A preliminary `YText`, `YArray` or `YMap` is integrated into the document together with its
+        contents, after which the same instance can be used to modify the nested type. Shared
+        types which are already integrated can't be inserted again and raise
+        `MultipleIntegrationError`.
+        """
+    def setdefault(self, txn: YTransaction, key: str, default: Any = None) -> Any:
+        """
+        Returns a value stored under a given `key`, like `dict.setdefault` does. If there's no such entry, `default`
+        is inserted first. Shared types are returned in their integrated form, so nested structures can be lazily
+        initialized and then modified within the same transaction.
+
+        Args:
+            txn: A transaction to perform the insertion in.
+            key: The identifier of the entry.
+            default: A value inserted when there's no entry under `key`.
         """
     def update(
         self, txn: YTransaction, items: Union[Iterable[Tuple[str, Any]], Dict[str, Any]]
     ):
         """
-        Updates `YMap` with the contents of items.
+        Updates `YMap` with the contents of items. Like `dict.update`, entries override existing
+        ones stored under the same keys. All entries are validated before any of them is inserted,
+        so an invalid entry leaves this map unchanged.
 
         Args:
             txn: A transaction to perform the insertion updates.
-            items: An iterable object that produces key value tuples to insert into the YMap
+            items: A mapping (any object with a `keys` method) or an iterable object that produces
+                key value tuples to insert into the YMap
+        """
+    def clear(self, txn: YTransaction):
+        """
+        Removes all entries from this instance of `YMap`.
+
+        Args:
+            txn: The transaction used to remove the entries.
         """
     def pop(self, txn: YTransaction, key: str, fallback: Optional[Any] = None) -> Any:
         """
         Removes an entry identified by a given `key` from this instance of `YMap`, if such exists.
         Throws a KeyError if the key does not exist and fallback value is not provided.
 
+        The value is read before it's removed. Removed shared types are returned as preliminary
+        copies of their contents, which can be inserted into a document again.
+
         Args:
             txn: The current transaction from a YDoc.
             key: Identifier of the requested item.
@@ -769,11 +2269,12 @@ class YMap:
         Returns:
             The item at the key.
         """
-    def get(self, key: str, fallback: Any) -> Any | None:
+    def get(self, key: str, fallback: Any = None) -> Any | None:
         """
         Args:
             key: The identifier for the requested data.
-            fallback: If the key doesn't exist in the map, this fallback value will be returned.
+            fallback: If the key doesn't exist in the map, this fallback value will be returned. Entries storing
+                `None` are returned as is.
 
         Returns:
             Requested data or the provided fallback value.
@@ -786,6 +2287,39 @@ class YMap:
         Returns:
             Value of an entry stored under given `key` within this instance of `YMap`. Will throw a `KeyError` if the provided key is unassigned.
         """
+    def get_type(
+        self, key: str
+    ) -> Literal["text", "array", "map", "xml", "primitive"]:
+        """
+        Returns a kind of a value stored under a given `key`, without converting it into a Python
+        object. Nested shared types are reported as `"text"`, `"array"`, `"map"` or `"xml"`, while any
+        other value is `"primitive"`.
+
+        Example::
+
+            from y_py import YMap, YText
+
+            map = YMap({"title": YText("hello"), "count": 1})
+            assert map.get_type("title") == "text"
+            assert map.get_type("count") == "primitive"
+
+        Raises:
+            KeyError: If the `key` is unassigned.
+        """
+    def __delitem__(self, key: str):
+        """
+        Removes an entry stored under a given `key`. Only preliminary maps can be modified this way,
+        changes of an integrated `YMap` have to be made within a transaction using `pop`.
+
+        Raises:
+            KeyError: If the `key` is unassigned.
+            TypeError: If this `YMap` is integrated into a document.
+        """
+    def __contains__(self, key: str) -> bool:
+        """
+        Returns:
+            True if an entry is stored under a given `key`. The value itself is not read.
+        """
     def __iter__(self) -> Iterator[str]:
         """
         Returns:
@@ -806,25 +2340,50 @@ class YMap:
             with doc.begin_transaction() as txn:
                 map.set(txn, 'key1', 'value1')
                 map.set(txn, 'key2', true)
-            for (key, value) in map.items()):
+            for (key, value) in map.items():
                 print(key, value)
         """
     def keys(self) -> YMapKeysView:
         """
         Returns:
-            A view of all key identifiers in the YMap. The order of keys is not stable.
+            A view of all key identifiers in the YMap. The order of keys is not specified, but
+            stays the same for as long as the map is not modified.
         """
     def values(self) -> YMapValuesView:
         """
         Returns:
-            A view of all values in the YMap. The order of values is not stable.
+            A view of all values in the YMap, listed in the same order as keys returned by
+            `YMap.keys`.
+        """
+    def transact(
+        self, doc: YDoc, callback: Callable[[YTransaction, YMap], Any]
+    ) -> Any:
+        """
+        Opens a new transaction on `doc` and calls `callback` with that transaction and this map.
+        The transaction is committed once `callback` returns.
+
+        Args:
+            doc: The document this map belongs to.
+            callback: A function receiving the transaction and this map.
+        Returns:
+            The result of `callback`.
         """
-    def observe(self, f: Callable[[YMapEvent]]) -> SubscriptionId:
+    def observe(
+        self,
+        f: Union[Callable[[YMapEvent]], Callable[[List[Event]]]],
+        with_snapshot: bool = False,
+        deep: bool = False,
+    ) -> SubscriptionId:
         """
         Assigns a callback function to listen to YMap updates.
 
         Args:
             f: Callback function that runs when the map object receives an update.
+            with_snapshot: If `True`, each event carries a `before` dictionary with the map entries prior to the transaction.
+                Disabled by default, since the whole map has to be copied on every change.
+            deep: If `True`, the callback is triggered by changes to the map and all of its nested children
+                and receives a list of events, each carrying its `path`, the same way as with `observe_deep`.
+                Cannot be combined with `with_snapshot`.
         Returns:
             A reference to the callback subscription. Delete this observer in order to erase the associated callback function.
         """
@@ -832,6 +2391,10 @@ class YMap:
         """
         Assigns a callback function to listen to YMap and child element updates.
 
+        Events are passed in the order in which their targets appear in the document: events of
+        parent types come before the ones of their children, while siblings are ordered by their
+        indexes in arrays and by their keys in maps.
+
         Args:
             f: Callback function that runs when the map object or any of its tracked elements receive an update.
         Returns:
@@ -879,25 +2442,46 @@ class YMapEvent:
     """
     Communicates updates that occurred during a transaction for an instance of `YMap`.
     The `target` references the `YMap` element that receives the update.
-    The `delta` is a list of updates applied by the transaction.
-    The `keys` are a list of changed values for a specific key.
+    The `keys` describe changes of entries modified by the transaction, by their keys.
     """
 
     target: YMap
     """The element modified during this event."""
     keys: Dict[str, YMapEventKeyChange]
-    """A list of modifications to the YMap by key. 
-    Includes the type of modification along with the before and after state."""
+    """Modifications of the YMap entries by their keys, each with the type of modification along with the
+    value before and after the transaction. Values are converted the same way as by `YMap.__getitem__`.
+
+    Example::
+
+        {
+            'added': {'action': 'add', 'newValue': 1},
+            'changed': {'action': 'update', 'oldValue': 1, 'newValue': 2},
+            'removed': {'action': 'delete', 'oldValue': 2},
+        }
+    """
+    before: Optional[Dict[str, Any]]
+    """Entries of the map prior to the transaction, if observed with `with_snapshot=True`."""
     def path(self) -> List[Union[int, str]]:
         """
         Returns:
             Path to this element from the root if this YMap is nested inside another data structure.
         """
+    def clock_ranges(self) -> Dict[int, Tuple[int, int]]:
+        """
+        Returns:
+            Clients whose clocks have advanced during the transaction which produced this event,
+            mapped to a `(before, after)` pair of their clocks, like `AfterTransactionEvent.clock_ranges`.
+        """
+
+class YMapEventKeyChange(TypedDict, total=False):
+    """
+    A change of a single `YMap` entry. Added entries have no `oldValue`, while deleted ones have no
+    `newValue`.
+    """
 
-class YMapEventKeyChange(TypedDict):
     action: Literal["add", "update", "delete"]
-    oldValue: Optional[Any]
-    newValue: Optional[Any]
+    oldValue: Any
+    newValue: Any
 
 YXmlAttributes = Iterator[Tuple[str, str]]
 """Generates a sequence of key/value properties for an XML Element"""
@@ -907,13 +2491,28 @@ YXmlTreeWalker = Iterator[Xml]
 """Visits elements in an Xml tree"""
 EntryChange = Dict[Literal["action", "newValue", "oldValue"], Any]
 
-class YXmlElementEvent:
+class YXmlEvent:
+    """
+    Communicates updates that occurred during a transaction for an instance of `YXmlElement`.
+    The `target` references the `YXmlElement` that receives the update, `keys` describe
+    attribute changes and `delta` describes changes of its children, following the `YArrayEvent`
+    delta format.
+    """
+
     target: YXmlElement
     keys: Dict[str, EntryChange]
     delta: List[Dict]
     def path(self) -> List[Union[int, str]]:
         """
-        Returns a current shared type instance, that current event changes refer to.
+        Returns:
+            Array of keys and indexes creating a path from the observed shared type (a root type for `YDoc.observe_deep`)
+            down to current instance of shared type (accessible via `target` getter).
+        """
+    def clock_ranges(self) -> Dict[int, Tuple[int, int]]:
+        """
+        Returns:
+            Clients whose clocks have advanced during the transaction which produced this event,
+            mapped to a `(before, after)` pair of their clocks, like `AfterTransactionEvent.clock_ranges`.
         """
 
 class YXmlElement:
@@ -950,15 +2549,24 @@ class YXmlElement:
     ) -> YXmlElement:
         """
         Inserts a new instance of `YXmlElement` as a child of this XML node and returns it.
+
+        Raises:
+            IndexError: If `index` is past the last child.
         """
     def insert_xml_text(self, txn: YTransaction, index: int) -> YXmlText:
         """
         Inserts a new instance of `YXmlText` as a child of this XML node and returns it.
+
+        Raises:
+            IndexError: If `index` is past the last child.
         """
     def delete(self, txn: YTransaction, index: int, length: int):
         """
         Removes a range of children XML nodes from this `YXmlElement` instance,
         starting at given `index`.
+
+        Raises:
+            IndexError: If the range goes past the last child.
         """
     def push_xml_element(self, txn: YTransaction, name: str) -> YXmlElement:
         """
@@ -981,12 +2589,12 @@ class YXmlElement:
     def set_attribute(self, txn: YTransaction, name: str, value: str):
         """
         Sets a `name` and `value` as new attribute for this XML node. If an attribute with the same
-        `name` already existed on that node, its value with be overridden with a provided one.
+        `name` already existed on that node, its value will be overridden with a provided one.
         """
     def get_attribute(self, name: str) -> Optional[str]:
         """
         Returns a value of an attribute given its `name`. If no attribute with such name existed,
-        `null` will be returned.
+        `None` will be returned.
         """
     def remove_attribute(self, txn: YTransaction, name: str):
         """
@@ -995,14 +2603,23 @@ class YXmlElement:
     def attributes(self) -> YXmlAttributes:
         """
         Returns an iterator that enables to traverse over all attributes of this XML node in
-        unspecified order.
+        unspecified order, as `(name, value)` pairs. Use `dict(node.attributes())` to read them
+        into a dictionary.
+        """
+    def children(self, txn: YTransaction) -> Iterator[Xml]:
+        """
+        Returns an iterator over direct children of this XML node, in document order. Unlike
+        `tree_walker`, it doesn't descend into nested XML elements.
+
+        Args:
+            txn: A transaction in which the children are read.
         """
     def tree_walker(self) -> YXmlTreeWalker:
         """
         Returns an iterator that enables a deep traversal of this XML node - starting from first
         child over this XML node successors using depth-first strategy.
         """
-    def observe(self, f: Callable[[YXmlElementEvent]]) -> SubscriptionId:
+    def observe(self, f: Callable[[YXmlEvent]]) -> SubscriptionId:
         """
         Subscribes to all operations happening over this instance of `YXmlElement`. All changes are
         batched and eventually triggered during transaction commit phase.
@@ -1039,9 +2656,20 @@ class YXmlText:
         Returns:
             The length of an underlying string stored in this `YXmlText` instance, understood as a number of UTF-8 encoded bytes.
         """
-    def insert(self, txn: YTransaction, index: int, chunk: str):
+    def insert(
+        self,
+        txn: YTransaction,
+        index: int,
+        chunk: str,
+        attributes: Optional[Dict[str, Any]] = None,
+    ):
         """
         Inserts a given `chunk` of text into this `YXmlText` instance, starting at a given `index`.
+        Optional `attributes` (e.g. marks of a ProseMirror text node) are used to format the inserted text.
+
+        Raises:
+            TypeError: If `chunk` is not a string.
+            IndexError: If the `index` is past the end of the text.
         """
     def push(self, txn: YTransaction, chunk: str):
         """
@@ -1051,6 +2679,41 @@ class YXmlText:
         """
         Deletes a specified range of of characters, starting at a given `index`.
         Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
+
+        Raises:
+            IndexError: If the range doesn't fit within the text.
+        """
+    def format(
+        self, txn: YTransaction, index: int, length: int, attributes: Dict[str, Any]
+    ):
+        """
+        Wraps an existing piece of text within a range described by `index`-`length` parameters with
+        formatting blocks containing provided `attributes` metadata.
+
+        Raises:
+            IndexError: If the range doesn't fit within the text.
+        """
+    def to_delta(self, txn: YTransaction) -> List[YTextChangeInsert]:
+        """
+        Returns:
+            The whole content of this `YXmlText` as a list of Quill-style delta operations, in the same
+            shape as `YText.to_delta`.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_xml_text('name')
+            with doc.begin_transaction() as txn:
+                text.push(txn, 'hello world')
+                text.format(txn, 0, 5, {'strong': {}})
+                delta = text.to_delta(txn)
+
+            assert delta == [
+                {'insert': 'hello', 'attributes': {'strong': {}}},
+                {'insert': ' world'},
+            ]
         """
     def __str__(self) -> str:
         """
@@ -1065,7 +2728,7 @@ class YXmlText:
     def set_attribute(self, txn: YTransaction, name: str, value: str):
         """
         Sets a `name` and `value` as new attribute for this XML node. If an attribute with the same
-        `name` already existed on that node, its value with be overridden with a provided one.
+        `name` already existed on that node, its value will be overridden with a provided one.
         """
     def get_attribute(self, name: str) -> Optional[str]:
         """
@@ -1081,7 +2744,8 @@ class YXmlText:
         """
         Returns:
             An iterator that enables to traverse over all attributes of this XML node in
-        unspecified order.
+            unspecified order, as `(name, value)` pairs. Use `dict(node.attributes())` to read them
+            into a dictionary.
         """
     def observe(self, f: Callable[[YXmlTextEvent]]) -> SubscriptionId:
         """
@@ -1114,10 +2778,24 @@ class YXmlText:
         """
 
 class YXmlTextEvent:
+    """
+    Communicates updates that occurred during a transaction for an instance of `YXmlText`.
+    The `target` references the `YXmlText` that receives the update, `keys` describe
+    attribute changes and `delta` describes text changes, like in `YTextEvent`.
+    """
+
     target: YXmlText
-    keys: List[EntryChange]
+    keys: Dict[str, EntryChange]
     delta: List[YTextDelta]
     def path(self) -> List[Union[int, str]]:
         """
-        Returns a current shared type instance, that current event changes refer to.
+        Returns:
+            Array of keys and indexes creating a path from the observed shared type (a root type for `YDoc.observe_deep`)
+            down to current instance of shared type (accessible via `target` getter).
+        """
+    def clock_ranges(self) -> Dict[int, Tuple[int, int]]:
+        """
+        Returns:
+            Clients whose clocks have advanced during the transaction which produced this event,
+            mapped to a `(before, after)` pair of their clocks, like `AfterTransactionEvent.clock_ranges`.
         """